@@ -7,11 +7,25 @@
 //! Built into the `shared_py` extension module via maturin. Plans cross as
 //! `(evict, load)` string-list tuples; the host executes the side-effects and
 //! reports results back through `commit_*` / `mark_recovered`.
+//!
+//! `def_graph_fingerprint` exposes the `DefGraph` content hash, so Python can key caches
+//! on the same value the Rust and wasm sides compute, and `compile_workflow` turns a
+//! declarative workflow file into `DefGraph` JSON. `PortDirection` mirrors the port
+//! direction enum, whose `value` is the `"in"`/`"out"` string found in graph JSON, and
+//! `root_spec_alias` takes one to look up the alias exposing a port.
+//! `instantiate_graph` instantiates `DefGraph` JSON with job and stream ids generated by
+//! one of the built-in schemes `IdScheme` selects.
 
-use std::collections::BTreeMap;
+use std::collections::{BTreeMap, HashMap};
 
 use livestack_shared::residency::{Planner as CorePlanner, ResidencyPolicy, UnitMeta};
-use pyo3::exceptions::PyKeyError;
+use livestack_shared::systems::def_graph::{
+    try_load_from_json, PortDirection as CorePortDirection,
+};
+use livestack_shared::systems::id_scheme::{BuiltinIdScheme, HashedIds};
+use livestack_shared::systems::instantiated_graph::InstantiatedGraph;
+use livestack_shared::systems::workflow_file::{load_workflow, WorkflowFormat};
+use pyo3::exceptions::{PyKeyError, PyValueError};
 use pyo3::prelude::*;
 
 /// Residency state machine, callable from Python. Wraps the pure core planner.
@@ -100,9 +114,165 @@ impl Planner {
     }
 }
 
+/// Which side of a spec a port or alias is on.
+#[pyclass(eq, eq_int, frozen, from_py_object)]
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum PortDirection {
+    #[pyo3(name = "IN")]
+    In,
+    #[pyo3(name = "OUT")]
+    Out,
+}
+
+impl From<PortDirection> for CorePortDirection {
+    fn from(direction: PortDirection) -> Self {
+        match direction {
+            PortDirection::In => CorePortDirection::In,
+            PortDirection::Out => CorePortDirection::Out,
+        }
+    }
+}
+
+#[pymethods]
+impl PortDirection {
+    /// Parses `"in"` or `"out"`. Raises `ValueError` on anything else.
+    #[staticmethod]
+    fn parse(value: &str) -> PyResult<Self> {
+        match value.parse::<CorePortDirection>() {
+            Ok(CorePortDirection::In) => Ok(PortDirection::In),
+            Ok(CorePortDirection::Out) => Ok(PortDirection::Out),
+            Err(e) => Err(PyValueError::new_err(e.to_string())),
+        }
+    }
+
+    /// The serialized form, `"in"` or `"out"`.
+    #[getter]
+    fn value(&self) -> &'static str {
+        CorePortDirection::from(*self).as_str()
+    }
+
+    fn __str__(&self) -> &'static str {
+        self.value()
+    }
+}
+
+/// A built-in way of generating job and stream ids: readable (the default), hashed to a
+/// short hex digest, or prefixed with a namespace such as a project UUID.
+#[pyclass(eq, frozen, from_py_object)]
+#[derive(Clone, PartialEq, Eq)]
+struct IdScheme {
+    scheme: BuiltinIdScheme,
+}
+
+#[pymethods]
+impl IdScheme {
+    /// Ids such as `[ctx]Summarizer[short]`.
+    #[staticmethod]
+    fn readable() -> Self {
+        IdScheme {
+            scheme: BuiltinIdScheme::Readable,
+        }
+    }
+
+    /// The first `length` hex digits of the SHA-256 of each readable id.
+    #[staticmethod]
+    #[pyo3(signature = (length = HashedIds::DEFAULT_LENGTH))]
+    fn hashed_short(length: usize) -> Self {
+        IdScheme {
+            scheme: BuiltinIdScheme::HashedShort { length },
+        }
+    }
+
+    /// Readable ids prefixed with `"{namespace}:"`.
+    #[staticmethod]
+    fn namespaced(namespace: String) -> Self {
+        IdScheme {
+            scheme: BuiltinIdScheme::Namespaced { namespace },
+        }
+    }
+}
+
+/// Content fingerprint of a serialized `DefGraph` (the JSON `DefGraph.toJson` produces),
+/// identical to `DefGraph::fingerprint` in Rust and wasm. Raises `ValueError` if the JSON
+/// does not load.
+#[pyfunction]
+fn def_graph_fingerprint(json: String) -> PyResult<String> {
+    try_load_from_json(json)
+        .map(|graph| graph.fingerprint())
+        .map_err(|e| PyValueError::new_err(e.to_string()))
+}
+
+/// The root-spec alias exposing the `direction` port `tag` of a spec in a serialized
+/// `DefGraph`, or `None` if no alias exposes it. Raises `ValueError` if the JSON does not
+/// load.
+#[pyfunction]
+#[pyo3(signature = (def_graph_json, spec_name, tag, direction, unique_spec_label = None))]
+fn root_spec_alias(
+    def_graph_json: String,
+    spec_name: String,
+    tag: String,
+    direction: PortDirection,
+    unique_spec_label: Option<String>,
+) -> PyResult<Option<String>> {
+    let graph =
+        try_load_from_json(def_graph_json).map_err(|e| PyValueError::new_err(e.to_string()))?;
+    Ok(graph.lookup_root_spec_alias(spec_name, unique_spec_label, tag, direction.into()))
+}
+
+/// Compiles a YAML, TOML or JSON workflow document (`format` is a file extension such as
+/// `"yaml"`) into `DefGraph` JSON that `loadDefGraphFromJson` accepts. Raises `ValueError`
+/// naming the offending line when the document does not compile.
+#[pyfunction]
+fn compile_workflow(source: &str, format: &str) -> PyResult<String> {
+    let format = WorkflowFormat::from_extension(format)
+        .ok_or_else(|| PyValueError::new_err(format!("unknown workflow format: {}", format)))?;
+    let graph = load_workflow(source, format).map_err(|e| PyValueError::new_err(e.to_string()))?;
+    graph
+        .to_json()
+        .map_err(|e| PyValueError::new_err(e.to_string()))
+}
+
+/// Instantiates a serialized `DefGraph` for `context_id` and returns the `InstantiatedGraph`
+/// JSON. Job and stream ids come from `id_scheme`, or from the scheme saved with the
+/// `DefGraph` when it is omitted. Raises `ValueError` if the `DefGraph` JSON does not load
+/// or cannot be instantiated.
+#[pyfunction]
+#[pyo3(signature = (def_graph_json, context_id, root_job_id, id_scheme = None))]
+fn instantiate_graph(
+    def_graph_json: String,
+    context_id: String,
+    root_job_id: String,
+    id_scheme: Option<IdScheme>,
+) -> PyResult<String> {
+    let def_graph =
+        try_load_from_json(def_graph_json).map_err(|e| PyValueError::new_err(e.to_string()))?;
+    let graph = InstantiatedGraph::try_new_with_id_scheme(
+        context_id,
+        root_job_id,
+        HashMap::new(),
+        HashMap::new(),
+        HashMap::new(),
+        &def_graph,
+        match id_scheme {
+            Some(id_scheme) => id_scheme.scheme.build(),
+            None => def_graph.id_scheme().clone(),
+        },
+    )
+    .map_err(|e| PyValueError::new_err(e.to_string()))?;
+    graph
+        .to_json()
+        .map_err(|e| PyValueError::new_err(e.to_string()))
+}
+
 /// The `shared_py` extension module.
 #[pymodule]
 fn shared_py(m: &Bound<'_, PyModule>) -> PyResult<()> {
     m.add_class::<Planner>()?;
+    m.add_class::<PortDirection>()?;
+    m.add_class::<IdScheme>()?;
+    m.add_function(wrap_pyfunction!(def_graph_fingerprint, m)?)?;
+    m.add_function(wrap_pyfunction!(root_spec_alias, m)?)?;
+    m.add_function(wrap_pyfunction!(compile_workflow, m)?)?;
+    m.add_function(wrap_pyfunction!(instantiate_graph, m)?)?;
     Ok(())
 }
@@ -4,13 +4,20 @@
 use crate::utils;
 use js_sys::Number;
 use livestack_shared::systems::def_graph::{
-    load_from_json as load_from_json_impl, DefGraph as DefGraphImpl,
-    DefGraphNodeType as NodeTypeImpl,
+    try_load_from_json as try_load_from_json_impl, DefGraph as DefGraphImpl,
+    DefGraphNodeType as NodeTypeImpl, Durability as DurabilityImpl, EdgeMeta as EdgeMetaImpl,
+    MergePolicy as MergePolicyImpl, PortDirection as PortDirectionImpl,
 
 };
+use livestack_shared::systems::def_graph_diff::DefGraphDiff as DefGraphDiffImpl;
+use livestack_shared::systems::def_graph_topology::SpecCycle as SpecCycleImpl;
+use livestack_shared::systems::graph_render::RenderOptions;
+use livestack_shared::systems::id_scheme::BuiltinIdScheme as BuiltinIdSchemeImpl;
+use livestack_shared::systems::workflow_file::{load_workflow, WorkflowFormat};
 use livestack_shared::systems::def_graph_utils::unique_spec_identifier as unique_spec_identifier_impl;
 use livestack_shared::systems::def_graph_utils::{
-    unique_stream_identifier as unique_stream_identifier_impl,
+    parse_stream_identifier as parse_stream_identifier_impl,
+    unique_stream_identifier as unique_stream_identifier_impl, IdentifierSyntax,
     FromSpecAndTag as FromSpecAndTagImpl, SpecTagInfo as SpecTagInfoImpl,
     ToSpecAndTag as ToSpecAndTagImpl,
 };
@@ -38,6 +45,30 @@ pub enum DefGraphNodeType {
 #[serde(rename_all(serialize = "camelCase", deserialize = "camelCase"))]
 pub struct DefGraphParams {
     pub root: DefGraphSpecParams,
+    /// Generates stream definition ids; readable ids when omitted.
+    #[serde(default)]
+    #[tsify(optional)]
+    pub id_scheme: Option<IdScheme>,
+}
+
+/// One of the built-in id schemes, e.g. `{ kind: "hashedShort", length: 12 }`.
+#[derive(Tsify, Serialize, Deserialize, Debug, Clone)]
+#[tsify(into_wasm_abi, from_wasm_abi)]
+#[serde(rename_all = "camelCase", tag = "kind")]
+pub enum IdScheme {
+    Readable,
+    HashedShort { length: usize },
+    Namespaced { namespace: String },
+}
+
+impl From<IdScheme> for BuiltinIdSchemeImpl {
+    fn from(scheme: IdScheme) -> Self {
+        match scheme {
+            IdScheme::Readable => BuiltinIdSchemeImpl::Readable,
+            IdScheme::HashedShort { length } => BuiltinIdSchemeImpl::HashedShort { length },
+            IdScheme::Namespaced { namespace } => BuiltinIdSchemeImpl::Namespaced { namespace },
+        }
+    }
 }
 
 #[derive(Tsify, Serialize, Deserialize)]
@@ -66,7 +97,7 @@ pub struct SpecAndTagInfoAndDirection {
     pub spec_name: String,
     pub tag: String,
     pub unique_spec_label: Option<String>,
-    pub direction: String,
+    pub direction: PortDirection,
 }
 
 #[derive(Tsify, Serialize, Deserialize)]
@@ -81,10 +112,68 @@ pub struct DefGraphNode {
     pub has_transform: Option<bool>,
     pub stream_def_id: Option<String>,
     pub alias: Option<String>,
-    pub direction: Option<String>,
+    pub direction: Option<PortDirection>,
+    pub merge_policy: Option<MergePolicy>,
+    pub json_schema_str: Option<String>,
     pub label: String,
 }
 
+#[derive(Tsify, Serialize, Deserialize, Debug, Clone)]
+#[tsify(into_wasm_abi, from_wasm_abi)]
+#[serde(rename_all = "camelCase", tag = "kind")]
+pub enum MergePolicy {
+    Interleave,
+    RoundRobin,
+    ZipByKey { key: String },
+}
+
+impl From<&MergePolicyImpl> for MergePolicy {
+    fn from(policy: &MergePolicyImpl) -> Self {
+        match policy {
+            MergePolicyImpl::Interleave => MergePolicy::Interleave,
+            MergePolicyImpl::RoundRobin => MergePolicy::RoundRobin,
+            MergePolicyImpl::ZipByKey { key } => MergePolicy::ZipByKey { key: key.clone() },
+        }
+    }
+}
+
+impl From<MergePolicy> for MergePolicyImpl {
+    fn from(policy: MergePolicy) -> Self {
+        match policy {
+            MergePolicy::Interleave => MergePolicyImpl::Interleave,
+            MergePolicy::RoundRobin => MergePolicyImpl::RoundRobin,
+            MergePolicy::ZipByKey { key } => MergePolicyImpl::ZipByKey { key },
+        }
+    }
+}
+
+/// Which side of a spec a port or alias is on, as `"in"` or `"out"`.
+#[derive(Tsify, Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[tsify(into_wasm_abi, from_wasm_abi)]
+#[serde(rename_all = "lowercase")]
+pub enum PortDirection {
+    In,
+    Out,
+}
+
+impl From<PortDirectionImpl> for PortDirection {
+    fn from(direction: PortDirectionImpl) -> Self {
+        match direction {
+            PortDirectionImpl::In => PortDirection::In,
+            PortDirectionImpl::Out => PortDirection::Out,
+        }
+    }
+}
+
+impl From<PortDirection> for PortDirectionImpl {
+    fn from(direction: PortDirection) -> Self {
+        match direction {
+            PortDirection::In => PortDirectionImpl::In,
+            PortDirection::Out => PortDirectionImpl::Out,
+        }
+    }
+}
+
 #[wasm_bindgen]
 #[derive(Serialize, Deserialize)]
 #[serde(rename_all(serialize = "camelCase", deserialize = "camelCase"))]
@@ -137,6 +226,61 @@ pub struct GetOutboundNodeSetsResult {
 pub struct RawEdge {
     pub source: u32,
     pub target: u32,
+    pub meta: EdgeMeta,
+}
+
+#[derive(Tsify, Serialize, Deserialize, Debug, Clone, Default)]
+#[tsify(into_wasm_abi, from_wasm_abi)]
+#[serde(rename_all = "camelCase")]
+pub struct EdgeMeta {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub transform: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub buffer_limit: Option<u32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub durability: Option<Durability>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub partition_key: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub is_feedback: Option<bool>,
+}
+
+#[derive(Tsify, Serialize, Deserialize, Debug, Clone, Copy)]
+#[tsify(into_wasm_abi, from_wasm_abi)]
+#[serde(rename_all = "camelCase")]
+pub enum Durability {
+    Ephemeral,
+    Persistent,
+}
+
+impl From<&EdgeMetaImpl> for EdgeMeta {
+    fn from(meta: &EdgeMetaImpl) -> Self {
+        EdgeMeta {
+            transform: meta.transform.clone(),
+            buffer_limit: meta.buffer_limit,
+            durability: meta.durability.map(|d| match d {
+                DurabilityImpl::Ephemeral => Durability::Ephemeral,
+                DurabilityImpl::Persistent => Durability::Persistent,
+            }),
+            partition_key: meta.partition_key.clone(),
+            is_feedback: meta.is_feedback,
+        }
+    }
+}
+
+impl From<EdgeMeta> for EdgeMetaImpl {
+    fn from(meta: EdgeMeta) -> Self {
+        EdgeMetaImpl {
+            transform: meta.transform,
+            buffer_limit: meta.buffer_limit,
+            durability: meta.durability.map(|d| match d {
+                Durability::Ephemeral => DurabilityImpl::Ephemeral,
+                Durability::Persistent => DurabilityImpl::Persistent,
+            }),
+            partition_key: meta.partition_key,
+            is_feedback: meta.is_feedback,
+        }
+    }
 }
 
 #[derive(Tsify, Serialize, Deserialize)]
@@ -182,7 +326,7 @@ pub struct AssignAliasParams {
     pub tag: String,
     pub spec_name: String,
     pub unique_spec_label: Option<String>,
-    pub direction: String,
+    pub direction: PortDirection,
     pub root_spec_name: String,
 }
 
@@ -193,7 +337,20 @@ pub struct LookUpRootSpecAliasParams {
     pub spec_name: String,
     pub tag: String,
     pub unique_spec_label: Option<String>,
-    pub direction: String,
+    pub direction: PortDirection,
+}
+
+#[derive(Serialize)]
+struct SpecCycleReport {
+    accidental: Vec<SpecCycleImpl>,
+    feedback: Vec<SpecCycleImpl>,
+}
+
+#[derive(Serialize)]
+struct DiffReport {
+    #[serde(flatten)]
+    diff: DefGraphDiffImpl,
+    summary: String,
 }
 
 #[wasm_bindgen]
@@ -201,10 +358,11 @@ impl DefGraph {
     #[wasm_bindgen(constructor)]
     pub fn new(params: DefGraphParams) -> Result<DefGraph, JsValue> {
         // let params: DefGraphParams = serde_wasm_bindgen::from_value(params)?;
-        let def_graph = DefGraphImpl::new(
+        let def_graph = DefGraphImpl::new_with_id_scheme(
             params.root.name,
             params.root.input_tags,
             params.root.output_tags,
+            BuiltinIdSchemeImpl::from(params.id_scheme.unwrap_or(IdScheme::Readable)).build(),
         );
         Ok(DefGraph { def_graph })
     }
@@ -217,6 +375,31 @@ impl DefGraph {
             .expect("Failed to serialize DefGraph to JSON");
     }
 
+    /// Renders the graph as Graphviz DOT. With `collapsePorts`, inlets and outlets become
+    /// edge labels.
+    #[wasm_bindgen(js_name = toDot)]
+    pub fn to_dot(&self, collapse_ports: Option<bool>) -> String {
+        self.def_graph.to_dot(&RenderOptions {
+            collapse_ports: collapse_ports.unwrap_or(false),
+        })
+    }
+
+    /// Renders the graph as a Mermaid flowchart. With `collapsePorts`, inlets and outlets
+    /// become edge labels.
+    #[wasm_bindgen(js_name = toMermaid)]
+    pub fn to_mermaid(&self, collapse_ports: Option<bool>) -> String {
+        self.def_graph.to_mermaid(&RenderOptions {
+            collapse_ports: collapse_ports.unwrap_or(false),
+        })
+    }
+
+    /// SHA-256 hex of the graph's semantic content; equal for graphs describing the same
+    /// workflow regardless of how they were built. Matches the Rust and Python values.
+    #[wasm_bindgen(js_name = fingerprint)]
+    pub fn fingerprint(&self) -> String {
+        self.def_graph.fingerprint()
+    }
+
     #[wasm_bindgen(js_name = getSpecNodeIds)]
     pub fn get_spec_node_ids(&self) -> Vec<Number> {
         return self.def_graph.get_spec_node_ids().into_iter().map(|e| Number::from(e)).collect();
@@ -231,6 +414,13 @@ impl DefGraph {
         return Number::from(root_id);
     }
 
+    /// Node id of the spec instance with the given name and label, if any.
+    #[wasm_bindgen(js_name = findSpecNode)]
+    pub fn find_spec_node(&self, spec_name: String, unique_spec_label: Option<String>) -> Option<u32> {
+        self.def_graph
+            .find_spec_node(&spec_name, unique_spec_label.as_deref())
+    }
+
     #[wasm_bindgen(js_name = getNodeAttributes)]
     pub fn get_node_attributes(&self, node_id: u32) -> DefGraphNode {
         let node = self.def_graph.node_weight(node_id);
@@ -253,7 +443,9 @@ impl DefGraph {
                     has_transform: node.has_transform,
                     stream_def_id: node.stream_def_id.clone(),
                     alias: node.alias.clone(),
-                    direction: node.direction.clone(),
+                    direction: node.direction.map(PortDirection::from),
+                    merge_policy: node.merge_policy.as_ref().map(MergePolicy::from),
+                    json_schema_str: node.json_schema_str.clone(),
                     label: node.label.clone(),
                 };
             }
@@ -264,9 +456,15 @@ impl DefGraph {
     }
 
     #[wasm_bindgen(js_name = getInboundStreamNodes)]
-    pub fn get_inbound_stream_nodes(&self, node_id: u32) -> GetInboundNodeSetsResult {
+    pub fn get_inbound_stream_nodes(
+        &self,
+        node_id: u32,
+    ) -> Result<GetInboundNodeSetsResult, JsError> {
         let mut results = vec![];
-        let inbound_node_sets = self.def_graph.get_inbound_stream_nodes(node_id);
+        let inbound_node_sets = self
+            .def_graph
+            .get_inbound_stream_nodes(node_id)
+            .map_err(|e| JsError::new(&e.to_string()))?;
         for (inlet_node_id, stream_node_id) in inbound_node_sets {
             let inlet_node = self
                 .def_graph
@@ -286,7 +484,9 @@ impl DefGraph {
                     has_transform: inlet_node.has_transform,
                     stream_def_id: inlet_node.stream_def_id.clone(),
                     alias: inlet_node.alias.clone(),
-                    direction: inlet_node.direction.clone(),
+                    direction: inlet_node.direction.map(PortDirection::from),
+                    merge_policy: inlet_node.merge_policy.as_ref().map(MergePolicy::from),
+                    json_schema_str: inlet_node.json_schema_str.clone(),
                     label: inlet_node.label.clone(),
                 },
                 stream_node: DefGraphNode {
@@ -298,19 +498,27 @@ impl DefGraph {
                     has_transform: stream_node.has_transform,
                     stream_def_id: stream_node.stream_def_id.clone(),
                     alias: stream_node.alias.clone(),
-                    direction: stream_node.direction.clone(),
+                    direction: stream_node.direction.map(PortDirection::from),
+                    merge_policy: stream_node.merge_policy.as_ref().map(MergePolicy::from),
+                    json_schema_str: stream_node.json_schema_str.clone(),
                     label: stream_node.label.clone(),
                 },
             });
         }
 
-        return GetInboundNodeSetsResult { results: results };
+        return Ok(GetInboundNodeSetsResult { results: results });
     }
 
     #[wasm_bindgen(js_name = getOutboundStreamNodes)]
-    pub fn get_outbound_stream_nodes(&self, node_id: u32) -> GetOutboundNodeSetsResult {
+    pub fn get_outbound_stream_nodes(
+        &self,
+        node_id: u32,
+    ) -> Result<GetOutboundNodeSetsResult, JsError> {
         let mut results = vec![];
-        let outbound_node_sets = self.def_graph.get_outbound_stream_nodes(node_id);
+        let outbound_node_sets = self
+            .def_graph
+            .get_outbound_stream_nodes(node_id)
+            .map_err(|e| JsError::new(&e.to_string()))?;
         for (outlet_node_id, stream_node_id) in outbound_node_sets {
             let outlet_node = self
                 .def_graph
@@ -330,7 +538,9 @@ impl DefGraph {
                     has_transform: outlet_node.has_transform,
                     stream_def_id: outlet_node.stream_def_id.clone(),
                     alias: outlet_node.alias.clone(),
-                    direction: outlet_node.direction.clone(),
+                    direction: outlet_node.direction.map(PortDirection::from),
+                    merge_policy: outlet_node.merge_policy.as_ref().map(MergePolicy::from),
+                    json_schema_str: outlet_node.json_schema_str.clone(),
                     label: outlet_node.label.clone(),
                 },
                 stream_node: DefGraphNode {
@@ -342,12 +552,14 @@ impl DefGraph {
                     has_transform: stream_node.has_transform,
                     stream_def_id: stream_node.stream_def_id.clone(),
                     alias: stream_node.alias.clone(),
-                    direction: stream_node.direction.clone(),
+                    direction: stream_node.direction.map(PortDirection::from),
+                    merge_policy: stream_node.merge_policy.as_ref().map(MergePolicy::from),
+                    json_schema_str: stream_node.json_schema_str.clone(),
                     label: stream_node.label.clone(),
                 },
             });
         }
-        return GetOutboundNodeSetsResult { results: results };
+        return Ok(GetOutboundNodeSetsResult { results: results });
     }
 
     #[wasm_bindgen(js_name = nodes)]
@@ -374,59 +586,86 @@ impl DefGraph {
             .map(|edge| RawEdge {
                 source: edge.0,
                 target: edge.1,
+                meta: self
+                    .def_graph
+                    .edge_meta(edge.0, edge.1)
+                    .map(EdgeMeta::from)
+                    .unwrap_or_default(),
             })
             .collect();
         return EdgesResults { results: results };
     }
 
+    /// Connects `from` to `to`. `meta` holds settings for this connection only.
     #[wasm_bindgen(js_name = addConnectedDualSpecs)]
-    pub fn add_connected_dual_specs(&mut self, from: FromSpecAndTag, to: ToSpecAndTag) {
-        self.def_graph.add_connected_dual_specs(
-            &FromSpecAndTagImpl {
-                spec_name: from.spec_name,
-                output: from.output,
-                unique_spec_label: from.unique_spec_label,
-            },
-            &ToSpecAndTagImpl {
-                spec_name: to.spec_name,
-                input: to.input,
-                has_transform: to.has_transform,
-                unique_spec_label: to.unique_spec_label,
-            },
-        );
+    pub fn add_connected_dual_specs(
+        &mut self,
+        from: FromSpecAndTag,
+        to: ToSpecAndTag,
+        meta: Option<EdgeMeta>,
+    ) -> Result<(), JsError> {
+        self.def_graph
+            .try_add_connected_dual_specs_with_meta(
+                &FromSpecAndTagImpl {
+                    spec_name: from.spec_name,
+                    output: from.output,
+                    unique_spec_label: from.unique_spec_label,
+                },
+                &ToSpecAndTagImpl {
+                    spec_name: to.spec_name,
+                    input: to.input,
+                    has_transform: to.has_transform,
+                    unique_spec_label: to.unique_spec_label,
+                },
+                meta.map(EdgeMetaImpl::from).unwrap_or_default(),
+            )
+            .map(|_| ())
+            .map_err(|e| JsError::new(&e.to_string()))
     }
 
     #[wasm_bindgen(js_name = ensureOutletAndStream)]
-    pub fn ensure_outlet_and_stream(&mut self, s: SpecAndTag) {
-        self.def_graph.ensure_outlet_and_stream(SpecTagInfoImpl {
-            spec_name: s.spec_name,
-            tag: s.tag,
-            unique_spec_label: s.unique_spec_label,
-        });
-    }
-
-    #[wasm_bindgen(js_name = ensureInletAndStream)]
-    pub fn ensure_inlet_and_stream(&mut self, s: SpecAndTag, has_transform: bool) {
-        self.def_graph.ensure_inlet_and_stream(
-            SpecTagInfoImpl {
+    pub fn ensure_outlet_and_stream(&mut self, s: SpecAndTag) -> Result<(), JsError> {
+        self.def_graph
+            .try_ensure_outlet_and_stream(SpecTagInfoImpl {
                 spec_name: s.spec_name,
                 tag: s.tag,
                 unique_spec_label: s.unique_spec_label,
-            },
-            has_transform,
-        );
+            })
+            .map(|_| ())
+            .map_err(|e| JsError::new(&e.to_string()))
+    }
+
+    #[wasm_bindgen(js_name = ensureInletAndStream)]
+    pub fn ensure_inlet_and_stream(
+        &mut self,
+        s: SpecAndTag,
+        has_transform: bool,
+    ) -> Result<(), JsError> {
+        self.def_graph
+            .try_ensure_inlet_and_stream(
+                SpecTagInfoImpl {
+                    spec_name: s.spec_name,
+                    tag: s.tag,
+                    unique_spec_label: s.unique_spec_label,
+                },
+                has_transform,
+            )
+            .map(|_| ())
+            .map_err(|e| JsError::new(&e.to_string()))
     }
 
     #[wasm_bindgen(js_name = assignAlias)]
-    pub fn assign_alias(&mut self, p: AssignAliasParams) {
-        self.def_graph.assign_alias(
-            &p.alias,
-            &p.spec_name,
-            &p.root_spec_name,
-            p.unique_spec_label.as_deref(),
-            &p.direction,
-            &p.tag,
-        );
+    pub fn assign_alias(&mut self, p: AssignAliasParams) -> Result<(), JsError> {
+        self.def_graph
+            .try_assign_alias(
+                &p.alias,
+                &p.spec_name,
+                &p.root_spec_name,
+                p.unique_spec_label.as_deref(),
+                p.direction.into(),
+                &p.tag,
+            )
+            .map_err(|e| JsError::new(&e.to_string()))
     }
 
     #[wasm_bindgen(js_name = getAllAliasNodeIds)]
@@ -440,7 +679,7 @@ impl DefGraph {
             p.spec_name,
             p.unique_spec_label,
             p.tag,
-            p.direction,
+            p.direction.into(),
         );
     }
 
@@ -448,11 +687,11 @@ impl DefGraph {
     pub fn lookup_spec_and_tag_by_alias(
         &self,
         alias: String,
-        direction: String,
+        direction: PortDirection,
     ) -> Option<SpecAndTagInfoAndDirection> {
         let info = self
             .def_graph
-            .lookup_spec_and_tag_by_alias(alias, direction.as_str());
+            .lookup_spec_and_tag_by_alias(alias, direction.into());
 
         match info {
             Some(info) => {
@@ -475,14 +714,347 @@ impl DefGraph {
         let connections = self.def_graph.get_nodes_connected_to_stream(stream_node_id);
         serde_wasm_bindgen::to_value(&connections).unwrap()
     }
+
+    /// Marks (or unmarks) the connection from `from` to `to` as an intended feedback loop.
+    #[wasm_bindgen(js_name = markFeedbackConnection)]
+    pub fn mark_feedback_connection(
+        &mut self,
+        from: SpecAndTag,
+        to: SpecAndTag,
+        is_feedback: bool,
+    ) -> Result<(), JsError> {
+        self.def_graph
+            .try_mark_feedback_connection(
+                &SpecTagInfoImpl {
+                    spec_name: from.spec_name,
+                    tag: from.tag,
+                    unique_spec_label: from.unique_spec_label,
+                },
+                &SpecTagInfoImpl {
+                    spec_name: to.spec_name,
+                    tag: to.tag,
+                    unique_spec_label: to.unique_spec_label,
+                },
+                is_feedback,
+            )
+            .map_err(|e| JsError::new(&e.to_string()))
+    }
+
+    /// Replaces the settings of an existing connection.
+    #[wasm_bindgen(js_name = setConnectionMeta)]
+    pub fn set_connection_meta(
+        &mut self,
+        from: SpecAndTag,
+        to: SpecAndTag,
+        meta: EdgeMeta,
+    ) -> Result<(), JsError> {
+        self.def_graph
+            .set_connection_meta(
+                &SpecTagInfoImpl {
+                    spec_name: from.spec_name,
+                    tag: from.tag,
+                    unique_spec_label: from.unique_spec_label,
+                },
+                &SpecTagInfoImpl {
+                    spec_name: to.spec_name,
+                    tag: to.tag,
+                    unique_spec_label: to.unique_spec_label,
+                },
+                meta.into(),
+            )
+            .map_err(|e| JsError::new(&e.to_string()))
+    }
+
+    /// Returns the settings of an existing connection.
+    #[wasm_bindgen(js_name = connectionMeta)]
+    pub fn connection_meta(&self, from: SpecAndTag, to: SpecAndTag) -> Result<EdgeMeta, JsError> {
+        self.def_graph
+            .connection_meta(
+                &SpecTagInfoImpl {
+                    spec_name: from.spec_name,
+                    tag: from.tag,
+                    unique_spec_label: from.unique_spec_label,
+                },
+                &SpecTagInfoImpl {
+                    spec_name: to.spec_name,
+                    tag: to.tag,
+                    unique_spec_label: to.unique_spec_label,
+                },
+            )
+            .map(EdgeMeta::from)
+            .map_err(|e| JsError::new(&e.to_string()))
+    }
+
+    /// Sets the JSON Schema of a port. Pass `undefined` to clear it. Throws if it makes an existing connection without a transform incompatible.
+    #[wasm_bindgen(js_name = setPortSchema)]
+    pub fn set_port_schema(
+        &mut self,
+        s: SpecAndTag,
+        direction: PortDirection,
+        json_schema_str: Option<String>,
+    ) -> Result<(), JsError> {
+        self.def_graph
+            .set_port_schema(
+                &SpecTagInfoImpl {
+                    spec_name: s.spec_name,
+                    tag: s.tag,
+                    unique_spec_label: s.unique_spec_label,
+                },
+                direction.into(),
+                json_schema_str,
+            )
+            .map_err(|e| JsError::new(&e.to_string()))
+    }
+
+    /// Returns the JSON Schema of a port, or `undefined` if none was set.
+    #[wasm_bindgen(js_name = portSchema)]
+    pub fn port_schema(
+        &self,
+        s: SpecAndTag,
+        direction: PortDirection,
+    ) -> Result<Option<String>, JsError> {
+        self.def_graph
+            .port_schema(
+                &SpecTagInfoImpl {
+                    spec_name: s.spec_name,
+                    tag: s.tag,
+                    unique_spec_label: s.unique_spec_label,
+                },
+                direction.into(),
+            )
+            .map(|schema| schema.map(str::to_string))
+            .map_err(|e| JsError::new(&e.to_string()))
+    }
+
+    /// Declares how an inlet fed by several streams merges them. Pass `undefined` to clear it.
+    #[wasm_bindgen(js_name = setInletMergePolicy)]
+    pub fn set_inlet_merge_policy(
+        &mut self,
+        s: SpecAndTag,
+        merge_policy: Option<MergePolicy>,
+    ) -> Result<(), JsError> {
+        self.def_graph
+            .set_inlet_merge_policy(
+                &SpecTagInfoImpl {
+                    spec_name: s.spec_name,
+                    tag: s.tag,
+                    unique_spec_label: s.unique_spec_label,
+                },
+                merge_policy.map(MergePolicyImpl::from),
+            )
+            .map_err(|e| JsError::new(&e.to_string()))
+    }
+
+    /// Spec node ids in data-flow order. Throws, naming the specs and streams involved,
+    /// if the graph contains a loop that is not marked as feedback.
+    #[wasm_bindgen(js_name = topologicalSpecOrder)]
+    pub fn topological_spec_order(&self) -> Result<Vec<u32>, JsError> {
+        self.def_graph.topological_spec_order().map_err(|cycles| {
+            let described = cycles
+                .iter()
+                .map(|c| {
+                    format!(
+                        "[{}] via [{}]",
+                        c.spec_identifiers.join(", "),
+                        c.stream_def_ids.join(", ")
+                    )
+                })
+                .collect::<Vec<_>>()
+                .join("; ");
+            JsError::new(&format!("Unmarked cycle(s) between specs: {}", described))
+        })
+    }
+
+    /// Returns `{ accidental, feedback }`, each a list of `{ specNodeIds, specIdentifiers, streamDefIds }`.
+    #[wasm_bindgen(js_name = findSpecCycles)]
+    pub fn find_spec_cycles(&self) -> Result<JsValue, JsError> {
+        let report = SpecCycleReport {
+            accidental: self.def_graph.find_spec_cycles(),
+            feedback: self.def_graph.find_feedback_cycles(),
+        };
+        serde_wasm_bindgen::to_value(&report).map_err(|e| JsError::new(&e.to_string()))
+    }
+
+    /// Specs, streams and root output aliases fed, directly or not, by the given spec or
+    /// stream def node.
+    #[wasm_bindgen(js_name = downstreamOf)]
+    pub fn downstream_of(&self, node_id: u32) -> Result<JsValue, JsError> {
+        let reachable = self
+            .def_graph
+            .downstream_of(node_id)
+            .map_err(|e| JsError::new(&e.to_string()))?;
+        serde_wasm_bindgen::to_value(&reachable).map_err(|e| JsError::new(&e.to_string()))
+    }
+
+    /// Specs, streams and root input aliases feeding, directly or not, the given spec or
+    /// stream def node.
+    #[wasm_bindgen(js_name = upstreamOf)]
+    pub fn upstream_of(&self, node_id: u32) -> Result<JsValue, JsError> {
+        let reachable = self
+            .def_graph
+            .upstream_of(node_id)
+            .map_err(|e| JsError::new(&e.to_string()))?;
+        serde_wasm_bindgen::to_value(&reachable).map_err(|e| JsError::new(&e.to_string()))
+    }
+
+    /// Every route between two spec nodes that visits no spec twice, each as the list of
+    /// spec connections taken.
+    #[wasm_bindgen(js_name = specPaths)]
+    pub fn spec_paths(&self, from: u32, to: u32) -> Result<JsValue, JsError> {
+        let paths = self
+            .def_graph
+            .spec_paths(from, to)
+            .map_err(|e| JsError::new(&e.to_string()))?;
+        serde_wasm_bindgen::to_value(&paths).map_err(|e| JsError::new(&e.to_string()))
+    }
+
+    /// Removes a spec with its ports and aliases. Returns the removed node ids; the ids of
+    /// all other nodes are unchanged.
+    #[wasm_bindgen(js_name = removeSpec)]
+    pub fn remove_spec(
+        &mut self,
+        spec_name: String,
+        unique_spec_label: Option<String>,
+    ) -> Result<Vec<u32>, JsError> {
+        self.def_graph
+            .remove_spec(&spec_name, unique_spec_label.as_deref())
+            .map_err(|e| JsError::new(&e.to_string()))
+    }
+
+    /// Cuts the stream from `from`'s outlet into `to`'s inlet. Returns the removed node ids.
+    #[wasm_bindgen(js_name = disconnect)]
+    pub fn disconnect(&mut self, from: SpecAndTag, to: SpecAndTag) -> Result<Vec<u32>, JsError> {
+        self.def_graph
+            .disconnect(
+                &SpecTagInfoImpl {
+                    spec_name: from.spec_name,
+                    tag: from.tag,
+                    unique_spec_label: from.unique_spec_label,
+                },
+                &SpecTagInfoImpl {
+                    spec_name: to.spec_name,
+                    tag: to.tag,
+                    unique_spec_label: to.unique_spec_label,
+                },
+            )
+            .map_err(|e| JsError::new(&e.to_string()))
+    }
+
+    /// Removes a root-spec alias. Returns the removed alias node id.
+    #[wasm_bindgen(js_name = removeAlias)]
+    pub fn remove_alias(&mut self, alias: String, direction: PortDirection) -> Result<u32, JsError> {
+        self.def_graph
+            .remove_alias(&alias, direction.into())
+            .map_err(|e| JsError::new(&e.to_string()))
+    }
+
+    /// Renames a root-spec alias, keeping the port it exposes. Throws if the new name is
+    /// already in use. Returns the alias node id.
+    #[wasm_bindgen(js_name = renameAlias)]
+    pub fn rename_alias(
+        &mut self,
+        alias: String,
+        direction: PortDirection,
+        new_alias: String,
+    ) -> Result<u32, JsError> {
+        self.def_graph
+            .rename_alias(&alias, direction.into(), &new_alias)
+            .map_err(|e| JsError::new(&e.to_string()))
+    }
+
+    /// Every root-spec alias with its direction and the spec port it exposes: the public
+    /// inputs and outputs of the workflow.
+    #[wasm_bindgen(js_name = aliases)]
+    pub fn aliases(&self) -> Result<JsValue, JsError> {
+        serde_wasm_bindgen::to_value(&self.def_graph.aliases())
+            .map_err(|e| JsError::new(&e.to_string()))
+    }
+
+    /// Returns what changed from this graph to `other`, matched by spec, stream and alias
+    /// identity. The `summary` field holds the same changes as readable lines.
+    #[wasm_bindgen(js_name = diff)]
+    pub fn diff(&self, other: &DefGraph) -> Result<JsValue, JsError> {
+        let diff = self.def_graph.diff(&other.def_graph);
+        let report = DiffReport {
+            summary: diff.to_string(),
+            diff,
+        };
+        serde_wasm_bindgen::to_value(&report).map_err(|e| JsError::new(&e.to_string()))
+    }
+
+    /// Embeds `child` as a composite spec whose ports are the child's root aliases.
+    /// Returns the composite's spec node id.
+    #[wasm_bindgen(js_name = addCompositeSpec)]
+    pub fn add_composite_spec(
+        &mut self,
+        child: &DefGraph,
+        unique_spec_label: Option<String>,
+    ) -> Result<u32, JsError> {
+        self.def_graph
+            .try_add_composite_spec(child.def_graph.clone(), unique_spec_label.as_deref())
+            .map_err(|e| JsError::new(&e.to_string()))
+    }
+
+    /// The graph behind a composite spec, for drilling into the nested view.
+    #[wasm_bindgen(js_name = getComposite)]
+    pub fn get_composite(
+        &self,
+        spec_name: String,
+        unique_spec_label: Option<String>,
+    ) -> Option<DefGraph> {
+        self.def_graph
+            .get_composite(&spec_name, unique_spec_label.as_deref())
+            .map(|def_graph| DefGraph {
+                def_graph: def_graph.clone(),
+            })
+    }
+
+    #[wasm_bindgen(js_name = getCompositeSpecIdentifiers)]
+    pub fn get_composite_spec_identifiers(&self) -> Vec<String> {
+        self.def_graph.get_composite_spec_identifiers()
+    }
+
+    /// Returns a copy with every composite replaced by its inner specs.
+    #[wasm_bindgen(js_name = flatten)]
+    pub fn flatten(&self) -> Result<DefGraph, JsError> {
+        let def_graph = self
+            .def_graph
+            .flatten()
+            .map_err(|e| JsError::new(&e.to_string()))?;
+        Ok(DefGraph { def_graph })
+    }
+
+    /// Returns every structural problem in the graph as `{ kind, ...details }` objects.
+    #[wasm_bindgen(js_name = validate)]
+    pub fn validate(&self) -> Result<JsValue, JsError> {
+        let diagnostics = self.def_graph.validate();
+        serde_wasm_bindgen::to_value(&diagnostics).map_err(|e| JsError::new(&e.to_string()))
+    }
+
+    /// Returns legal but suspicious shapes as `{ code, severity, nodeId, message }` objects,
+    /// ordered by node id. Codes are stable, e.g. `"unconnected-outlet"`.
+    #[wasm_bindgen(js_name = lint)]
+    pub fn lint(&self) -> Result<JsValue, JsError> {
+        serde_wasm_bindgen::to_value(&self.def_graph.lint()).map_err(|e| JsError::new(&e.to_string()))
+    }
 }
 
 #[wasm_bindgen(js_name = loadDefGraphFromJson)]
-pub fn load_def_graph_from_json(json: String) -> DefGraph {
-  set_panic_hook();
-    return DefGraph {
-        def_graph: load_from_json_impl(json),
-    };
+pub fn load_def_graph_from_json(json: String) -> Result<DefGraph, JsError> {
+    set_panic_hook();
+    let def_graph = try_load_from_json_impl(json).map_err(|e| JsError::new(&e.to_string()))?;
+    Ok(DefGraph { def_graph })
+}
+
+/// Compiles a YAML, TOML or JSON workflow document. `format` is a file extension such as
+/// `"yaml"`; errors name the line of the offending entry.
+#[wasm_bindgen(js_name = loadDefGraphFromWorkflow)]
+pub fn load_def_graph_from_workflow(source: String, format: String) -> Result<DefGraph, JsError> {
+    set_panic_hook();
+    let format = WorkflowFormat::from_extension(&format)
+        .ok_or_else(|| JsError::new(&format!("unknown workflow format: {}", format)))?;
+    let def_graph = load_workflow(&source, format).map_err(|e| JsError::new(&e.to_string()))?;
+    Ok(DefGraph { def_graph })
 }
 
 #[wasm_bindgen(js_name = genSpecIdentifier)]
@@ -510,3 +1082,28 @@ pub fn unique_stream_identifier(p: UniqueStreamIdentifierParams) -> String {
     };
     return unique_stream_identifier_impl(from, to);
 }
+
+/// Splits a stream id back into its two sides. Pass `legacy` for ids stored before spec and
+/// stream ids were escaped.
+#[wasm_bindgen(js_name = parseStreamIdentifier)]
+pub fn parse_stream_identifier(
+    id: String,
+    legacy: Option<bool>,
+) -> Result<UniqueStreamIdentifierParams, JsError> {
+    let syntax = if legacy.unwrap_or(false) {
+        IdentifierSyntax::Legacy
+    } else {
+        IdentifierSyntax::Escaped
+    };
+    let (from, to) =
+        parse_stream_identifier_impl(&id, syntax).map_err(|e| JsError::new(&e.to_string()))?;
+    let params = |side: SpecTagInfoImpl| SpecTagInfoParams {
+        spec_name: side.spec_name,
+        unique_spec_label: side.unique_spec_label,
+        tag: side.tag,
+    };
+    Ok(UniqueStreamIdentifierParams {
+        from: from.map(params),
+        to: to.map(params),
+    })
+}
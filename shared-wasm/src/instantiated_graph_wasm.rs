@@ -9,7 +9,9 @@ use serde_wasm_bindgen::{from_value, to_value};
 use std::collections::HashMap;
 
 // Import the Rust definitions from your shared crate...
-use livestack_shared::systems::def_graph::load_from_json as load_def_graph_from_json_impl;
+use livestack_shared::systems::def_graph::try_load_from_json as try_load_def_graph_from_json_impl;
+use livestack_shared::systems::graph_render::RenderOptions;
+use livestack_shared::systems::id_scheme::BuiltinIdScheme as BuiltinIdSchemeImpl;
 use livestack_shared::systems::instantiated_graph::{
     InstantiatedGraph as InstantiatedGraphImpl, 
     InstantiatedGraphNode as InstantiatedGraphNodeImpl, 
@@ -17,6 +19,7 @@ use livestack_shared::systems::instantiated_graph::{
     StreamSourceSpecType, 
 };
 // (Optional) if you have a panic hook for better debugging:
+use crate::def_graph_wasm::{EdgeMeta, IdScheme, MergePolicy, PortDirection};
 use crate::utils::set_panic_hook;  
 
 /// A TS-friendly version of `InstantiatedNodeType`.
@@ -45,7 +48,8 @@ pub struct InstantiatedGraphNodeWasm {
     pub tag: Option<String>,
     pub has_transform: Option<bool>,
     pub alias: Option<String>,
-    pub direction: Option<String>,
+    pub direction: Option<PortDirection>,
+    pub merge_policy: Option<MergePolicy>,
     pub label: String,
 }
 
@@ -86,7 +90,10 @@ impl InstantiatedGraphWasm {
     /// Create a new InstantiatedGraphWasm by:
     ///  - Deserializing a `DefGraph` from JSON,
     ///  - Parsing the stream/inlet overrides from JS objects,
+    ///  - Picking the id scheme for job and stream ids, the DefGraph's own when omitted,
     ///  - Constructing the internal Rust `InstantiatedGraph`.
+    ///
+    /// A malformed DefGraph throws an `Error` naming the node at fault.
     #[wasm_bindgen(constructor)]
     pub fn new(
         context_id: String,               // formerly "contextId"
@@ -95,12 +102,14 @@ impl InstantiatedGraphWasm {
         streamIdOverrides: JsValue,       // a JS object => parse into HashMap<String,String>
         inletHasTransformOverridesByTag: JsValue, // a JS object => parse into HashMap<String,bool>
         streamSourceSpecTypeByStreamId: JsValue,  // a JS object => parse into HashMap<String,(String,String)>
+        idScheme: JsValue,                // optional IdScheme; defaults to the DefGraph's
     ) -> Result<InstantiatedGraphWasm, JsError> {
         // For better rust panic messages in the JS console (optional):
         set_panic_hook();
 
         // 1) Reconstruct the DefGraph from the JSON string.
-        let def_graph = load_def_graph_from_json_impl(def_graph_json);
+        let def_graph = try_load_def_graph_from_json_impl(def_graph_json)
+            .map_err(|e| JsError::new(&e.to_string()))?;
 
         // 2) Parse the override maps from JS Values --> Rust HashMaps:
         let parsed_stream_id_overrides: HashMap<String, String> =
@@ -109,16 +118,23 @@ impl InstantiatedGraphWasm {
             from_value(inletHasTransformOverridesByTag).map_err(|e| JsError::new(&e.to_string()))?;
         let parsed_source_spec_type_map: HashMap<String, StreamSourceSpecType> =
             from_value(streamSourceSpecTypeByStreamId).map_err(|e| JsError::new(&e.to_string()))?;
+        let id_scheme: Option<IdScheme> =
+            from_value(idScheme).map_err(|e| JsError::new(&e.to_string()))?;
 
         // 3) Build our Rust InstantiatedGraph from the reconstructed DefGraph + overrides:
-        let inst_graph = InstantiatedGraphImpl::new(
+        let inst_graph = InstantiatedGraphImpl::try_new_with_id_scheme(
             context_id,
             root_job_id,
             parsed_stream_id_overrides,
             parsed_inlet_transform_map,
             parsed_source_spec_type_map,
             &def_graph,
-        );
+            match id_scheme {
+                Some(id_scheme) => BuiltinIdSchemeImpl::from(id_scheme).build(),
+                None => def_graph.id_scheme().clone(),
+            },
+        )
+        .map_err(|e| JsError::new(&e.to_string()))?;
 
         Ok(InstantiatedGraphWasm { inst_graph })
     }
@@ -158,7 +174,8 @@ impl InstantiatedGraphWasm {
             tag: node.tag.clone(),
             has_transform: node.has_transform,
                     alias: node.alias.clone(),
-                    direction: node.direction.clone(),
+                    direction: node.direction.map(PortDirection::from),
+                    merge_policy: node.merge_policy.as_ref().map(MergePolicy::from),
                     label: node.label.clone(),
                 };
             }
@@ -226,11 +243,11 @@ impl InstantiatedGraphWasm {
     pub fn find_stream_node_id_connected_to_job(
         &self,
         job_id: String,
-        direction: String,
+        direction: PortDirection,
         tag: String,
     ) -> Option<u32> {
         self.inst_graph
-            .find_stream_node_id_connected_to_job(&job_id, &direction, &tag)
+            .find_stream_node_id_connected_to_job(&job_id, direction.into(), &tag)
     }
 
     /// Return an array of edge IDs that enter the given nodeId.
@@ -247,6 +264,12 @@ impl InstantiatedGraphWasm {
         self.inst_graph.outbound_edges(node_id).into_iter().map(|e| Number::from(e)).collect()
     }
 
+    /// Return the connection settings carried by the given edge ID, if it exists.
+    #[wasm_bindgen(js_name = edgeMeta)]
+    pub fn edge_meta(&self, edge_id: u32) -> Option<EdgeMeta> {
+        self.inst_graph.edge_meta(edge_id).map(EdgeMeta::from)
+    }
+
     /// Return the source node ID of the given edge ID.
     #[wasm_bindgen(js_name = source)]
     pub fn source(&self, edge_id: u32) -> u32 {
@@ -278,12 +301,45 @@ impl InstantiatedGraphWasm {
         self.inst_graph.get_root_job_node_id()
     }
 
+    /// Re-instantiate from a changed DefGraph (as JSON) in place, keeping the ids and node ids
+    /// of jobs and streams that still exist. Returns the `InstantiationDelta` of added,
+    /// removed and rewired jobs, streams and inlets; throws if the DefGraph cannot be
+    /// instantiated.
+    #[wasm_bindgen(js_name = applyDefGraphChange)]
+    pub fn apply_def_graph_change(&mut self, def_graph_json: String) -> Result<JsValue, JsError> {
+        let def_graph = try_load_def_graph_from_json_impl(def_graph_json)
+            .map_err(|e| JsError::new(&e.to_string()))?;
+        let delta = self
+            .inst_graph
+            .apply_def_graph_change(&def_graph)
+            .map_err(|e| JsError::new(&e.to_string()))?;
+        to_value(&delta).map_err(|e| JsError::new(&e.to_string()))
+    }
+
     /// Return the serialized JSON string of the InstantiatedGraph.
     #[wasm_bindgen(js_name = toJson)]
     pub fn to_json(&self) -> String {
         self.inst_graph.to_json().expect("Failed to serialize InstantiatedGraph to JSON")
     }
 
+    /// Render the graph as Graphviz DOT. With `collapsePorts`, inlets and outlets become
+    /// edge labels.
+    #[wasm_bindgen(js_name = toDot)]
+    pub fn to_dot(&self, collapse_ports: Option<bool>) -> String {
+        self.inst_graph.to_dot(&RenderOptions {
+            collapse_ports: collapse_ports.unwrap_or(false),
+        })
+    }
+
+    /// Render the graph as a Mermaid flowchart. With `collapsePorts`, inlets and outlets
+    /// become edge labels.
+    #[wasm_bindgen(js_name = toMermaid)]
+    pub fn to_mermaid(&self, collapse_ports: Option<bool>) -> String {
+        self.inst_graph.to_mermaid(&RenderOptions {
+            collapse_ports: collapse_ports.unwrap_or(false),
+        })
+    }
+
     /// Return an array of edge IDs.
     #[wasm_bindgen(js_name = edgesPrintout)]
     pub fn edges_printout(&self) -> String {
@@ -294,6 +350,14 @@ impl InstantiatedGraphWasm {
 
 }
 
+/// Restore an InstantiatedGraph saved with `toJson`, in any supported format version.
+#[wasm_bindgen(js_name = loadInstantiatedGraphFromJson)]
+pub fn load_instantiated_graph_from_json(json: String) -> Result<InstantiatedGraphWasm, JsError> {
+    let inst_graph =
+        InstantiatedGraphImpl::from_json(&json).map_err(|e| JsError::new(&e.to_string()))?;
+    Ok(InstantiatedGraphWasm { inst_graph })
+}
+
 // Helper for converting from internal Rust node -> Wasm-friendly node
 impl InstantiatedGraphWasm {
     fn graph_node_to_wasm(&self, node: &InstantiatedGraphNodeImpl) -> InstantiatedGraphNodeWasm {
@@ -306,7 +370,7 @@ impl InstantiatedGraphWasm {
             InstantiatedNodeTypeImpl::Alias => InstantiatedNodeType::Alias,
         };
 
-        InstantiatedGraphNodeWasm {
+        return InstantiatedGraphNodeWasm {
             node_type,
             job_id: node.job_id.clone(),
             spec_name: node.spec_name.clone(),
@@ -315,7 +379,8 @@ impl InstantiatedGraphWasm {
             tag: node.tag.clone(),
             has_transform: node.has_transform,
             alias: node.alias.clone(),
-            direction: node.direction.clone(),
+            direction: node.direction.map(PortDirection::from),
+            merge_policy: node.merge_policy.as_ref().map(MergePolicy::from),
             label: node.label.clone(),
         }
     }
@@ -0,0 +1,143 @@
+//! Indexed lookups against the full scans they replace, on a chain of a few thousand specs.
+//!
+//! Run with `cargo bench -p livestack-shared --bench graph_lookups`.
+
+use criterion::{black_box, criterion_group, criterion_main, Criterion};
+use livestack_shared::systems::def_graph::{DefGraph, DefGraphNodeType, PortDirection};
+use livestack_shared::systems::def_graph_utils::{FromSpecAndTag, SpecTagInfo, ToSpecAndTag};
+use livestack_shared::systems::instantiated_graph::{InstantiatedGraph, InstantiatedNodeType};
+use std::collections::HashMap;
+
+const SPECS: usize = 2000;
+/// Inputs of the middle spec besides the one the chain feeds.
+const EXTRA_INPUTS: usize = 500;
+
+/// S0 -> S1 -> ... -> S{SPECS - 1}.
+fn chain() -> DefGraph {
+    let mut graph = DefGraph::new("Root".to_string(), vec![], vec![]);
+    for i in 1..SPECS {
+        graph.add_connected_dual_specs(
+            &FromSpecAndTag {
+                spec_name: format!("S{}", i - 1),
+                output: "out".to_string(),
+                unique_spec_label: None,
+            },
+            &ToSpecAndTag {
+                spec_name: format!("S{}", i),
+                input: "in".to_string(),
+                has_transform: false,
+                unique_spec_label: None,
+            },
+        );
+    }
+    graph
+}
+
+fn def_graph_lookups(c: &mut Criterion) {
+    let graph = chain();
+    let target = format!("S{}", SPECS / 2);
+
+    c.bench_function("def_graph/find_spec_node/indexed", |b| {
+        b.iter(|| graph.find_spec_node(black_box(&target), None))
+    });
+    c.bench_function("def_graph/find_spec_node/scan", |b| {
+        b.iter(|| {
+            graph.find_node(|node| {
+                node.node_type == DefGraphNodeType::Spec
+                    && node.spec_name.as_deref() == Some(black_box(target.as_str()))
+                    && node.unique_spec_label.is_none()
+            })
+        })
+    });
+    c.bench_function("def_graph/spec_node_ids/indexed", |b| {
+        b.iter(|| graph.get_spec_node_ids())
+    });
+    c.bench_function("def_graph/spec_node_ids/scan", |b| {
+        b.iter(|| {
+            graph
+                .node_indices()
+                .into_iter()
+                .filter(|&id| graph.node_weight(id).unwrap().node_type == DefGraphNodeType::Spec)
+                .collect::<Vec<_>>()
+        })
+    });
+}
+
+fn def_graph_port_lookups(c: &mut Criterion) {
+    let mut graph = chain();
+    let target = format!("S{}", SPECS / 2);
+    for i in 0..EXTRA_INPUTS {
+        graph.ensure_inlet_and_stream(
+            SpecTagInfo {
+                spec_name: target.clone(),
+                unique_spec_label: None,
+                tag: format!("extra{}", i),
+            },
+            false,
+        );
+    }
+    graph.assign_alias("middle", &target, "Root", None, PortDirection::In, "in");
+
+    c.bench_function("def_graph/root_spec_alias/indexed", |b| {
+        b.iter(|| {
+            graph.lookup_root_spec_alias(
+                black_box(target.clone()),
+                None,
+                "in".to_string(),
+                PortDirection::In,
+            )
+        })
+    });
+    c.bench_function("def_graph/root_spec_alias/scan", |b| {
+        b.iter(|| {
+            let spec = graph.find_spec_node(black_box(&target), None)?;
+            let inlet = graph.find_inbound_neighbor(spec, |node| {
+                node.node_type == DefGraphNodeType::Inlet && node.tag.as_deref() == Some("in")
+            })?;
+            let alias = graph
+                .find_outbound_neighbor(inlet, |node| node.node_type == DefGraphNodeType::Alias)?;
+            graph.node_weight(alias)?.alias.clone()
+        })
+    });
+}
+
+fn instantiated_graph_lookups(c: &mut Criterion) {
+    let graph = InstantiatedGraph::new(
+        "ctx".to_string(),
+        "root".to_string(),
+        HashMap::new(),
+        HashMap::new(),
+        HashMap::new(),
+        &chain(),
+    );
+    let job_id = format!("[ctx]S{}", SPECS / 2);
+
+    c.bench_function("instantiated_graph/stream_of_job/indexed", |b| {
+        b.iter(|| {
+            graph.find_stream_node_id_connected_to_job(black_box(&job_id), PortDirection::In, "in")
+        })
+    });
+    c.bench_function("instantiated_graph/stream_of_job/scan", |b| {
+        b.iter(|| {
+            graph.node_indices().into_iter().find(|&id| {
+                graph.node_weight(id).unwrap().node_type == InstantiatedNodeType::Stream
+                    && graph
+                        .get_nodes_connected_to_stream(id)
+                        .targets
+                        .iter()
+                        .any(|target| {
+                            target.destination.job_id.as_deref() == Some(black_box(&job_id))
+                                && target.inlet_node.tag.as_deref() == Some("in")
+                        })
+            })
+        })
+    });
+}
+
+criterion_group!(
+    benches,
+    def_graph_lookups,
+    def_graph_port_lookups,
+    instantiated_graph_lookups
+);
+criterion_main!(benches);
@@ -1,19 +1,26 @@
-use crate::systems::def_graph_utils::{unique_spec_identifier, unique_stream_identifier};
-use petgraph::graph::DiGraph;
+use crate::systems::def_graph_utils::unique_spec_identifier;
+use crate::systems::id_scheme::{IdScheme, ReadableIds};
+use petgraph::stable_graph::StableDiGraph;
+use petgraph::visit::{EdgeRef, IntoEdgeReferences};
 // use petgraph::graph::Node;
 // use napi_derive::napi;
 use petgraph::graph::NodeIndex;
 use serde::{Deserialize, Serialize};
 
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap};
+use std::sync::Arc;
 
 use super::def_graph_utils::{FromSpecAndTag, SpecTagInfo, ToSpecAndTag};
+use super::def_graph_diff::PortIdentity;
+use super::graph_format::to_canonical_json;
+use super::graph_index::DefGraphIndex;
 
-#[derive(Debug, PartialEq, Serialize, Deserialize, Clone)]
+#[derive(Debug, Default, PartialEq, Eq, Hash, Serialize, Deserialize, Clone)]
 #[serde(rename_all = "kebab-case")]
 // #[napi]
 pub enum DefGraphNodeType {
     RootSpec,
+    #[default]
     Spec,
     StreamDef,
     Inlet,
@@ -21,7 +28,7 @@ pub enum DefGraphNodeType {
     Alias,
 }
 
-fn node_type_to_string(node_type: &DefGraphNodeType) -> String {
+pub(crate) fn node_type_to_string(node_type: &DefGraphNodeType) -> String {
     match node_type {
         DefGraphNodeType::RootSpec => "RootSpec".to_string(),
         DefGraphNodeType::Spec => "Spec".to_string(),
@@ -32,7 +39,59 @@ fn node_type_to_string(node_type: &DefGraphNodeType) -> String {
     }
 }
 
-#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+/// Key of `stream_node_id_by_spec_identifier_type_and_tag`, e.g. `"translate[fr]::in/text"`.
+/// Keyed by the full spec identifier so labelled instances of one spec never share an entry.
+pub(crate) fn stream_def_key(spec_identifier: &str, direction: PortDirection, tag: &str) -> String {
+    format!("{}::{}/{}", spec_identifier, direction, tag)
+}
+
+/// Which side of a spec a port or alias is on. Serialized as `"in"` and `"out"`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum PortDirection {
+    In,
+    Out,
+}
+
+impl PortDirection {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            PortDirection::In => "in",
+            PortDirection::Out => "out",
+        }
+    }
+
+    /// The node type of a port on this side: Inlet or Outlet.
+    pub fn port_node_type(self) -> DefGraphNodeType {
+        match self {
+            PortDirection::In => DefGraphNodeType::Inlet,
+            PortDirection::Out => DefGraphNodeType::Outlet,
+        }
+    }
+}
+
+impl std::fmt::Display for PortDirection {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+/// Parses `"in"` or `"out"`, for callers that still hold a direction as text.
+impl std::str::FromStr for PortDirection {
+    type Err = DefGraphError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "in" => Ok(PortDirection::In),
+            "out" => Ok(PortDirection::Out),
+            _ => Err(DefGraphError::InvalidDirection(s.to_string())),
+        }
+    }
+}
+
+/// A node of any type; fields that do not apply to its type stay `None`. `Default` gives an
+/// unlabelled Spec with every optional field empty, to fill in with `..Default::default()`.
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub struct DefGraphNode {
     pub node_type: DefGraphNodeType,
@@ -49,10 +108,66 @@ pub struct DefGraphNode {
     #[serde(skip_serializing_if = "Option::is_none")]
     pub alias: Option<String>,
     #[serde(skip_serializing_if = "Option::is_none")]
-    pub direction: Option<String>,
+    pub direction: Option<PortDirection>,
+    /// Set on an Inlet fed by more than one StreamDef: how their messages are combined.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub merge_policy: Option<MergePolicy>,
+    /// JSON Schema of the values an Inlet accepts or an Outlet produces, in the form
+    /// `EnsureStreamRequest.json_schema_str` takes.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub json_schema_str: Option<String>,
     pub label: String,
 }
 
+/// How an Inlet with several upstream streams combines them into one input.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", tag = "kind")]
+pub enum MergePolicy {
+    /// Pass messages on in the order they arrive, whichever stream they come from.
+    Interleave,
+    /// Take one message from each upstream stream in turn, in stream order.
+    RoundRobin,
+    /// Wait for one message per upstream stream with the same value at `key`, then
+    /// pass them on together.
+    ZipByKey { key: String },
+}
+
+/// Settings that belong to one connection rather than to either endpoint. Stored on the
+/// StreamDef -> Inlet edge of the connection; all fields are optional.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct EdgeMeta {
+    /// Name of the transform applied to messages on this connection.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub transform: Option<String>,
+    /// Most messages held for the consumer before the producer is slowed down.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub buffer_limit: Option<u32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub durability: Option<Durability>,
+    /// Message field used to route messages to partitions of the consumer.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub partition_key: Option<String>,
+    /// Set on a connection that deliberately closes a loop.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub is_feedback: Option<bool>,
+}
+
+impl EdgeMeta {
+    /// True when no setting is present, as on structural edges.
+    pub fn is_default(&self) -> bool {
+        *self == EdgeMeta::default()
+    }
+}
+
+/// Whether messages on a connection must survive a restart.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum Durability {
+    Ephemeral,
+    Persistent,
+}
+
 // NEW: Structs for stream connection info
 #[derive(Debug, Serialize, Deserialize, Clone)]
 #[serde(rename_all = "camelCase")]
@@ -75,32 +190,185 @@ pub struct StreamConnections {
     pub targets: Vec<StreamConnectionTarget>,
 }
 
-#[derive(Serialize, Deserialize, Debug, Clone)]
+/// Serialized through the versioned layout in [`crate::systems::graph_format`].
+#[derive(Debug, Clone)]
 // #[napi]
-#[serde(rename_all = "camelCase")]
 pub struct DefGraph {
-    graph: DiGraph<DefGraphNode, ()>,
-    node_indices: HashMap<String, NodeIndex>,
-    stream_node_id_by_spec_identifier_type_and_tag: HashMap<String, String>,
+    /// Stable indices: removing a node never renumbers the others, so the `u32` ids handed
+    /// out through the API (and stored in `node_indices`) stay valid across removals.
+    pub(crate) graph: StableDiGraph<DefGraphNode, EdgeMeta>,
+    pub(crate) node_indices: HashMap<String, NodeIndex>,
+    pub(crate) stream_node_id_by_spec_identifier_type_and_tag: HashMap<String, String>,
+    /// Graphs embedded as composite specs, keyed by the composite's `unique_spec_identifier`.
+    pub(crate) composites: BTreeMap<String, DefGraph>,
+    /// Nodes by type and specs by identifier; derived from `graph`, never serialized.
+    pub(crate) indexes: DefGraphIndex,
+    /// Generates the `stream_def_id` of each new StreamDef. Never serialized.
+    pub(crate) id_scheme: Arc<dyn IdScheme>,
+}
+
+/// Errors raised while building, mutating or loading a [`DefGraph`].
+#[derive(Debug, Clone, PartialEq)]
+pub enum DefGraphError {
+    /// No Spec node matches the given name and label.
+    SpecNotFound {
+        spec_name: String,
+        unique_spec_label: Option<String>,
+    },
+    /// No RootSpec node matches the given name.
+    RootSpecNotFound { root_spec_name: String },
+    /// The spec has no Inlet (`"in"`) or Outlet (`"out"`) with the given tag.
+    PortNotFound {
+        spec_name: String,
+        tag: String,
+        direction: PortDirection,
+    },
+    /// An Outlet feeds more than one StreamDef, so the stream to reuse is ambiguous.
+    AmbiguousStreamDef { outlet_label: String, count: usize },
+    /// Text other than `"in"` or `"out"` where a [`PortDirection`] was expected.
+    InvalidDirection(String),
+    /// A spec or stream id that does not follow the identifier syntax it was parsed with.
+    InvalidIdentifier { id: String, reason: String },
+    /// No Alias with this name and direction is attached to the root spec.
+    AliasNotFound {
+        alias: String,
+        direction: PortDirection,
+    },
+    /// The alias name is already bound, in the direction and to the port given here, so it
+    /// cannot be bound elsewhere.
+    AliasConflict {
+        alias: String,
+        direction: PortDirection,
+        port: String,
+    },
+    /// The outlet and inlet exist but no stream connects them.
+    NotConnected { from: String, to: String },
+    /// A spec with this `unique_spec_identifier` is already in the graph.
+    SpecAlreadyExists { spec_identifier: String },
+    /// A node id does not exist in the graph.
+    NodeNotFound(u32),
+    /// A node exists but is not of a type the operation accepts.
+    UnexpectedNodeType { node_id: u32, expected: String },
+    /// A node exists but lacks a field its node type requires.
+    MalformedNode { node_id: u32, reason: String },
+    /// The JSON input could not be deserialized into a `DefGraph`.
+    Deserialize(String),
+    /// The JSON was written by a newer format version than this build understands.
+    UnsupportedFormatVersion { version: u64, latest: u32 },
+    /// A port schema is not a JSON Schema document.
+    InvalidSchema { port: String, reason: String },
+    /// An outlet's schema allows values the connected inlet's schema rejects, and the
+    /// connection has no transform to convert them.
+    IncompatibleSchemas {
+        from: String,
+        to: String,
+        reason: String,
+    },
 }
 
+impl std::fmt::Display for DefGraphError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            DefGraphError::SpecNotFound {
+                spec_name,
+                unique_spec_label,
+            } => write!(
+                f,
+                "Spec node not found: {}",
+                unique_spec_identifier(spec_name.clone(), unique_spec_label.clone())
+            ),
+            DefGraphError::RootSpecNotFound { root_spec_name } => {
+                write!(f, "Root spec node not found: {}", root_spec_name)
+            }
+            DefGraphError::PortNotFound {
+                spec_name,
+                tag,
+                direction,
+            } => {
+                let port = match direction {
+                    PortDirection::In => "Inlet",
+                    PortDirection::Out => "Outlet",
+                };
+                write!(f, "{} node not found: {}/{}", port, spec_name, tag)
+            }
+            DefGraphError::AmbiguousStreamDef {
+                outlet_label,
+                count,
+            } => write!(
+                f,
+                "More than one stream def node found for outlet node {} ({} found)",
+                outlet_label, count
+            ),
+            DefGraphError::InvalidDirection(direction) => {
+                write!(f, "Invalid direction type: {:?}", direction)
+            }
+            DefGraphError::InvalidIdentifier { id, reason } => {
+                write!(f, "Invalid identifier {:?}: {}", id, reason)
+            }
+            DefGraphError::AliasNotFound { alias, direction } => {
+                write!(f, "Alias node not found: {} ({})", alias, direction)
+            }
+            DefGraphError::AliasConflict {
+                alias,
+                direction,
+                port,
+            } => write!(
+                f,
+                "Alias {} is already assigned to {} port {}",
+                alias, direction, port
+            ),
+            DefGraphError::NotConnected { from, to } => {
+                write!(f, "No stream connects {} to {}", from, to)
+            }
+            DefGraphError::SpecAlreadyExists { spec_identifier } => {
+                write!(f, "Spec already exists: {}", spec_identifier)
+            }
+            DefGraphError::NodeNotFound(node_id) => write!(f, "Node not found: {}", node_id),
+            DefGraphError::UnexpectedNodeType { node_id, expected } => {
+                write!(f, "Node {} is not a {} node", node_id, expected)
+            }
+            DefGraphError::MalformedNode { node_id, reason } => {
+                write!(f, "Malformed node {}: {}", node_id, reason)
+            }
+            DefGraphError::Deserialize(message) => {
+                write!(f, "Failed to deserialize the JSON string to a DefGraph: {}", message)
+            }
+            DefGraphError::UnsupportedFormatVersion { version, latest } => write!(
+                f,
+                "Unsupported graph format version {} (latest supported is {})",
+                version, latest
+            ),
+            DefGraphError::InvalidSchema { port, reason } => {
+                write!(f, "Invalid JSON Schema on {}: {}", port, reason)
+            }
+            DefGraphError::IncompatibleSchemas { from, to, reason } => {
+                write!(f, "Schema of {} does not fit {}: {}", from, to, reason)
+            }
+        }
+    }
+}
+
+impl std::error::Error for DefGraphError {}
+
 pub fn load_from_json(json_str: String) -> DefGraph {
-    // Deserialize the JSON string to a SerializedDefGraph
-    let graph: DefGraph = serde_json::from_str(&json_str)
-        .expect("Failed to deserialize the JSON string to a DefGraph");
+    try_load_from_json(json_str).unwrap_or_else(|e| panic!("{}", e))
+}
 
-    graph
+/// Deserializes a `DefGraph` from a JSON string, returning an error instead of panicking.
+/// Older format versions are migrated on load.
+pub fn try_load_from_json(json_str: String) -> Result<DefGraph, DefGraphError> {
+    let value: serde_json::Value =
+        serde_json::from_str(&json_str).map_err(|e| DefGraphError::Deserialize(e.to_string()))?;
+    DefGraph::from_json_value(value)
 }
 
 // #[napi]
 impl DefGraph {
-    /// Deserializes a `DefGraph` from a JSON string.
-    // #[napi(factory)]
-   
-    /// Serializes the `DefGraph` to a JSON string.
+    /// Serializes the `DefGraph` to canonical JSON in the current format version, so equal
+    /// graphs give equal strings.
     // #[napi]
     pub fn to_json(&self) -> Result<String, serde_json::Error> {
-        serde_json::to_string(&self)
+        to_canonical_json(self)
     }
 
     /// Retrieves all node indices.
@@ -110,32 +378,25 @@ impl DefGraph {
 
     pub fn node_weight(&self, index: u32) -> Option<DefGraphNode> {
         let index = NodeIndex::new(index as usize);
-        let w = self.graph.node_weight(index);
-        if let Some(w) = w {
-            Some(DefGraphNode {
-                node_type: w.node_type.clone(),
-                spec_name: w.spec_name.clone(),
-                unique_spec_label: w.unique_spec_label.clone(),
-                tag: w.tag.clone(),
-                has_transform: w.has_transform.clone(),
-                stream_def_id: w.stream_def_id.clone(),
-                alias: w.alias.clone(),
-                direction: w.direction.clone(),
-                label: w.label.clone(),
-            })
-        } else {
-            None
-        }
+        self.graph.node_weight(index).cloned()
     }
 
     pub fn raw_edges(&self) -> Vec<(u32, u32)> {
-        self.graph
-            .raw_edges()
-            .iter()
+        (&self.graph)
+            .edge_references()
             .map(|edge| (edge.source().index() as u32, edge.target().index() as u32))
             .collect()
     }
 
+    /// Metadata on the edge between two nodes, if they are connected.
+    pub fn edge_meta(&self, from: u32, to: u32) -> Option<&EdgeMeta> {
+        let edge = self.graph.find_edge(
+            NodeIndex::new(from as usize),
+            NodeIndex::new(to as usize),
+        )?;
+        self.graph.edge_weight(edge)
+    }
+
     pub fn contains_edge(&self, from: u32, to: u32) -> bool {
         let from = NodeIndex::new(from as usize);
         let to = NodeIndex::new(to as usize);
@@ -159,55 +420,44 @@ impl DefGraph {
 
     // #[napi]
     pub fn get_all_alias_node_ids(&self) -> Vec<u32> {
-        self.graph
-            .node_indices()
-            .filter_map(|index| {
-                if let Some(node) = self.graph.node_weight(index) {
-                    if node.node_type == DefGraphNodeType::Alias {
-                        // Cast usize to u32 here
-                        Some(index.index() as u32)
-                    } else {
-                        None
-                    }
-                } else {
-                    None
-                }
-            })
+        self.indexes
+            .of_type(&DefGraphNodeType::Alias)
+            .map(|index| index.index() as u32)
             .collect()
     }
 
     pub fn get_root_spec_node_id(&self) -> Option<u32> {
-        self.graph
-            .node_indices()
-            .find(|&index| {
-                if let Some(node) = self.graph.node_weight(index) {
-                    node.node_type == DefGraphNodeType::RootSpec
-                } else {
-                    false
-                }
-            })
-            .map(|index| index.index() as u32) // Convert NodeIndex to u32
+        self.indexes
+            .of_type(&DefGraphNodeType::RootSpec)
+            .next()
+            .map(|index| index.index() as u32)
+    }
+
+    /// Node id of the Spec with this name and label, looked up by its identifier.
+    pub fn find_spec_node(&self, spec_name: &str, unique_spec_label: Option<&str>) -> Option<u32> {
+        self.indexes
+            .spec(spec_name, unique_spec_label)
+            .map(|index| index.index() as u32)
     }
 
     // #[napi]
     pub fn lookup_spec_and_tag_by_alias(
         &self,
         alias: String,
-        direction: &str,
+        direction: PortDirection,
     ) -> Option<SpecTagInfo> {
         let root_spec_node_id = self.get_root_spec_node_id()?;
         let alias_node_id = match direction {
-            "in" => self.find_inbound_neighbor(root_spec_node_id, |node| {
+            PortDirection::In => self.find_inbound_neighbor(root_spec_node_id, |node| {
                 node.node_type == DefGraphNodeType::Alias && node.alias.as_deref() == Some(&alias)
             }),
-            "out" => self.find_outbound_neighbor(root_spec_node_id, |node| {
+            PortDirection::Out => self.find_outbound_neighbor(root_spec_node_id, |node| {
                 node.node_type == DefGraphNodeType::Alias && node.alias.as_deref() == Some(&alias)
             }),
-            _ => None,
         }?;
 
         let (spec_node_id, tag) = match direction {
-            "in" => {
+            PortDirection::In => {
                 let inlet_node_id = self.find_inbound_neighbor(alias_node_id, |node| {
                     node.node_type == DefGraphNodeType::Inlet
                 })?;
@@ -218,7 +468,7 @@ impl DefGraph {
                 let tag = self.graph.node_weight(inlet_node_id_idx)?.tag.clone()?;
                 (spec_node_id, tag)
             }
-            "out" => {
+            PortDirection::Out => {
                 let outlet_node_id = self.find_outbound_neighbor(alias_node_id, |node| {
                     node.node_type == DefGraphNodeType::Outlet
                 })?;
@@ -229,7 +479,6 @@ impl DefGraph {
                 let tag = self.graph.node_weight(outlet_node_id_idx)?.tag.clone()?;
                 (spec_node_id, tag)
             }
-            _ => return None,
         };
         let spec_node_id_idx = NodeIndex::new(spec_node_id as usize);
         let spec_name = self
@@ -256,34 +505,23 @@ impl DefGraph {
         spec_name: String,
         unique_spec_label: Option<String>,
         tag: String,
-        direction: String,
+        direction: PortDirection,
     ) -> Option<String> {
-        let spec_node_id = self.find_node(|node| {
-            node.node_type == DefGraphNodeType::Spec
-                && node.spec_name.as_deref() == Some(spec_name.as_str())
-                && node.unique_spec_label.as_deref() == unique_spec_label.as_deref()
-        })?;
-
-        let alias_node_id = match direction.as_str() {
-            "in" => {
-                let inlet_node_id = self.find_inbound_neighbor(spec_node_id, |node| {
-                    node.node_type == DefGraphNodeType::Inlet
-                        && node.tag.as_deref() == Some(tag.as_str())
-                })?;
+        let spec_node_id = self.find_spec_node(&spec_name, unique_spec_label.as_deref())?;
+
+        let alias_node_id = match direction {
+            PortDirection::In => {
+                let inlet_node_id = self.port_node(spec_node_id, PortDirection::In, &tag)?;
                 self.find_outbound_neighbor(inlet_node_id, |node| {
                     node.node_type == DefGraphNodeType::Alias
                 })
             }
-            "out" => {
-                let outlet_node_id = self.find_outbound_neighbor(spec_node_id, |node| {
-                    node.node_type == DefGraphNodeType::Outlet
-                        && node.tag.as_deref() == Some(tag.as_str())
-                })?;
+            PortDirection::Out => {
+                let outlet_node_id = self.port_node(spec_node_id, PortDirection::Out, &tag)?;
                 self.find_inbound_neighbor(outlet_node_id, |node| {
                     node.node_type == DefGraphNodeType::Alias
                 })
             }
-            _ => return None,
         };
 
         alias_node_id.and_then(|id| {
@@ -304,6 +542,19 @@ impl DefGraph {
             .map(|index| index.index() as u32)
     }
 
+    /// The Inlet (`In`) or Outlet (`Out`) tagged `tag` of a spec or root spec node. Unlike
+    /// [`DefGraph::find_inbound_neighbor`], this does not visit the spec's other ports.
+    pub(crate) fn port_node(
+        &self,
+        spec_node_id: u32,
+        direction: PortDirection,
+        tag: &str,
+    ) -> Option<u32> {
+        self.indexes
+            .port(NodeIndex::new(spec_node_id as usize), direction, tag)
+            .map(|index| index.index() as u32)
+    }
+
     pub fn find_outbound_neighbor<F>(&self, node_id: u32, condition: F) -> Option<u32>
     where
         F: FnMut(&DefGraphNode) -> bool,
@@ -312,7 +563,6 @@ impl DefGraph {
         self.filter_outbound_neighbors(node_id, condition)
             .into_iter()
             .next()
-            .map(|index| index)
     }
 
     pub fn add_connected_dual_specs(
@@ -320,8 +570,83 @@ impl DefGraph {
         from: &FromSpecAndTag, // (spec_name, output, unique_spec_label)
         to: &ToSpecAndTag,     // (spec_name, input, has_transform, unique_spec_label)
     ) -> (u32, u32, u32, u32, u32) {
+        self.try_add_connected_dual_specs(from, to)
+            .unwrap_or_else(|e| panic!("{}", e))
+    }
+
+    /// Fallible version of [`DefGraph::add_connected_dual_specs`]. The graph is left
+    /// untouched when an error is returned.
+    pub fn try_add_connected_dual_specs(
+        &mut self,
+        from: &FromSpecAndTag,
+        to: &ToSpecAndTag,
+    ) -> Result<(u32, u32, u32, u32, u32), DefGraphError> {
         let from_spec_id =
-        unique_spec_identifier(from.spec_name.clone(), from.unique_spec_label.clone());
+            unique_spec_identifier(from.spec_name.clone(), from.unique_spec_label.clone());
+        let from_outlet_label = format!("{}/{}", from_spec_id, from.output);
+        self.check_composite_port(
+            &from.spec_name,
+            &from_spec_id,
+            &from.output,
+            PortDirection::Out,
+        )?;
+        self.check_composite_port(
+            &to.spec_name,
+            &unique_spec_identifier(to.spec_name.clone(), to.unique_spec_label.clone()),
+            &to.input,
+            PortDirection::In,
+        )?;
+
+        if !to.has_transform {
+            let port_index = |port_type: DefGraphNodeType, label: String| {
+                self.node_indices
+                    .get(&format!("{}_{}", node_type_to_string(&port_type), label))
+                    .copied()
+            };
+            let to_spec_id =
+                unique_spec_identifier(to.spec_name.clone(), to.unique_spec_label.clone());
+            if let (Some(outlet), Some(inlet)) = (
+                port_index(DefGraphNodeType::Outlet, from_outlet_label.clone()),
+                port_index(DefGraphNodeType::Inlet, format!("{}/{}", to_spec_id, to.input)),
+            ) {
+                self.check_port_schemas(outlet, inlet)?;
+            }
+        }
+
+        // Resolve the stream already fed by this outlet (if any) before mutating anything.
+        let existing_stream = match self.node_indices.get(&format!(
+            "{}_{}",
+            node_type_to_string(&DefGraphNodeType::Outlet),
+            from_outlet_label
+        )) {
+            Some(&outlet_index) => {
+                let existing_stream_def_ids = self.outbound_neighbors(outlet_index.index() as u32);
+                if existing_stream_def_ids.len() > 1 {
+                    return Err(DefGraphError::AmbiguousStreamDef {
+                        outlet_label: from_outlet_label,
+                        count: existing_stream_def_ids.len(),
+                    });
+                }
+                match existing_stream_def_ids.first() {
+                    Some(&stream_node_id) => {
+                        let stream_def_id = self
+                            .graph
+                            .node_weight(NodeIndex::new(stream_node_id as usize))
+                            .ok_or(DefGraphError::NodeNotFound(stream_node_id))?
+                            .stream_def_id
+                            .clone()
+                            .ok_or_else(|| DefGraphError::MalformedNode {
+                                node_id: stream_node_id,
+                                reason: "StreamDef node must have a stream_def_id".to_string(),
+                            })?;
+                        Some((stream_node_id, stream_def_id))
+                    }
+                    None => None,
+                }
+            }
+            None => None,
+        };
+
         let from_spec_node_id = self.ensure_node(
             &from_spec_id,
             DefGraphNode {
@@ -334,10 +659,11 @@ impl DefGraph {
                 alias: None,
                 direction: None,
                 label: from_spec_id.clone(),
+                ..Default::default()
             },
         );
         let from_outlet_node_id = self.ensure_node(
-            &format!("{}/{}", from_spec_id, from.output),
+            &from_outlet_label,
             DefGraphNode {
                 node_type: DefGraphNodeType::Outlet,
                 spec_name: None,
@@ -347,54 +673,46 @@ impl DefGraph {
                 stream_def_id: None,
                 alias: None,
                 direction: None,
-                label: format!("{}/{}", from_spec_id, from.output),
+                label: from_outlet_label.clone(),
+                ..Default::default()
             },
         );
 
-        
-        let stream_node_id: u32;
-        let stream_def_id: String;
-        let existing_stream_def_ids = self.outbound_neighbors(from_outlet_node_id);
-        if existing_stream_def_ids.len()  > 1 {
-            // throw error
-            panic!("More than one stream def node found for outlet node");
-        } else if existing_stream_def_ids.len() == 1 {
-            stream_node_id = existing_stream_def_ids[0];
-            stream_def_id = self.graph.node_weight(NodeIndex::new(stream_node_id as usize))
-                .expect("StreamDef node must exist")
-                .stream_def_id
-                .clone()
-                .expect("StreamDef node must have a stream_def_id");
-        } else {
-            stream_def_id = unique_stream_identifier(
-                Some(SpecTagInfo {
-                    spec_name: from.spec_name.clone(),
-                    unique_spec_label: from.unique_spec_label.clone(),
-                    tag: from.output.clone(),
-                }),
-                Some(SpecTagInfo {
-                    spec_name: to.spec_name.clone(),
-                    unique_spec_label: to.unique_spec_label.clone(),
-                    tag: to.input.clone(),
-                }),
-            );
-            stream_node_id = self.ensure_node(
-                &stream_def_id,
-                DefGraphNode {
-                    node_type: DefGraphNodeType::StreamDef,
-                    spec_name: None,
-                    unique_spec_label: None,
-                    tag: None,
-                    has_transform: None,
-                    stream_def_id: Some(stream_def_id.clone()),
-                    alias: None,
-                    direction: None,
-                    label: stream_def_id.clone(),
-                },
-            );
-            self.ensure_edge(from_spec_node_id, from_outlet_node_id);
-            self.ensure_edge(from_outlet_node_id, stream_node_id);
-        }
+        let (stream_node_id, stream_def_id) = match existing_stream {
+            Some(existing) => existing,
+            None => {
+                let stream_def_id = self.id_scheme.stream_def_id(
+                    Some(&SpecTagInfo {
+                        spec_name: from.spec_name.clone(),
+                        unique_spec_label: from.unique_spec_label.clone(),
+                        tag: from.output.clone(),
+                    }),
+                    Some(&SpecTagInfo {
+                        spec_name: to.spec_name.clone(),
+                        unique_spec_label: to.unique_spec_label.clone(),
+                        tag: to.input.clone(),
+                    }),
+                );
+                let stream_node_id = self.ensure_node(
+                    &stream_def_id,
+                    DefGraphNode {
+                        node_type: DefGraphNodeType::StreamDef,
+                        spec_name: None,
+                        unique_spec_label: None,
+                        tag: None,
+                        has_transform: None,
+                        stream_def_id: Some(stream_def_id.clone()),
+                        alias: None,
+                        direction: None,
+                        label: stream_def_id.clone(),
+                        ..Default::default()
+                    },
+                );
+                self.ensure_edge(from_spec_node_id, from_outlet_node_id);
+                self.ensure_edge(from_outlet_node_id, stream_node_id);
+                (stream_node_id, stream_def_id)
+            }
+        };
         let to_spec_id: String = unique_spec_identifier(to.spec_name.clone(), to.unique_spec_label.clone());
 
         let to_inlet_node_id = self.ensure_node(
@@ -409,6 +727,7 @@ impl DefGraph {
                 alias: None,
                 direction: None,
                 label: format!("{}/{}", to_spec_id, to.input),
+                ..Default::default()
             },
         );
 
@@ -424,56 +743,155 @@ impl DefGraph {
                 alias: None,
                 direction: None,
                 label: to_spec_id.clone(),
+                ..Default::default()
             },
         );
 
-     
-
         self.ensure_edge(stream_node_id, to_inlet_node_id);
         self.ensure_edge(to_inlet_node_id, to_spec_node_id);
 
         // insert to hash
         self.stream_node_id_by_spec_identifier_type_and_tag.insert(
-            format!("{}::in/{}", to.spec_name, to.input),
+            stream_def_key(&to_spec_id, PortDirection::In, &to.input),
             stream_def_id.clone()
         );
         self.stream_node_id_by_spec_identifier_type_and_tag.insert(
-            format!("{}::out/{}", from.spec_name, from.output),
+            stream_def_key(&from_spec_id, PortDirection::Out, &from.output),
             stream_def_id.clone()
         );
 
-    
-        (
+        Ok((
             from_spec_node_id,
             to_spec_node_id,
             stream_node_id,
             from_outlet_node_id,
             to_inlet_node_id,
-        )
+        ))
+    }
+
+    /// Like [`DefGraph::try_add_connected_dual_specs`], and records `meta` on the new
+    /// connection, replacing any metadata it already had.
+    pub fn try_add_connected_dual_specs_with_meta(
+        &mut self,
+        from: &FromSpecAndTag,
+        to: &ToSpecAndTag,
+        meta: EdgeMeta,
+    ) -> Result<(u32, u32, u32, u32, u32), DefGraphError> {
+        let ids = self.try_add_connected_dual_specs(from, to)?;
+        let (_, _, stream_node_id, _, to_inlet_node_id) = ids;
+        let edge = self
+            .graph
+            .find_edge(
+                NodeIndex::new(stream_node_id as usize),
+                NodeIndex::new(to_inlet_node_id as usize),
+            )
+            .expect("connection edge was just ensured");
+        self.graph[edge] = meta;
+        Ok(ids)
+    }
+
+    /// Replaces the metadata of an existing connection.
+    pub fn set_connection_meta(
+        &mut self,
+        from: &SpecTagInfo,
+        to: &SpecTagInfo,
+        meta: EdgeMeta,
+    ) -> Result<(), DefGraphError> {
+        let (_, stream, inlet) = self.connection_nodes(from, to)?;
+        let edge = self
+            .graph
+            .find_edge(stream, inlet)
+            .expect("connection_nodes checks the edge");
+        self.graph[edge] = meta;
+        Ok(())
+    }
+
+    /// Metadata of an existing connection.
+    pub fn connection_meta(
+        &self,
+        from: &SpecTagInfo,
+        to: &SpecTagInfo,
+    ) -> Result<&EdgeMeta, DefGraphError> {
+        let (_, stream, inlet) = self.connection_nodes(from, to)?;
+        let edge = self
+            .graph
+            .find_edge(stream, inlet)
+            .expect("connection_nodes checks the edge");
+        Ok(&self.graph[edge])
     }
+
     pub fn assign_alias(
         &mut self,
         alias: &str,
         spec_name: &str,
         root_spec_name: &str,
         unique_spec_label: Option<&str>,
-        type_: &str,
+        direction: PortDirection,
         tag: &str,
     ) {
+        self.try_assign_alias(alias, spec_name, root_spec_name, unique_spec_label, direction, tag)
+            .unwrap_or_else(|e| panic!("{}", e))
+    }
+
+    /// Fallible version of [`DefGraph::assign_alias`]. The graph is left untouched
+    /// when an error is returned.
+    pub fn try_assign_alias(
+        &mut self,
+        alias: &str,
+        spec_name: &str,
+        root_spec_name: &str,
+        unique_spec_label: Option<&str>,
+        direction: PortDirection,
+        tag: &str,
+    ) -> Result<(), DefGraphError> {
         let spec_node_id = self
-            .find_node(|node| {
-                node.node_type == DefGraphNodeType::Spec
-                    && node.spec_name.as_deref() == Some(spec_name)
-                    && node.unique_spec_label.as_deref() == unique_spec_label
-            })
-            .expect(format!("Spec node not found: {}", spec_name).as_str());
+            .find_spec_node(spec_name, unique_spec_label)
+            .ok_or_else(|| DefGraphError::SpecNotFound {
+                spec_name: spec_name.to_string(),
+                unique_spec_label: unique_spec_label.map(|l| l.to_string()),
+            })?;
 
         let root_spec_node_id = self
-            .find_node(|node| {
-                node.node_type == DefGraphNodeType::RootSpec
-                    && node.spec_name.as_deref() == Some(root_spec_name)
+            .get_root_spec_node_id()
+            .filter(|&id| {
+                self.graph[NodeIndex::new(id as usize)].spec_name.as_deref() == Some(root_spec_name)
             })
-            .expect(format!("Root spec node not found: {}", root_spec_name).as_str());
+            .ok_or_else(|| DefGraphError::RootSpecNotFound {
+                root_spec_name: root_spec_name.to_string(),
+            })?;
+
+        let port_not_found = || DefGraphError::PortNotFound {
+            spec_name: spec_name.to_string(),
+            tag: tag.to_string(),
+            direction,
+        };
+        // Ports of a composite spec exist as soon as the embedded graph exposes them.
+        let spec_id = unique_spec_identifier(
+            spec_name.to_string(),
+            unique_spec_label.map(|l| l.to_string()),
+        );
+        self.check_alias_available(
+            alias,
+            direction,
+            &PortIdentity {
+                spec_identifier: spec_id.clone(),
+                tag: tag.to_string(),
+            },
+        )?;
+        if self.composites.contains_key(&spec_id) {
+            self.check_composite_port(spec_name, &spec_id, tag, direction)?;
+            self.ensure_port_node(
+                &SpecTagInfo {
+                    spec_name: spec_name.to_string(),
+                    unique_spec_label: unique_spec_label.map(|l| l.to_string()),
+                    tag: tag.to_string(),
+                },
+                direction,
+            );
+        }
+        let port_node_id = self
+            .port_node(spec_node_id, direction, tag)
+            .ok_or_else(port_not_found)?;
 
         let alias_id = format!("{}/{}", root_spec_name, alias);
         let alias_node_id = self.ensure_node(
@@ -486,88 +904,127 @@ impl DefGraph {
                 has_transform: None,
                 stream_def_id: None,
                 alias: Some(alias.to_string()),
-                direction: Some(type_.to_string()),
+                direction: Some(direction),
                 label: alias_id.clone(),
+                ..Default::default()
             },
         );
 
-        match type_ {
-            "in" => {
-                let inlet_node_id = self
-                    .find_inbound_neighbor(spec_node_id, |node| {
-                        node.node_type == DefGraphNodeType::Inlet && node.tag.as_deref() == Some(tag)
-                    })
-                    .expect(format!("Inlet node not found: {}", tag).as_str());
-                self.ensure_edge(inlet_node_id, alias_node_id);
-                self.ensure_edge(alias_node_id, root_spec_node_id);
-            }
-            "out" => {
-                let outlet_node_id = self
-                    .find_outbound_neighbor(spec_node_id, |node| {
-                        node.node_type == DefGraphNodeType::Outlet && node.tag.as_deref() == Some(tag)
-                    });
-                // propagate error if outlet_node_id is None
-                let outlet_node_id = outlet_node_id.expect(format ! ("Outlet node not found: {}", tag).as_str());
-                self.ensure_edge(root_spec_node_id, alias_node_id);
-                self.ensure_edge(alias_node_id, outlet_node_id);
-            }
-            _ => panic!("Invalid direction type"),
-        };
+        if direction == PortDirection::In {
+            self.ensure_edge(port_node_id, alias_node_id);
+            self.ensure_edge(alias_node_id, root_spec_node_id);
+        } else {
+            self.ensure_edge(root_spec_node_id, alias_node_id);
+            self.ensure_edge(alias_node_id, port_node_id);
+        }
+        Ok(())
     }
 
-
+    /// Declares how an inlet fed by several streams combines them, or clears the
+    /// declaration with `None`. The policy applies to every stream connected to the inlet,
+    /// including ones connected later.
+    pub fn set_inlet_merge_policy(
+        &mut self,
+        to: &SpecTagInfo,
+        merge_policy: Option<MergePolicy>,
+    ) -> Result<(), DefGraphError> {
+        let spec_node_id = self
+            .find_spec_node(&to.spec_name, to.unique_spec_label.as_deref())
+            .ok_or_else(|| DefGraphError::SpecNotFound {
+                spec_name: to.spec_name.clone(),
+                unique_spec_label: to.unique_spec_label.clone(),
+            })?;
+        let inlet_node_id = self
+            .port_node(spec_node_id, PortDirection::In, &to.tag)
+            .ok_or_else(|| DefGraphError::PortNotFound {
+                spec_name: to.spec_name.clone(),
+                tag: to.tag.clone(),
+                direction: PortDirection::In,
+            })?;
+        self.graph[NodeIndex::new(inlet_node_id as usize)].merge_policy = merge_policy;
+        Ok(())
+    }
     //
 
-    pub fn get_inbound_stream_nodes(&self, spec_node_id: u32) -> Vec<(u32, u32)> {
-        let spec_node_id = NodeIndex::new(spec_node_id as usize);
-        self.graph
-            .neighbors_directed(spec_node_id, petgraph::Incoming)
-            .filter_map(|inlet_node_id| {
-                if let Some(inlet_node) = self.graph.node_weight(inlet_node_id) {
-                    if inlet_node.node_type == DefGraphNodeType::Inlet {
-                        let stream_node_id = self
-                            .graph
-                            .neighbors_directed(inlet_node_id, petgraph::Incoming)
-                            .next() // Assuming there is only one stream node connected to the inlet
-                            .expect("Inlet node should have an incoming stream node");
-                        let inlet_node_id = inlet_node_id.index() as u32;
-                        let stream_node_id = stream_node_id.index() as u32;
-                        Some((inlet_node_id, stream_node_id))
-                    } else {
-                        None
-                    }
-                } else {
-                    None
-                }
-            })
-            .collect()
+    /// Returns one `(inlet, stream)` pair per stream feeding each of the spec's inlets, so an
+    /// inlet that merges several producers appears once for each of them. Fails if the spec
+    /// does not exist or one of its inlets is fed by no stream.
+    pub fn get_inbound_stream_nodes(
+        &self,
+        spec_node_id: u32,
+    ) -> Result<Vec<(u32, u32)>, DefGraphError> {
+        let mut pairs = Vec::new();
+        for inlet in self.spec_ports(spec_node_id, PortDirection::In)? {
+            let mut streams = self.neighbor_indices_of_types(
+                inlet,
+                petgraph::Incoming,
+                &[DefGraphNodeType::StreamDef],
+            );
+            if streams.is_empty() {
+                return Err(DefGraphError::MalformedNode {
+                    node_id: inlet.index() as u32,
+                    reason: "inlet is fed by no stream".to_string(),
+                });
+            }
+            streams.sort();
+            pairs.extend(
+                streams
+                    .into_iter()
+                    .map(|stream| (inlet.index() as u32, stream.index() as u32)),
+            );
+        }
+        Ok(pairs)
     }
 
-    pub fn get_outbound_stream_nodes(&self, spec_node_id: u32) -> Vec<(u32, u32)> {
-        let spec_node_id = NodeIndex::new(spec_node_id as usize);
-        self.graph
-            .neighbors_directed(spec_node_id, petgraph::Outgoing)
-            .filter_map(|outlet_node_id| {
-                if let Some(outlet_node) = self.graph.node_weight(outlet_node_id) {
-                    if outlet_node.node_type == DefGraphNodeType::Outlet {
-                        let stream_node_id = self
-                            .graph
-                            .neighbors_directed(outlet_node_id, petgraph::Outgoing)
-                            .next() // Assuming there is only one stream node connected to the outlet
-                            .expect("Outlet node should have an outgoing stream node");
-                        let outlet_node_id = outlet_node_id.index() as u32;
-                        let stream_node_id = stream_node_id.index() as u32;
-                        Some((outlet_node_id, stream_node_id))
-                    } else {
-                        None
-                    }
-                } else {
-                    None
-                }
+    /// Returns one `(outlet, stream)` pair per outlet of the spec. Fails if the spec does not
+    /// exist or one of its outlets feeds no stream.
+    pub fn get_outbound_stream_nodes(
+        &self,
+        spec_node_id: u32,
+    ) -> Result<Vec<(u32, u32)>, DefGraphError> {
+        self.spec_ports(spec_node_id, PortDirection::Out)?
+            .into_iter()
+            .map(|outlet| {
+                let stream = self
+                    .neighbor_indices_of_types(
+                        outlet,
+                        petgraph::Outgoing,
+                        &[DefGraphNodeType::StreamDef],
+                    )
+                    .into_iter()
+                    .next()
+                    .ok_or_else(|| DefGraphError::MalformedNode {
+                        node_id: outlet.index() as u32,
+                        reason: "outlet feeds no stream".to_string(),
+                    })?;
+                Ok((outlet.index() as u32, stream.index() as u32))
             })
             .collect()
     }
 
+    /// The Inlets (`In`) or Outlets (`Out`) of a spec node, in the order the graph stores
+    /// their edges.
+    fn spec_ports(
+        &self,
+        spec_node_id: u32,
+        direction: PortDirection,
+    ) -> Result<Vec<NodeIndex>, DefGraphError> {
+        let spec = NodeIndex::new(spec_node_id as usize);
+        if !self.graph.contains_node(spec) {
+            return Err(DefGraphError::NodeNotFound(spec_node_id));
+        }
+        let neighbors = match direction {
+            PortDirection::In => petgraph::Incoming,
+            PortDirection::Out => petgraph::Outgoing,
+        };
+        let port_type = direction.port_node_type();
+        Ok(self
+            .graph
+            .neighbors_directed(spec, neighbors)
+            .filter(|&port| self.graph[port].node_type == port_type)
+            .collect())
+    }
+
     pub fn filter_inbound_neighbors<F>(&self, index: u32, mut condition: F) -> Vec<NodeIndex>
     where
         F: FnMut(&DefGraphNode) -> bool,
@@ -582,10 +1039,12 @@ impl DefGraph {
                         spec_name: node.spec_name.clone(),
                         unique_spec_label: node.unique_spec_label.clone(),
                         tag: node.tag.clone(),
-                        has_transform: node.has_transform.clone(),
+                        has_transform: node.has_transform,
                         stream_def_id: node.stream_def_id.clone(),
                         alias: node.alias.clone(),
-                        direction: node.direction.clone(),
+                        direction: node.direction,
+                        merge_policy: node.merge_policy.clone(),
+                        json_schema_str: node.json_schema_str.clone(),
                         label: node.label.clone(),
                     })
                     {
@@ -598,35 +1057,34 @@ impl DefGraph {
             .collect()
     }
     pub fn ensure_edge(&mut self, from_index: u32, to_index: u32) {
+        self.try_ensure_edge(from_index, to_index)
+            .unwrap_or_else(|e| panic!("{}", e))
+    }
+
+    /// Fallible version of [`DefGraph::ensure_edge`]; fails if either endpoint is missing.
+    pub fn try_ensure_edge(&mut self, from_index: u32, to_index: u32) -> Result<(), DefGraphError> {
+        for index in [from_index, to_index] {
+            if self.graph.node_weight(NodeIndex::new(index as usize)).is_none() {
+                return Err(DefGraphError::NodeNotFound(index));
+            }
+        }
         let from_index = NodeIndex::new(from_index as usize);
         let to_index = NodeIndex::new(to_index as usize);
         if !self.graph.contains_edge(from_index, to_index) {
-            self.graph.add_edge(from_index, to_index, ());
+            self.graph.add_edge(from_index, to_index, EdgeMeta::default());
+            self.indexes.insert_edge(&self.graph, from_index, to_index);
         }
+        Ok(())
     }
+    /// First node, in node id order, matching `condition`. This visits every node; use
+    /// [`DefGraph::find_spec_node`] to look up a spec.
     pub fn find_node<F>(&self, mut condition: F) -> Option<u32>
     where
         F: FnMut(&DefGraphNode) -> bool,
     {
         self.graph
             .node_indices()
-            .find(|&index| {
-                if let Some(node) = self.graph.node_weight(index) {
-                    condition(&DefGraphNode {
-                        node_type: node.node_type.clone(),
-                        spec_name: node.spec_name.clone(),
-                        unique_spec_label: node.unique_spec_label.clone(),
-                        tag: node.tag.clone(),
-                        has_transform: node.has_transform.clone(),
-                        stream_def_id: node.stream_def_id.clone(),
-                        alias: node.alias.clone(),
-                        direction: node.direction.clone(),
-                        label: node.label.clone(),
-                    })
-                } else {
-                    false
-                }
-            })
+            .find(|&index| condition(&self.graph[index]))
             .map(|index| index.index() as u32)
     }
 
@@ -635,21 +1093,36 @@ impl DefGraph {
         s: SpecTagInfo,
         has_transform: bool,
     ) -> (u32, u32) {
+        self.try_ensure_inlet_and_stream(s, has_transform)
+            .unwrap_or_else(|e| panic!("{}", e))
+    }
+
+    /// Fallible version of [`DefGraph::ensure_inlet_and_stream`]; fails with `PortNotFound`
+    /// on a composite spec that exposes no such input. The graph is left untouched when an
+    /// error is returned.
+    pub fn try_ensure_inlet_and_stream(
+        &mut self,
+        s: SpecTagInfo,
+        has_transform: bool,
+    ) -> Result<(u32, u32), DefGraphError> {
         let spec_name = s.spec_name;
         let tag = s.tag;
-        let spec_id = unique_spec_identifier(spec_name.to_string(), None);
+        let unique_spec_label = s.unique_spec_label;
+        let spec_id = unique_spec_identifier(spec_name.to_string(), unique_spec_label.clone());
+        self.check_composite_port(&spec_name, &spec_id, &tag, PortDirection::In)?;
         let spec_node_id = self.ensure_node(
             &spec_id,
             DefGraphNode {
                 node_type: DefGraphNodeType::Spec,
-                spec_name: Some(spec_id.clone()),
-                unique_spec_label: None,
+                spec_name: Some(spec_name.clone()),
+                unique_spec_label: unique_spec_label.clone(),
                 tag: None,
                 has_transform: None,
                 stream_def_id: None,
                 alias: None,
                 direction: None,
                 label: spec_id.clone(),
+                ..Default::default()
             },
         );
 
@@ -665,37 +1138,24 @@ impl DefGraph {
                 alias: None,
                 direction: None,
                 label: format!("{}/{}", spec_id, tag),
+                ..Default::default()
             },
         );
 
-        // let stream_def_id = unique_stream_identifier(None, Some(SpecTagInfo {
-        //     spec_name: spec_name.clone(),
-        //     tag: tag.clone(),
-        //     unique_spec_label: None,
-        // }));
-
         // check if stream_def_id exists in hash; if not, initialize
         let stream_def_id = match self.stream_node_id_by_spec_identifier_type_and_tag.get(
-            format!("{}::in/{}", spec_name, tag).as_str()) {
+            stream_def_key(&spec_id, PortDirection::In, &tag).as_str()) {
             Some(stream_def_id) => {
                 stream_def_id.clone()
             },
-            None => {
-                let stream_def_id = unique_stream_identifier(
-                    None,
-                    Some(SpecTagInfo {
-                        spec_name: spec_name.clone(),
-                        tag: tag.clone(),
-                        unique_spec_label: None,
-                    }),
-                );
-                let stream_def_id0 = stream_def_id.clone();
-                // self.stream_node_id_by_spec_identifier_type_and_tag.insert(
-                //     format!("{}::in/{}", spec_name, tag),
-                //     stream_def_id.clone()
-                // );
-                stream_def_id0
-            }
+            None => self.id_scheme.stream_def_id(
+                None,
+                Some(&SpecTagInfo {
+                    spec_name: spec_name.clone(),
+                    tag: tag.clone(),
+                    unique_spec_label,
+                }),
+            ),
         };
 
 
@@ -711,39 +1171,48 @@ impl DefGraph {
                 alias: None,
                 direction: None,
                 label: stream_def_id.clone(),
+                ..Default::default()
             },
         );
-        let stream_node_id_index = NodeIndex::new(stream_node_id as usize);
-        let inlet_node_id_index = NodeIndex::new(inlet_node_id as usize);
-        let spec_node_id_index = NodeIndex::new(spec_node_id as usize);
-
-        self.graph
-            .add_edge(stream_node_id_index, inlet_node_id_index, ());
-        self.graph
-            .add_edge(inlet_node_id_index, spec_node_id_index, ());
+        self.ensure_edge(stream_node_id, inlet_node_id);
+        self.ensure_edge(inlet_node_id, spec_node_id);
 
-        (inlet_node_id, stream_node_id)
+        Ok((inlet_node_id, stream_node_id))
     }
 
     pub fn ensure_outlet_and_stream(&mut self, s: SpecTagInfo) -> (u32, u32) {
+        self.try_ensure_outlet_and_stream(s)
+            .unwrap_or_else(|e| panic!("{}", e))
+    }
+
+    /// Fallible version of [`DefGraph::ensure_outlet_and_stream`]; fails with `PortNotFound`
+    /// on a composite spec that exposes no such output. The graph is left untouched when an
+    /// error is returned.
+    pub fn try_ensure_outlet_and_stream(
+        &mut self,
+        s: SpecTagInfo,
+    ) -> Result<(u32, u32), DefGraphError> {
         let spec_name = s.spec_name;
         let tag = s.tag;
+        let unique_spec_label = s.unique_spec_label;
         let spec_name0: String = spec_name.clone();
         
-        let spec_id = unique_spec_identifier(spec_name, None);
+        let spec_id = unique_spec_identifier(spec_name, unique_spec_label.clone());
+        self.check_composite_port(&spec_name0, &spec_id, &tag, PortDirection::Out)?;
         
         let spec_node_id = self.ensure_node(
             &spec_id,
             DefGraphNode {
                 node_type: DefGraphNodeType::Spec,
-                spec_name: Some(spec_id.clone()),
-                unique_spec_label: None,
+                spec_name: Some(spec_name0.clone()),
+                unique_spec_label: unique_spec_label.clone(),
                 tag: None,
                 has_transform: None,
                 stream_def_id: None,
                 alias: None,
                 direction: None,
                 label: spec_id.clone(),
+                ..Default::default()
             },
         );
 
@@ -759,37 +1228,24 @@ impl DefGraph {
                 alias: None,
                 direction: None,
                 label: format!("{}_{}", spec_id, tag),
+                ..Default::default()
             },
         );
-        
-        // let stream_def_id = unique_stream_identifier(Some(SpecTagInfo {
-        //     spec_name: spec_name0.clone(),
-        //     tag: tag.clone(),
-        //     unique_spec_label: None,
-        // }), None);
 
         // check if stream_def_id exists in hash; if not, initialize
         let stream_def_id = match self.stream_node_id_by_spec_identifier_type_and_tag.get(
-            format!("{}::out/{}", spec_name0, tag).as_str()) {
+            stream_def_key(&spec_id, PortDirection::Out, &tag).as_str()) {
             Some(stream_def_id) => {
                 stream_def_id.clone()
             },
-            None => {
-                let stream_def_id = unique_stream_identifier(
-                    Some(SpecTagInfo {
-                        spec_name: spec_name0.clone(),
-                        tag: tag.clone(),
-                        unique_spec_label: None,
-                    }),
-                    None,
-                );
-                let stream_def_id0 = stream_def_id.clone();
-                // self.stream_node_id_by_spec_identifier_type_and_tag.insert(
-                //     format!("{}::out/{}", spec_name0, tag),
-                //     stream_def_id.clone()
-                // );
-                stream_def_id0
-            }
+            None => self.id_scheme.stream_def_id(
+                Some(&SpecTagInfo {
+                    spec_name: spec_name0.clone(),
+                    tag: tag.clone(),
+                    unique_spec_label,
+                }),
+                None,
+            ),
         };
 
         let stream_node_id = self.ensure_node(
@@ -804,24 +1260,30 @@ impl DefGraph {
                 alias: None,
                 direction: None,
                 label: stream_def_id.clone(),
+                ..Default::default()
             },
         );
-        let spec_node_id_index = NodeIndex::new(spec_node_id as usize);
-        let outlet_node_id_index = NodeIndex::new(outlet_node_id as usize);
-        let stream_node_id_index = NodeIndex::new(stream_node_id as usize);
-        self.graph
-            .add_edge(spec_node_id_index, outlet_node_id_index, ());
-        self.graph
-            .add_edge(outlet_node_id_index, stream_node_id_index, ());
+        self.ensure_edge(spec_node_id, outlet_node_id);
+        self.ensure_edge(outlet_node_id, stream_node_id);
 
-        (outlet_node_id, stream_node_id)
+        Ok((outlet_node_id, stream_node_id))
     }
 
     // #[napi(constructor)]
     pub fn new(root_spec_name: String, input_tags: Vec<String>, output_tags: Vec<String>) -> Self {
+        Self::new_with_id_scheme(root_spec_name, input_tags, output_tags, Arc::new(ReadableIds))
+    }
+
+    /// Like [`DefGraph::new`], with stream definition ids generated by `id_scheme`.
+    pub fn new_with_id_scheme(
+        root_spec_name: String,
+        input_tags: Vec<String>,
+        output_tags: Vec<String>,
+        id_scheme: Arc<dyn IdScheme>,
+    ) -> Self {
         let root_spec_name_c0: String = root_spec_name.clone();
         let root_spec_name_c1 = root_spec_name.clone();
-        let mut graph = DiGraph::<DefGraphNode, ()>::new();
+        let mut graph = StableDiGraph::<DefGraphNode, EdgeMeta>::new();
         let mut node_indices = HashMap::new();
         let root_spec_id = unique_spec_identifier(root_spec_name, None);
         let root_spec_node_id = graph.add_node(DefGraphNode {
@@ -834,6 +1296,7 @@ impl DefGraph {
             alias: None,
             direction: None,
             label: root_spec_id.clone(),
+            ..Default::default()
         });
         node_indices.insert(root_spec_id.clone(), root_spec_node_id);
         let mut stream_node_id_by_spec_identifier_type_and_tag: HashMap<String, String> = HashMap::new();
@@ -850,20 +1313,21 @@ impl DefGraph {
                 alias: None,
                 direction: None,
                 label: format!("{}/{}", root_spec_id, tag),
+                ..Default::default()
             });
 
             // check hash first; if not found, initialize
             let stream_def_id = stream_node_id_by_spec_identifier_type_and_tag.get(
-            format!("{}::in/{}", root_spec_name_c1, tag).as_str());
+            stream_def_key(&root_spec_id, PortDirection::In, tag).as_str());
 
             let stream_def_id = match stream_def_id {
                 Some(stream_def_id) => {
                 stream_def_id.clone()
                 },
                 None => {
-                    let stream_def_id = unique_stream_identifier(
+                    let stream_def_id = id_scheme.stream_def_id(
                         None,
-                        Some(SpecTagInfo {
+                        Some(&SpecTagInfo {
                             spec_name: root_spec_name_c1.clone(),
                             tag: tag.clone(),
                             unique_spec_label: None,
@@ -871,10 +1335,10 @@ impl DefGraph {
                     );
                    
                     let stream_def_id0 = stream_def_id.clone();
-                    // stream_node_id_by_spec_identifier_type_and_tag.insert(
-                    //     format!("{}/{}", root_spec_name_c1, tag),
-                    //     stream_def_id.clone()
-                    // );
+                    stream_node_id_by_spec_identifier_type_and_tag.insert(
+                        stream_def_key(&root_spec_id, PortDirection::In, tag),
+                        stream_def_id.clone()
+                    );
                     stream_def_id0
                 }
             };
@@ -889,9 +1353,12 @@ impl DefGraph {
                 alias: None,
                 direction: None,
                 label: stream_def_id.clone(),
+                ..Default::default()
             });
-            graph.add_edge(stream_node_id, inlet_node_id, ());
-            graph.add_edge(inlet_node_id, root_spec_node_id, ());
+            graph.add_edge(stream_node_id, inlet_node_id, EdgeMeta::default());
+            graph.add_edge(inlet_node_id, root_spec_node_id, EdgeMeta::default());
+            node_indices.insert(format!("Inlet_{}/{}", root_spec_id, tag), inlet_node_id);
+            node_indices.insert(format!("StreamDef_{}", stream_def_id), stream_node_id);
         }
 
         for tag in output_tags.iter() {
@@ -905,19 +1372,20 @@ impl DefGraph {
                 alias: None,
                 direction: None,
                 label: format!("{}/{}", root_spec_id, tag),
+                ..Default::default()
             });
            
             // check hash first; if not found, initialize
             let stream_def_id = stream_node_id_by_spec_identifier_type_and_tag.get(
-            format!("{}::out/{}", root_spec_name_c1, tag).as_str());
+            stream_def_key(&root_spec_id, PortDirection::Out, tag).as_str());
 
             let stream_def_id = match stream_def_id {
                 Some(stream_def_id) => {
                 stream_def_id.clone()
                 },
                 None => {
-                    let stream_def_id = unique_stream_identifier(
-                        Some(SpecTagInfo {
+                    let stream_def_id = id_scheme.stream_def_id(
+                        Some(&SpecTagInfo {
                             spec_name: root_spec_name_c1.clone(),
                             tag: tag.clone(),
                             unique_spec_label: None,
@@ -926,7 +1394,7 @@ impl DefGraph {
                     );
                     let stream_def_id0 = stream_def_id.clone();
                     stream_node_id_by_spec_identifier_type_and_tag.insert(
-                        format!("{}/{}", root_spec_name_c1, tag),
+                        stream_def_key(&root_spec_id, PortDirection::Out, tag),
                         stream_def_id.clone()
                     );
                     stream_def_id0
@@ -944,32 +1412,40 @@ impl DefGraph {
                 alias: None,
                 direction: None,
                 label: stream_def_id.clone(),
+                ..Default::default()
             });
-            graph.add_edge(root_spec_node_id, outlet_node_id, ());
-            graph.add_edge(outlet_node_id, stream_node_id, ());
+            graph.add_edge(root_spec_node_id, outlet_node_id, EdgeMeta::default());
+            graph.add_edge(outlet_node_id, stream_node_id, EdgeMeta::default());
+            node_indices.insert(format!("Outlet_{}/{}", root_spec_id, tag), outlet_node_id);
+            node_indices.insert(format!("StreamDef_{}", stream_def_id), stream_node_id);
         }
 
+        let indexes = DefGraphIndex::build(&graph);
         Self {
             graph,
             node_indices,
-            stream_node_id_by_spec_identifier_type_and_tag
+            stream_node_id_by_spec_identifier_type_and_tag,
+            composites: BTreeMap::new(),
+            indexes,
+            id_scheme,
         }
     }
 
+    /// The scheme that generates the `stream_def_id` of each new StreamDef.
+    pub fn id_scheme(&self) -> &Arc<dyn IdScheme> {
+        &self.id_scheme
+    }
+
+    /// Replaces the id scheme, e.g. on a graph loaded from JSON that was saved with a custom
+    /// scheme. Only StreamDefs created afterwards are affected.
+    pub fn set_id_scheme(&mut self, id_scheme: Arc<dyn IdScheme>) {
+        self.id_scheme = id_scheme;
+    }
+
     pub fn get_spec_node_ids(&self) -> Vec<u32> {
-        self.graph
-            .node_indices()
-            .filter_map(|index| {
-                if let Some(node) = self.graph.node_weight(index) {
-                    if node.node_type == DefGraphNodeType::Spec {
-                        Some(index.index() as u32)
-                    } else {
-                        None
-                    }
-                } else {
-                    None
-                }
-            })
+        self.indexes
+            .of_type(&DefGraphNodeType::Spec)
+            .map(|index| index.index() as u32)
             .collect()
     }
 
@@ -986,9 +1462,12 @@ impl DefGraph {
                     has_transform: data.has_transform,
                     stream_def_id: data.stream_def_id.clone(),
                     alias: data.alias.clone(),
-                    direction: data.direction.clone(),
+                    direction: data.direction,
+                    merge_policy: data.merge_policy.clone(),
+                    json_schema_str: data.json_schema_str.clone(),
                     label: data.label.clone(),
                 });
+                self.indexes.insert(index, &self.graph[index]);
                 self.node_indices.insert(full_node_id.to_string(), index);
                 index.index() as u32
             }
@@ -1009,10 +1488,12 @@ impl DefGraph {
                         spec_name: node.spec_name.clone(),
                         unique_spec_label: node.unique_spec_label.clone(),
                         tag: node.tag.clone(),
-                        has_transform: node.has_transform.clone(),
+                        has_transform: node.has_transform,
                         stream_def_id: node.stream_def_id.clone(),
                         alias: node.alias.clone(),
-                        direction: node.direction.clone(),
+                        direction: node.direction,
+                        merge_policy: node.merge_policy.clone(),
+                        json_schema_str: node.json_schema_str.clone(),
                         label: node.label.clone(),
                     })
                     {
@@ -1025,6 +1506,19 @@ impl DefGraph {
             .collect()
     }
 
+    /// Neighbors of `index` in `direction` whose node type is one of `types`.
+    pub(crate) fn neighbor_indices_of_types(
+        &self,
+        index: NodeIndex,
+        direction: petgraph::Direction,
+        types: &[DefGraphNodeType],
+    ) -> Vec<NodeIndex> {
+        self.graph
+            .neighbors_directed(index, direction)
+            .filter(|&neighbor| types.contains(&self.graph[neighbor].node_type))
+            .collect()
+    }
+
     pub fn inbound_neighbors(&self, node_id: u32) -> Vec<u32> {
         let node_id = NodeIndex::new(node_id as usize);
         self.graph
@@ -0,0 +1,147 @@
+//! Root-spec aliases as a whole: listing them with the ports they expose, renaming them and
+//! keeping one name from being bound to two ports.
+//!
+//! An alias name identifies a single Alias node, whatever its direction, so a name bound
+//! as an input cannot also be an output, and a name bound to one port cannot be bound to
+//! another until it is removed or renamed.
+
+use crate::systems::def_graph::{
+    node_type_to_string, DefGraph, DefGraphError, DefGraphNodeType, PortDirection,
+};
+use crate::systems::def_graph_diff::PortIdentity;
+use petgraph::graph::NodeIndex;
+use petgraph::Direction;
+use serde::{Deserialize, Serialize};
+
+/// A root-spec alias and the spec port it exposes.
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct AliasInfo {
+    pub direction: PortDirection,
+    pub alias: String,
+    pub alias_node_id: u32,
+    pub spec_name: String,
+    pub unique_spec_label: Option<String>,
+    pub tag: String,
+}
+
+impl DefGraph {
+    /// Every alias bound to a spec port, inputs first and then by name. These are the
+    /// public inputs and outputs of the workflow.
+    pub fn aliases(&self) -> Vec<AliasInfo> {
+        let mut aliases: Vec<AliasInfo> = self
+            .indexes
+            .of_type(&DefGraphNodeType::Alias)
+            .filter_map(|index| {
+                let node = &self.graph[index];
+                let port = self.alias_port(index)?;
+                let spec_direction = match node.direction? {
+                    PortDirection::In => Direction::Outgoing,
+                    PortDirection::Out => Direction::Incoming,
+                };
+                let spec = self
+                    .neighbor_indices_of_types(port, spec_direction, &[DefGraphNodeType::Spec])
+                    .into_iter()
+                    .next()?;
+                Some(AliasInfo {
+                    direction: node.direction?,
+                    alias: node.alias.clone()?,
+                    alias_node_id: index.index() as u32,
+                    spec_name: self.graph[spec].spec_name.clone()?,
+                    unique_spec_label: self.graph[spec].unique_spec_label.clone(),
+                    tag: self.graph[port].tag.clone()?,
+                })
+            })
+            .collect();
+        aliases.sort();
+        aliases
+    }
+
+    /// Gives the alias `alias` of the given direction the name `new_alias`, keeping the
+    /// port it exposes. Fails with [`DefGraphError::AliasConflict`] if `new_alias` is
+    /// already in use. Returns the alias node id, which does not change.
+    pub fn rename_alias(
+        &mut self,
+        alias: &str,
+        direction: PortDirection,
+        new_alias: &str,
+    ) -> Result<u32, DefGraphError> {
+        let index = self
+            .alias_index(alias)
+            .filter(|&index| self.graph[index].direction == Some(direction))
+            .ok_or_else(|| DefGraphError::AliasNotFound {
+                alias: alias.to_string(),
+                direction,
+            })?;
+        if new_alias != alias {
+            if let Some(existing) = self.alias_index(new_alias) {
+                return Err(self.alias_conflict(existing));
+            }
+            let alias_id = format!(
+                "{}/{}",
+                self.root_spec_name().unwrap_or_default(),
+                new_alias
+            );
+            let key_prefix = node_type_to_string(&DefGraphNodeType::Alias);
+            self.node_indices.retain(|_, &mut i| i != index);
+            self.node_indices
+                .insert(format!("{}_{}", key_prefix, alias_id), index);
+            let node = &mut self.graph[index];
+            node.alias = Some(new_alias.to_string());
+            node.label = alias_id;
+            self.indexes.rename_alias(index, alias, new_alias);
+        }
+        Ok(index.index() as u32)
+    }
+
+    /// Fails if `alias` is bound to anything other than `port` in `direction`. Binding an
+    /// alias again to the port it already exposes is allowed.
+    pub(crate) fn check_alias_available(
+        &self,
+        alias: &str,
+        direction: PortDirection,
+        port: &PortIdentity,
+    ) -> Result<(), DefGraphError> {
+        let Some(existing) = self.alias_index(alias) else {
+            return Ok(());
+        };
+        let bound = self
+            .alias_port(existing)
+            .and_then(|index| self.port_identity(index));
+        if self.graph[existing].direction == Some(direction)
+            && bound.as_ref().is_none_or(|bound| bound == port)
+        {
+            return Ok(());
+        }
+        Err(self.alias_conflict(existing))
+    }
+
+    fn alias_conflict(&self, existing: NodeIndex) -> DefGraphError {
+        let node = &self.graph[existing];
+        DefGraphError::AliasConflict {
+            alias: node.alias.clone().unwrap_or_default(),
+            direction: node.direction.unwrap_or(PortDirection::In),
+            port: self
+                .alias_port(existing)
+                .and_then(|index| self.port_identity(index))
+                .map(|port| format!("{}/{}", port.spec_identifier, port.tag))
+                .unwrap_or_default(),
+        }
+    }
+
+    fn alias_index(&self, alias: &str) -> Option<NodeIndex> {
+        self.indexes.aliases_named(alias).next()
+    }
+
+    /// The Inlet an input alias is fed by, or the Outlet an output alias feeds from.
+    fn alias_port(&self, alias: NodeIndex) -> Option<NodeIndex> {
+        let direction = self.graph[alias].direction?;
+        let neighbors = match direction {
+            PortDirection::In => Direction::Incoming,
+            PortDirection::Out => Direction::Outgoing,
+        };
+        self.neighbor_indices_of_types(alias, neighbors, &[direction.port_node_type()])
+            .into_iter()
+            .next()
+    }
+}
@@ -0,0 +1,378 @@
+use crate::systems::def_graph::{
+    node_type_to_string, DefGraph, DefGraphError, DefGraphNode, DefGraphNodeType, EdgeMeta,
+    MergePolicy, PortDirection,
+};
+use crate::systems::def_graph_aliases::AliasInfo;
+use crate::systems::def_graph_utils::{
+    unique_spec_identifier, FromSpecAndTag, SpecTagInfo, ToSpecAndTag,
+};
+use petgraph::graph::NodeIndex;
+use petgraph::Direction;
+
+/// A spec-to-spec hop described by names and tags, so it can be replayed on another graph.
+struct PortConnection {
+    from: SpecTagInfo,
+    to: SpecTagInfo,
+    has_transform: bool,
+    merge_policy: Option<MergePolicy>,
+    meta: EdgeMeta,
+    from_schema: Option<String>,
+    to_schema: Option<String>,
+}
+
+impl DefGraph {
+    /// Embeds `child` as a composite spec named after the child's root spec.
+    ///
+    /// The child's root aliases become the composite's ports: each `"in"` alias is an inlet
+    /// and each `"out"` alias an outlet, tagged with the alias name. Connect to them with the
+    /// usual builder methods. Returns the composite's Spec node id.
+    pub fn try_add_composite_spec(
+        &mut self,
+        child: DefGraph,
+        unique_spec_label: Option<&str>,
+    ) -> Result<u32, DefGraphError> {
+        let spec_name = child
+            .root_spec_name()
+            .ok_or_else(|| DefGraphError::RootSpecNotFound {
+                root_spec_name: String::new(),
+            })?;
+        let spec_id =
+            unique_spec_identifier(spec_name.clone(), unique_spec_label.map(|l| l.to_string()));
+        if self.node_indices.contains_key(&format!("Spec_{}", spec_id)) {
+            return Err(DefGraphError::SpecAlreadyExists {
+                spec_identifier: spec_id,
+            });
+        }
+
+        let node_id = self.ensure_node(
+            &spec_id,
+            DefGraphNode {
+                node_type: DefGraphNodeType::Spec,
+                spec_name: Some(spec_name),
+                unique_spec_label: unique_spec_label.map(|l| l.to_string()),
+                tag: None,
+                has_transform: None,
+                stream_def_id: None,
+                alias: None,
+                direction: None,
+                label: spec_id.clone(),
+                ..Default::default()
+            },
+        );
+        self.composites.insert(spec_id, child);
+        Ok(node_id)
+    }
+
+    /// The graph embedded behind a composite spec, if the spec is one.
+    pub fn get_composite(
+        &self,
+        spec_name: &str,
+        unique_spec_label: Option<&str>,
+    ) -> Option<&DefGraph> {
+        self.composites.get(&unique_spec_identifier(
+            spec_name.to_string(),
+            unique_spec_label.map(|l| l.to_string()),
+        ))
+    }
+
+    /// `unique_spec_identifier`s of the composite specs directly inside this graph, sorted.
+    pub fn get_composite_spec_identifiers(&self) -> Vec<String> {
+        self.composites.keys().cloned().collect()
+    }
+
+    /// Fails with `PortNotFound` when `spec_id` is a composite without a matching alias.
+    pub(crate) fn check_composite_port(
+        &self,
+        spec_name: &str,
+        spec_id: &str,
+        tag: &str,
+        direction: PortDirection,
+    ) -> Result<(), DefGraphError> {
+        match self.composites.get(spec_id) {
+            Some(child)
+                if child
+                    .lookup_spec_and_tag_by_alias(tag.to_string(), direction)
+                    .is_none() =>
+            {
+                Err(DefGraphError::PortNotFound {
+                    spec_name: spec_name.to_string(),
+                    tag: tag.to_string(),
+                    direction,
+                })
+            }
+            _ => Ok(()),
+        }
+    }
+
+    /// Returns a copy of this graph with every composite spec replaced by the specs inside
+    /// it, recursively. Inner specs get a `unique_spec_label` path prefixed by the composite's
+    /// escaped `unique_spec_identifier`, e.g. `ASR` inside `voice` becomes `ASR[voice]`, a
+    /// labeled `ASR[en]` becomes `ASR[voice/en]`, and `ASR` inside `pipe[voice]` gets the
+    /// label `pipe[voice]`, so composites sharing a name or label keep their specs apart.
+    pub fn flatten(&self) -> Result<DefGraph, DefGraphError> {
+        let mut flat = self.clone();
+        flat.composites.clear();
+        for (spec_id, child) in &self.composites {
+            let composite = self
+                .find_node(|n| n.node_type == DefGraphNodeType::Spec && &n.label == spec_id)
+                .map(|id| self.graph[NodeIndex::new(id as usize)].clone())
+                .ok_or_else(|| DefGraphError::SpecNotFound {
+                    spec_name: spec_id.clone(),
+                    unique_spec_label: None,
+                })?;
+            let spec_name = composite.spec_name.clone().unwrap_or_default();
+            let label = composite.unique_spec_label.clone();
+            let prefix = unique_spec_identifier(spec_name.clone(), label.clone());
+            let child = child.flatten()?;
+
+            let is_composite =
+                |info: &SpecTagInfo| info.spec_name == spec_name && info.unique_spec_label == label;
+            let outer: Vec<PortConnection> = flat.port_connections();
+            let outer_aliases: Vec<AliasInfo> = flat
+                .aliases()
+                .into_iter()
+                .filter(|a| a.spec_name == spec_name && a.unique_spec_label == label)
+                .collect();
+            for c in &outer {
+                if is_composite(&c.from) || is_composite(&c.to) {
+                    flat.disconnect(&c.from, &c.to)?;
+                }
+            }
+            flat.remove_spec(&spec_name, label.as_deref())?;
+
+            let relabel = |info: &SpecTagInfo| SpecTagInfo {
+                spec_name: info.spec_name.clone(),
+                unique_spec_label: Some(match &info.unique_spec_label {
+                    Some(inner) => format!("{}/{}", prefix, inner),
+                    None => prefix.clone(),
+                }),
+                tag: info.tag.clone(),
+            };
+            let inner_port = |tag: &str, direction: PortDirection| {
+                child
+                    .lookup_spec_and_tag_by_alias(tag.to_string(), direction)
+                    .map(|port| relabel(&port))
+                    .ok_or_else(|| DefGraphError::PortNotFound {
+                        spec_name: spec_name.clone(),
+                        tag: tag.to_string(),
+                        direction,
+                    })
+            };
+
+            for index in child.graph.node_indices() {
+                let node = &child.graph[index];
+                if node.node_type == DefGraphNodeType::Spec {
+                    let spec = relabel(&SpecTagInfo {
+                        spec_name: node.spec_name.clone().unwrap_or_default(),
+                        unique_spec_label: node.unique_spec_label.clone(),
+                        tag: String::new(),
+                    });
+                    flat.ensure_spec_node(&spec.spec_name, spec.unique_spec_label.as_deref());
+                }
+            }
+            for c in child.port_connections() {
+                flat.replay_connection(PortConnection {
+                    from: relabel(&c.from),
+                    to: relabel(&c.to),
+                    ..c
+                })?;
+            }
+            for c in outer {
+                match (is_composite(&c.from), is_composite(&c.to)) {
+                    (false, true) => flat.replay_connection(PortConnection {
+                        to: inner_port(&c.to.tag, PortDirection::In)?,
+                        ..c
+                    })?,
+                    (true, false) => flat.replay_connection(PortConnection {
+                        from: inner_port(&c.from.tag, PortDirection::Out)?,
+                        ..c
+                    })?,
+                    (true, true) => flat.replay_connection(PortConnection {
+                        from: inner_port(&c.from.tag, PortDirection::Out)?,
+                        to: inner_port(&c.to.tag, PortDirection::In)?,
+                        ..c
+                    })?,
+                    (false, false) => {}
+                }
+            }
+            let root_spec_name = flat.root_spec_name().unwrap_or_default();
+            for a in outer_aliases {
+                let port = inner_port(&a.tag, a.direction)?;
+                flat.ensure_port_node(&port, a.direction);
+                flat.try_assign_alias(
+                    &a.alias,
+                    &port.spec_name,
+                    &root_spec_name,
+                    port.unique_spec_label.as_deref(),
+                    a.direction,
+                    &port.tag,
+                )?;
+            }
+        }
+        Ok(flat)
+    }
+
+    pub(crate) fn root_spec_name(&self) -> Option<String> {
+        let id = self.get_root_spec_node_id()?;
+        self.graph[NodeIndex::new(id as usize)].spec_name.clone()
+    }
+
+    /// Creates the Spec node for `spec_name[unique_spec_label]` unless it exists.
+    pub(crate) fn ensure_spec_node(
+        &mut self,
+        spec_name: &str,
+        unique_spec_label: Option<&str>,
+    ) -> u32 {
+        let spec_id = unique_spec_identifier(
+            spec_name.to_string(),
+            unique_spec_label.map(|l| l.to_string()),
+        );
+        self.ensure_node(
+            &spec_id,
+            DefGraphNode {
+                node_type: DefGraphNodeType::Spec,
+                spec_name: Some(spec_name.to_string()),
+                unique_spec_label: unique_spec_label.map(|l| l.to_string()),
+                tag: None,
+                has_transform: None,
+                stream_def_id: None,
+                alias: None,
+                direction: None,
+                label: spec_id.clone(),
+                ..Default::default()
+            },
+        )
+    }
+
+    /// Creates the Inlet or Outlet for `port`, label included, unless it
+    /// exists. A new port gets an unconnected stream, like the ones `ensure_inlet_and_stream`
+    /// and `ensure_outlet_and_stream` create.
+    pub(crate) fn ensure_port_node(&mut self, port: &SpecTagInfo, direction: PortDirection) -> u32 {
+        let spec_node_id =
+            self.ensure_spec_node(&port.spec_name, port.unique_spec_label.as_deref());
+        let spec_id =
+            unique_spec_identifier(port.spec_name.clone(), port.unique_spec_label.clone());
+        let port_label = format!("{}/{}", spec_id, port.tag);
+        let (port_type, stream_def_id) = if direction == PortDirection::In {
+            (
+                DefGraphNodeType::Inlet,
+                self.id_scheme.stream_def_id(None, Some(port)),
+            )
+        } else {
+            (
+                DefGraphNodeType::Outlet,
+                self.id_scheme.stream_def_id(Some(port), None),
+            )
+        };
+        let key = format!("{}_{}", node_type_to_string(&port_type), port_label);
+        if let Some(&existing) = self.node_indices.get(&key) {
+            return existing.index() as u32;
+        }
+
+        let port_node_id = self.ensure_node(
+            &port_label,
+            DefGraphNode {
+                node_type: port_type.clone(),
+                spec_name: None,
+                unique_spec_label: None,
+                tag: Some(port.tag.clone()),
+                has_transform: (port_type == DefGraphNodeType::Inlet).then_some(false),
+                stream_def_id: None,
+                alias: None,
+                direction: None,
+                label: port_label.clone(),
+                ..Default::default()
+            },
+        );
+        let stream_node_id = self.ensure_node(
+            &stream_def_id,
+            DefGraphNode {
+                node_type: DefGraphNodeType::StreamDef,
+                spec_name: None,
+                unique_spec_label: None,
+                tag: None,
+                has_transform: None,
+                stream_def_id: Some(stream_def_id.clone()),
+                alias: None,
+                direction: None,
+                label: stream_def_id.clone(),
+                ..Default::default()
+            },
+        );
+        if port_type == DefGraphNodeType::Inlet {
+            self.ensure_edge(stream_node_id, port_node_id);
+            self.ensure_edge(port_node_id, spec_node_id);
+        } else {
+            self.ensure_edge(spec_node_id, port_node_id);
+            self.ensure_edge(port_node_id, stream_node_id);
+        }
+        port_node_id
+    }
+
+    fn replay_connection(&mut self, c: PortConnection) -> Result<(), DefGraphError> {
+        self.try_add_connected_dual_specs_with_meta(
+            &FromSpecAndTag {
+                spec_name: c.from.spec_name.clone(),
+                output: c.from.tag.clone(),
+                unique_spec_label: c.from.unique_spec_label.clone(),
+            },
+            &ToSpecAndTag {
+                spec_name: c.to.spec_name.clone(),
+                input: c.to.tag.clone(),
+                has_transform: c.has_transform,
+                unique_spec_label: c.to.unique_spec_label.clone(),
+            },
+            c.meta,
+        )?;
+        if c.merge_policy.is_some() {
+            self.set_inlet_merge_policy(&c.to, c.merge_policy)?;
+        }
+        if c.from_schema.is_some() {
+            self.set_port_schema(&c.from, PortDirection::Out, c.from_schema)?;
+        }
+        if c.to_schema.is_some() {
+            self.set_port_schema(&c.to, PortDirection::In, c.to_schema)?;
+        }
+        Ok(())
+    }
+
+    fn port_connections(&self) -> Vec<PortConnection> {
+        self.get_spec_connections()
+            .into_iter()
+            .filter_map(|c| {
+                let stream = NodeIndex::new(c.stream_node_id as usize);
+                let inlet = NodeIndex::new(c.inlet_node_id as usize);
+                let outlet = self
+                    .neighbor_indices_of_types(
+                        stream,
+                        Direction::Incoming,
+                        &[DefGraphNodeType::Outlet],
+                    )
+                    .into_iter()
+                    .next()?;
+                Some(PortConnection {
+                    from: self.spec_tag_info(c.from_spec_node_id, outlet),
+                    to: self.spec_tag_info(c.to_spec_node_id, inlet),
+                    has_transform: self.graph[inlet].has_transform.unwrap_or(false),
+                    merge_policy: self.graph[inlet].merge_policy.clone(),
+                    meta: self
+                        .graph
+                        .find_edge(stream, inlet)
+                        .map(|edge| self.graph[edge].clone())
+                        .unwrap_or_default(),
+                    from_schema: self.graph[outlet].json_schema_str.clone(),
+                    to_schema: self.graph[inlet].json_schema_str.clone(),
+                })
+            })
+            .collect()
+    }
+
+    fn spec_tag_info(&self, spec_node_id: u32, port: NodeIndex) -> SpecTagInfo {
+        let spec = &self.graph[NodeIndex::new(spec_node_id as usize)];
+        SpecTagInfo {
+            spec_name: spec.spec_name.clone().unwrap_or_default(),
+            unique_spec_label: spec.unique_spec_label.clone(),
+            tag: self.graph[port].tag.clone().unwrap_or_default(),
+        }
+    }
+}
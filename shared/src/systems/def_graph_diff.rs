@@ -0,0 +1,376 @@
+use crate::systems::def_graph::{DefGraph, DefGraphNode, DefGraphNodeType, PortDirection};
+use crate::systems::def_graph_utils::unique_spec_identifier;
+use petgraph::graph::NodeIndex;
+use petgraph::Direction;
+use serde::{Deserialize, Serialize};
+
+use std::collections::{BTreeMap, BTreeSet};
+use std::fmt;
+
+/// An Inlet or Outlet identified by its spec and tag rather than by node id.
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PortIdentity {
+    pub spec_identifier: String,
+    pub tag: String,
+}
+
+/// An Inlet or Outlet that exists on only one side of a diff.
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PortChange {
+    pub direction: PortDirection,
+    pub port: PortIdentity,
+}
+
+/// An Outlet feeding an Inlet through a stream def, on only one side of a diff.
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ConnectionChange {
+    pub from: PortIdentity,
+    pub to: PortIdentity,
+}
+
+/// An alias that exists on only one side of a diff.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct AliasChange {
+    pub alias: String,
+    pub direction: PortDirection,
+    pub target: PortIdentity,
+}
+
+/// An alias present on both sides that points at a different port.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct AliasRetarget {
+    pub alias: String,
+    pub direction: PortDirection,
+    pub before: PortIdentity,
+    pub after: PortIdentity,
+}
+
+/// An inlet present on both sides whose `has_transform` flag differs.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct InletTransformChange {
+    pub spec_identifier: String,
+    pub tag: String,
+    pub before: bool,
+    pub after: bool,
+}
+
+/// What changed between two `DefGraph`s, as returned by [`DefGraph::diff`].
+///
+/// Entries are matched by identity (spec identifier, stream def id, spec and tag, the ports
+/// at both ends of a connection, alias name and direction) so node ids and insertion order
+/// play no part. Every list is sorted.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DefGraphDiff {
+    pub added_specs: Vec<String>,
+    pub removed_specs: Vec<String>,
+    pub added_ports: Vec<PortChange>,
+    pub removed_ports: Vec<PortChange>,
+    pub added_streams: Vec<String>,
+    pub removed_streams: Vec<String>,
+    pub added_connections: Vec<ConnectionChange>,
+    pub removed_connections: Vec<ConnectionChange>,
+    pub changed_inlet_transforms: Vec<InletTransformChange>,
+    pub added_aliases: Vec<AliasChange>,
+    pub removed_aliases: Vec<AliasChange>,
+    pub retargeted_aliases: Vec<AliasRetarget>,
+}
+
+impl DefGraphDiff {
+    /// True when both graphs have the same specs, ports, streams, connections, inlet
+    /// transforms and aliases. Port schemas, merge policies and edge metadata are not
+    /// compared; use [`DefGraph::fingerprint`] for those.
+    pub fn is_empty(&self) -> bool {
+        *self == DefGraphDiff::default()
+    }
+}
+
+/// One change per line, e.g. `+ spec Summarizer` or `~ alias in/query: A/q -> B/q`.
+impl fmt::Display for DefGraphDiff {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for spec in &self.added_specs {
+            writeln!(f, "+ spec {}", spec)?;
+        }
+        for spec in &self.removed_specs {
+            writeln!(f, "- spec {}", spec)?;
+        }
+        for p in &self.added_ports {
+            writeln!(
+                f,
+                "+ {} {}/{}",
+                port_kind(p.direction),
+                p.port.spec_identifier,
+                p.port.tag
+            )?;
+        }
+        for p in &self.removed_ports {
+            writeln!(
+                f,
+                "- {} {}/{}",
+                port_kind(p.direction),
+                p.port.spec_identifier,
+                p.port.tag
+            )?;
+        }
+        for stream in &self.added_streams {
+            writeln!(f, "+ stream {}", stream)?;
+        }
+        for stream in &self.removed_streams {
+            writeln!(f, "- stream {}", stream)?;
+        }
+        for c in &self.added_connections {
+            writeln!(
+                f,
+                "+ connection {}/{} -> {}/{}",
+                c.from.spec_identifier, c.from.tag, c.to.spec_identifier, c.to.tag
+            )?;
+        }
+        for c in &self.removed_connections {
+            writeln!(
+                f,
+                "- connection {}/{} -> {}/{}",
+                c.from.spec_identifier, c.from.tag, c.to.spec_identifier, c.to.tag
+            )?;
+        }
+        for c in &self.changed_inlet_transforms {
+            writeln!(
+                f,
+                "~ inlet {}/{} hasTransform: {} -> {}",
+                c.spec_identifier, c.tag, c.before, c.after
+            )?;
+        }
+        for a in &self.added_aliases {
+            writeln!(
+                f,
+                "+ alias {}/{} -> {}/{}",
+                a.direction, a.alias, a.target.spec_identifier, a.target.tag
+            )?;
+        }
+        for a in &self.removed_aliases {
+            writeln!(
+                f,
+                "- alias {}/{} -> {}/{}",
+                a.direction, a.alias, a.target.spec_identifier, a.target.tag
+            )?;
+        }
+        for a in &self.retargeted_aliases {
+            writeln!(
+                f,
+                "~ alias {}/{}: {}/{} -> {}/{}",
+                a.direction,
+                a.alias,
+                a.before.spec_identifier,
+                a.before.tag,
+                a.after.spec_identifier,
+                a.after.tag
+            )?;
+        }
+        Ok(())
+    }
+}
+
+fn port_kind(direction: PortDirection) -> &'static str {
+    match direction {
+        PortDirection::In => "inlet",
+        PortDirection::Out => "outlet",
+    }
+}
+
+impl DefGraph {
+    /// Describes how to get from `self` to `other`.
+    pub fn diff(&self, other: &DefGraph) -> DefGraphDiff {
+        let (before_specs, after_specs) = (self.spec_identifiers(), other.spec_identifiers());
+        let (before_ports, after_ports) = (self.ports(), other.ports());
+        let (before_streams, after_streams) = (self.stream_def_ids(), other.stream_def_ids());
+        let (before_connections, after_connections) = (self.connections(), other.connections());
+        let (before_inlets, after_inlets) = (self.inlet_transforms(), other.inlet_transforms());
+        let (before_aliases, after_aliases) = (self.alias_targets(), other.alias_targets());
+
+        let alias_change =
+            |(alias, direction): &(String, PortDirection), target: &PortIdentity| AliasChange {
+                alias: alias.clone(),
+                direction: *direction,
+                target: target.clone(),
+            };
+
+        DefGraphDiff {
+            added_specs: after_specs.difference(&before_specs).cloned().collect(),
+            removed_specs: before_specs.difference(&after_specs).cloned().collect(),
+            added_ports: after_ports.difference(&before_ports).cloned().collect(),
+            removed_ports: before_ports.difference(&after_ports).cloned().collect(),
+            added_streams: after_streams.difference(&before_streams).cloned().collect(),
+            removed_streams: before_streams.difference(&after_streams).cloned().collect(),
+            added_connections: after_connections
+                .difference(&before_connections)
+                .cloned()
+                .collect(),
+            removed_connections: before_connections
+                .difference(&after_connections)
+                .cloned()
+                .collect(),
+            changed_inlet_transforms: before_inlets
+                .iter()
+                .filter_map(|(port, &before)| {
+                    let &after = after_inlets.get(port)?;
+                    (before != after).then(|| InletTransformChange {
+                        spec_identifier: port.spec_identifier.clone(),
+                        tag: port.tag.clone(),
+                        before,
+                        after,
+                    })
+                })
+                .collect(),
+            added_aliases: after_aliases
+                .iter()
+                .filter(|(key, _)| !before_aliases.contains_key(*key))
+                .map(|(key, target)| alias_change(key, target))
+                .collect(),
+            removed_aliases: before_aliases
+                .iter()
+                .filter(|(key, _)| !after_aliases.contains_key(*key))
+                .map(|(key, target)| alias_change(key, target))
+                .collect(),
+            retargeted_aliases: before_aliases
+                .iter()
+                .filter_map(|((alias, direction), before)| {
+                    let after = after_aliases.get(&(alias.clone(), *direction))?;
+                    (before != after).then(|| AliasRetarget {
+                        alias: alias.clone(),
+                        direction: *direction,
+                        before: before.clone(),
+                        after: after.clone(),
+                    })
+                })
+                .collect(),
+        }
+    }
+
+    fn spec_identifiers(&self) -> BTreeSet<String> {
+        self.graph
+            .node_indices()
+            .filter(|&index| self.graph[index].node_type == DefGraphNodeType::Spec)
+            .map(|index| spec_identifier_of(&self.graph[index]))
+            .collect()
+    }
+
+    fn stream_def_ids(&self) -> BTreeSet<String> {
+        self.graph
+            .node_indices()
+            .filter_map(|index| {
+                let node = &self.graph[index];
+                (node.node_type == DefGraphNodeType::StreamDef).then(|| {
+                    node.stream_def_id
+                        .clone()
+                        .unwrap_or_else(|| node.label.clone())
+                })
+            })
+            .collect()
+    }
+
+    fn ports(&self) -> BTreeSet<PortChange> {
+        self.graph
+            .node_indices()
+            .filter_map(|index| {
+                let direction = match self.graph[index].node_type {
+                    DefGraphNodeType::Inlet => PortDirection::In,
+                    DefGraphNodeType::Outlet => PortDirection::Out,
+                    _ => return None,
+                };
+                let port = self.port_identity(index)?;
+                Some(PortChange { direction, port })
+            })
+            .collect()
+    }
+
+    /// Every Outlet -> StreamDef -> Inlet path, by the ports at either end.
+    fn connections(&self) -> BTreeSet<ConnectionChange> {
+        let mut connections = BTreeSet::new();
+        for stream in self.graph.node_indices() {
+            if self.graph[stream].node_type != DefGraphNodeType::StreamDef {
+                continue;
+            }
+            let outlets = self.neighbor_indices_of_types(
+                stream,
+                Direction::Incoming,
+                &[DefGraphNodeType::Outlet],
+            );
+            let inlets = self.neighbor_indices_of_types(
+                stream,
+                Direction::Outgoing,
+                &[DefGraphNodeType::Inlet],
+            );
+            for &outlet in &outlets {
+                for &inlet in &inlets {
+                    if let (Some(from), Some(to)) =
+                        (self.port_identity(outlet), self.port_identity(inlet))
+                    {
+                        connections.insert(ConnectionChange { from, to });
+                    }
+                }
+            }
+        }
+        connections
+    }
+
+    fn inlet_transforms(&self) -> BTreeMap<PortIdentity, bool> {
+        self.graph
+            .node_indices()
+            .filter(|&index| self.graph[index].node_type == DefGraphNodeType::Inlet)
+            .filter_map(|index| {
+                let port = self.port_identity(index)?;
+                Some((port, self.graph[index].has_transform.unwrap_or(false)))
+            })
+            .collect()
+    }
+
+    pub(crate) fn alias_targets(&self) -> BTreeMap<(String, PortDirection), PortIdentity> {
+        self.graph
+            .node_indices()
+            .filter(|&index| self.graph[index].node_type == DefGraphNodeType::Alias)
+            .filter_map(|index| {
+                let node = &self.graph[index];
+                let direction = node.direction?;
+                let port_type = direction.port_node_type();
+                let port = self
+                    .graph
+                    .neighbors_undirected(index)
+                    .find(|&n| self.graph[n].node_type == port_type)?;
+                Some(((node.alias.clone()?, direction), self.port_identity(port)?))
+            })
+            .collect()
+    }
+
+    /// Spec identifier and tag of the spec an Inlet or Outlet belongs to.
+    pub(crate) fn port_identity(&self, port: NodeIndex) -> Option<PortIdentity> {
+        let direction = match self.graph[port].node_type {
+            DefGraphNodeType::Inlet => Direction::Outgoing,
+            DefGraphNodeType::Outlet => Direction::Incoming,
+            _ => return None,
+        };
+        let spec = self
+            .neighbor_indices_of_types(
+                port,
+                direction,
+                &[DefGraphNodeType::Spec, DefGraphNodeType::RootSpec],
+            )
+            .into_iter()
+            .next()?;
+        Some(PortIdentity {
+            spec_identifier: spec_identifier_of(&self.graph[spec]),
+            tag: self.graph[port].tag.clone().unwrap_or_default(),
+        })
+    }
+}
+
+pub(crate) fn spec_identifier_of(node: &DefGraphNode) -> String {
+    unique_spec_identifier(
+        node.spec_name.clone().unwrap_or_default(),
+        node.unique_spec_label.clone(),
+    )
+}
@@ -0,0 +1,168 @@
+//! A stable content hash of a [`DefGraph`], for cache keys and "has this workflow changed"
+//! checks across processes and languages.
+//!
+//! The graph is first reduced to a canonical description built only from identities: spec
+//! identifiers, ports as spec identifier and tag, and alias names. A stream is identified by
+//! the ports it connects. Node ids, node labels, stream def ids and `HashMap` iteration order
+//! never reach it. Every list in the description is sorted, it is serialized as JSON and the
+//! SHA-256 of that is returned as lowercase hex.
+//!
+//! Stream def ids are left out because they depend on which connection was made to an outlet
+//! first and on the graph's [`IdScheme`](crate::systems::id_scheme::IdScheme), neither of
+//! which changes what the workflow does.
+
+use crate::systems::def_graph::{DefGraph, DefGraphNodeType, EdgeMeta, MergePolicy, PortDirection};
+use crate::systems::def_graph_diff::{spec_identifier_of, PortIdentity};
+use petgraph::visit::EdgeRef;
+use petgraph::Direction;
+use serde::Serialize;
+use serde_json::Value;
+use sha2::{Digest, Sha256};
+use std::collections::BTreeMap;
+
+/// Bumped whenever the canonical description changes shape, so old and new fingerprints of
+/// the same graph never collide by accident.
+const FINGERPRINT_VERSION: &str = "livestack-def-graph-fingerprint/3";
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct CanonicalGraph {
+    version: &'static str,
+    root_spec_name: Option<String>,
+    /// Spec identifier -> fingerprint of the embedded graph, for composite specs.
+    specs: BTreeMap<String, Option<String>>,
+    ports: Vec<CanonicalPort>,
+    streams: Vec<CanonicalStream>,
+    aliases: Vec<CanonicalAlias>,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct CanonicalPort {
+    direction: PortDirection,
+    port: PortIdentity,
+    has_transform: bool,
+    merge_policy: Option<MergePolicy>,
+    /// Parsed so that formatting and key order inside the schema do not matter.
+    json_schema: Option<Value>,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct CanonicalStream {
+    source: Option<PortIdentity>,
+    targets: Vec<(PortIdentity, EdgeMeta)>,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct CanonicalAlias {
+    alias: String,
+    direction: PortDirection,
+    target: PortIdentity,
+}
+
+impl DefGraph {
+    /// SHA-256, as lowercase hex, of the graph's semantic content: specs and their labels,
+    /// ports, streams with their connection metadata, aliases and transforms. Two graphs
+    /// describing the same workflow have the same fingerprint however they were built or
+    /// loaded.
+    pub fn fingerprint(&self) -> String {
+        let canonical = serde_json::to_vec(&self.canonical())
+            .expect("canonical description only holds serializable values");
+        Sha256::digest(&canonical)
+            .iter()
+            .map(|byte| format!("{:02x}", byte))
+            .collect()
+    }
+
+    fn canonical(&self) -> CanonicalGraph {
+        let mut root_spec_name = None;
+        let mut specs = BTreeMap::new();
+        let mut ports = Vec::new();
+        let mut streams = Vec::new();
+
+        for index in self.graph.node_indices() {
+            let node = &self.graph[index];
+            match node.node_type {
+                DefGraphNodeType::RootSpec => root_spec_name = node.spec_name.clone(),
+                DefGraphNodeType::Spec => {
+                    let spec_id = spec_identifier_of(node);
+                    let composite = self.composites.get(&spec_id).map(DefGraph::fingerprint);
+                    specs.insert(spec_id, composite);
+                }
+                DefGraphNodeType::Inlet | DefGraphNodeType::Outlet => {
+                    let Some(port) = self.port_identity(index) else {
+                        continue;
+                    };
+                    ports.push(CanonicalPort {
+                        direction: if node.node_type == DefGraphNodeType::Inlet {
+                            PortDirection::In
+                        } else {
+                            PortDirection::Out
+                        },
+                        port,
+                        has_transform: node.has_transform.unwrap_or(false),
+                        merge_policy: node.merge_policy.clone(),
+                        json_schema: node.json_schema_str.as_deref().map(|schema| {
+                            serde_json::from_str(schema)
+                                .unwrap_or_else(|_| Value::String(schema.to_string()))
+                        }),
+                    });
+                }
+                DefGraphNodeType::StreamDef => {
+                    let source = self
+                        .neighbor_indices_of_types(
+                            index,
+                            Direction::Incoming,
+                            &[DefGraphNodeType::Outlet],
+                        )
+                        .into_iter()
+                        .find_map(|outlet| self.port_identity(outlet));
+                    let mut targets: Vec<(PortIdentity, EdgeMeta)> = self
+                        .graph
+                        .edges_directed(index, Direction::Outgoing)
+                        .filter(|edge| {
+                            self.graph[edge.target()].node_type == DefGraphNodeType::Inlet
+                        })
+                        .filter_map(|edge| {
+                            Some((self.port_identity(edge.target())?, edge.weight().clone()))
+                        })
+                        .collect();
+                    targets.sort_by(|a, b| a.0.cmp(&b.0));
+                    streams.push(CanonicalStream { source, targets });
+                }
+                DefGraphNodeType::Alias => {}
+            }
+        }
+        ports.sort_by(|a, b| (a.direction, &a.port).cmp(&(b.direction, &b.port)));
+        streams.sort_by(|a, b| {
+            let endpoints = |stream: &CanonicalStream| {
+                let targets: Vec<_> = stream
+                    .targets
+                    .iter()
+                    .map(|(port, _)| port.clone())
+                    .collect();
+                (stream.source.clone(), targets)
+            };
+            endpoints(a).cmp(&endpoints(b))
+        });
+
+        CanonicalGraph {
+            version: FINGERPRINT_VERSION,
+            root_spec_name,
+            specs,
+            ports,
+            streams,
+            aliases: self
+                .alias_targets()
+                .into_iter()
+                .map(|((alias, direction), target)| CanonicalAlias {
+                    alias,
+                    direction,
+                    target,
+                })
+                .collect(),
+        }
+    }
+}
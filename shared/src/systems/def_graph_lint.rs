@@ -0,0 +1,262 @@
+//! Advisory checks for shapes that are legal but usually a mistake, mostly parts of a
+//! workflow that nothing feeds or that feed nothing.
+//!
+//! [`DefGraph::validate`] reports broken invariants the rest of the code cannot work with;
+//! [`DefGraph::lint`] reports graphs that work but probably do not do what was meant. Each
+//! lint has a stable [`LintCode`] and a fixed [`LintSeverity`], so tools can show, filter
+//! or silence them by code.
+
+use crate::systems::def_graph::{DefGraph, DefGraphNodeType, PortDirection};
+use crate::systems::def_graph_diff::spec_identifier_of;
+use petgraph::graph::NodeIndex;
+use petgraph::Direction;
+use serde::{Deserialize, Serialize};
+
+use std::collections::{BTreeSet, HashMap, VecDeque};
+use std::fmt;
+
+/// How much a lint matters. Neither stops the graph from being instantiated.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum LintSeverity {
+    /// Often intended, e.g. a sink kept for its side effects.
+    Info,
+    /// Almost always a disconnection someone forgot about.
+    Warning,
+}
+
+/// Identifies a kind of lint. The kebab-case names are stable and safe to match on.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum LintCode {
+    /// A spec outlet that feeds no inlet and is not exposed by an output alias.
+    UnconnectedOutlet,
+    /// A root input tag that no input alias of the same name binds to a spec port.
+    UnusedRootInput,
+    /// A spec that no root input alias reaches, directly or through other specs.
+    UnreachableSpec,
+    /// A spec none of whose outputs reach a root output alias.
+    DeadEndSpec,
+    /// An input alias on an inlet that another spec already feeds.
+    AliasOnInternalStream,
+    /// An inlet that expects a transform but has nothing upstream to transform.
+    TransformWithoutUpstream,
+}
+
+impl LintCode {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            LintCode::UnconnectedOutlet => "unconnected-outlet",
+            LintCode::UnusedRootInput => "unused-root-input",
+            LintCode::UnreachableSpec => "unreachable-spec",
+            LintCode::DeadEndSpec => "dead-end-spec",
+            LintCode::AliasOnInternalStream => "alias-on-internal-stream",
+            LintCode::TransformWithoutUpstream => "transform-without-upstream",
+        }
+    }
+
+    pub fn severity(self) -> LintSeverity {
+        match self {
+            LintCode::DeadEndSpec | LintCode::TransformWithoutUpstream => LintSeverity::Info,
+            _ => LintSeverity::Warning,
+        }
+    }
+}
+
+impl fmt::Display for LintCode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+/// One finding of [`DefGraph::lint`], attached to the node a UI should highlight.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DefGraphLint {
+    pub code: LintCode,
+    pub severity: LintSeverity,
+    pub node_id: u32,
+    pub message: String,
+}
+
+impl DefGraph {
+    /// Every lint that applies to the graph, ordered by node id and then code.
+    ///
+    /// Reachability lints only run once the graph declares a root alias in the direction
+    /// they check: a graph without input aliases has no inputs to be unreachable from.
+    pub fn lint(&self) -> Vec<DefGraphLint> {
+        let mut lints = Vec::new();
+        let mut push = |code: LintCode, index: NodeIndex, message: String| {
+            lints.push(DefGraphLint {
+                code,
+                severity: code.severity(),
+                node_id: index.index() as u32,
+                message,
+            })
+        };
+
+        for index in self.graph.node_indices() {
+            let node = &self.graph[index];
+            match node.node_type {
+                DefGraphNodeType::Outlet
+                    if self.is_spec_port(index)
+                        && self.connected_inlets(index).is_empty()
+                        && !self.has_alias(index) =>
+                {
+                    push(
+                        LintCode::UnconnectedOutlet,
+                        index,
+                        format!("Outlet {} feeds nothing", self.port_name(index)),
+                    );
+                }
+                DefGraphNodeType::Inlet
+                    if self.is_spec_port(index)
+                        && node.has_transform == Some(true)
+                        && self.connected_outlets(index).is_empty()
+                        && !self.has_alias(index) =>
+                {
+                    push(
+                        LintCode::TransformWithoutUpstream,
+                        index,
+                        format!(
+                            "Inlet {} expects a transform but nothing feeds it",
+                            self.port_name(index)
+                        ),
+                    );
+                }
+                DefGraphNodeType::Alias if node.direction == Some(PortDirection::In) => {
+                    let feeders: Vec<NodeIndex> = self
+                        .neighbor_indices_of_types(
+                            index,
+                            Direction::Incoming,
+                            &[DefGraphNodeType::Inlet],
+                        )
+                        .into_iter()
+                        .filter(|&inlet| !self.connected_outlets(inlet).is_empty())
+                        .collect();
+                    if let Some(&inlet) = feeders.first() {
+                        push(
+                            LintCode::AliasOnInternalStream,
+                            index,
+                            format!(
+                                "Input alias {} points at {}, which another spec already feeds",
+                                node.alias.as_deref().unwrap_or_default(),
+                                self.port_name(inlet)
+                            ),
+                        );
+                    }
+                }
+                _ => {}
+            }
+        }
+
+        let aliases = self.aliases();
+        let aliased_specs = |direction: PortDirection| -> BTreeSet<u32> {
+            aliases
+                .iter()
+                .filter(|alias| alias.direction == direction)
+                .filter_map(|alias| {
+                    self.find_spec_node(&alias.spec_name, alias.unique_spec_label.as_deref())
+                })
+                .collect()
+        };
+
+        if let Some(root) = self.indexes.of_type(&DefGraphNodeType::RootSpec).next() {
+            for inlet in self.neighbor_indices_of_types(
+                root,
+                Direction::Incoming,
+                &[DefGraphNodeType::Inlet],
+            ) {
+                let tag = self.graph[inlet].tag.clone().unwrap_or_default();
+                if !aliases
+                    .iter()
+                    .any(|alias| alias.direction == PortDirection::In && alias.alias == tag)
+                {
+                    push(
+                        LintCode::UnusedRootInput,
+                        inlet,
+                        format!("Root input {} is not aliased to any spec input", tag),
+                    );
+                }
+            }
+        }
+
+        let connections = self.get_spec_connections();
+        let mut downstream: HashMap<u32, Vec<u32>> = HashMap::new();
+        let mut upstream: HashMap<u32, Vec<u32>> = HashMap::new();
+        for connection in &connections {
+            downstream
+                .entry(connection.from_spec_node_id)
+                .or_default()
+                .push(connection.to_spec_node_id);
+            upstream
+                .entry(connection.to_spec_node_id)
+                .or_default()
+                .push(connection.from_spec_node_id);
+        }
+        let checks = [
+            (
+                PortDirection::In,
+                &downstream,
+                LintCode::UnreachableSpec,
+                "is not reached by any root input",
+            ),
+            (
+                PortDirection::Out,
+                &upstream,
+                LintCode::DeadEndSpec,
+                "does not reach any root output",
+            ),
+        ];
+        for (direction, edges, code, problem) in checks {
+            let starts = aliased_specs(direction);
+            if starts.is_empty() {
+                continue;
+            }
+            let reached = walk(starts, edges);
+            for spec in self.indexes.of_type(&DefGraphNodeType::Spec) {
+                if !reached.contains(&(spec.index() as u32)) {
+                    push(
+                        code,
+                        spec,
+                        format!("Spec {} {}", spec_identifier_of(&self.graph[spec]), problem),
+                    );
+                }
+            }
+        }
+
+        lints.sort_by_key(|lint| (lint.node_id, lint.code));
+        lints
+    }
+
+    /// Whether an Inlet or Outlet belongs to a non-root spec.
+    fn is_spec_port(&self, port: NodeIndex) -> bool {
+        let direction = match self.graph[port].node_type {
+            DefGraphNodeType::Inlet => Direction::Outgoing,
+            _ => Direction::Incoming,
+        };
+        !self
+            .neighbor_indices_of_types(port, direction, &[DefGraphNodeType::Spec])
+            .is_empty()
+    }
+
+    fn port_name(&self, port: NodeIndex) -> String {
+        self.port_identity(port)
+            .map(|port| format!("{}/{}", port.spec_identifier, port.tag))
+            .unwrap_or_else(|| self.graph[port].label.clone())
+    }
+}
+
+/// Every spec reachable from `starts` along `edges`, the starts included.
+fn walk(starts: BTreeSet<u32>, edges: &HashMap<u32, Vec<u32>>) -> BTreeSet<u32> {
+    let mut queue: VecDeque<u32> = starts.iter().copied().collect();
+    let mut reached = starts;
+    while let Some(spec) = queue.pop_front() {
+        for &next in edges.get(&spec).into_iter().flatten() {
+            if reached.insert(next) {
+                queue.push_back(next);
+            }
+        }
+    }
+    reached
+}
@@ -0,0 +1,238 @@
+//! Transitive data-flow queries: everything upstream or downstream of a spec or stream,
+//! and every path between two specs.
+//!
+//! Walks follow `Spec -> Outlet -> StreamDef -> Inlet -> Spec` hops, feedback connections
+//! included. The RootSpec ends a walk: it is where the workflow's inputs come from and its
+//! outputs go, not a step between specs.
+
+use crate::systems::def_graph::{DefGraph, DefGraphError, DefGraphNodeType, PortDirection};
+use crate::systems::def_graph_diff::spec_identifier_of;
+use crate::systems::def_graph_topology::SpecConnection;
+use petgraph::graph::NodeIndex;
+use petgraph::Direction;
+use serde::{Deserialize, Serialize};
+
+use std::collections::{BTreeSet, HashMap, VecDeque};
+
+/// A spec reached by a walk.
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ReachedSpec {
+    pub spec_node_id: u32,
+    pub spec_identifier: String,
+}
+
+/// A stream def reached by a walk.
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ReachedStream {
+    pub stream_node_id: u32,
+    pub stream_def_id: String,
+}
+
+/// A root alias on a port of a reached spec, facing the walk's direction: output aliases
+/// for a downstream walk, input aliases for an upstream one.
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ReachedAlias {
+    pub alias: String,
+    pub direction: PortDirection,
+    pub spec_node_id: u32,
+    pub tag: String,
+}
+
+/// Everything a walk from one spec or stream reaches, each list in ascending node id order.
+/// The starting node itself only appears when a loop leads back to it.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Reachable {
+    pub specs: Vec<ReachedSpec>,
+    pub streams: Vec<ReachedStream>,
+    pub aliases: Vec<ReachedAlias>,
+}
+
+/// One route between two specs, as the connections taken in order.
+pub type SpecPath = Vec<SpecConnection>;
+
+impl DefGraph {
+    /// Specs and streams fed, directly or not, by the Spec or StreamDef `node_id`, plus the
+    /// root output aliases they reach.
+    pub fn downstream_of(&self, node_id: u32) -> Result<Reachable, DefGraphError> {
+        self.reachable_from(node_id, Direction::Outgoing)
+    }
+
+    /// Specs and streams feeding, directly or not, the Spec or StreamDef `node_id`, plus the
+    /// root input aliases they depend on.
+    pub fn upstream_of(&self, node_id: u32) -> Result<Reachable, DefGraphError> {
+        self.reachable_from(node_id, Direction::Incoming)
+    }
+
+    /// Every route from one Spec to another that visits no spec twice, shortest first and
+    /// then by the node ids along the way. Empty when `to` is not downstream of `from`.
+    pub fn spec_paths(&self, from: u32, to: u32) -> Result<Vec<SpecPath>, DefGraphError> {
+        for id in [from, to] {
+            self.expect_node_type(id, &[DefGraphNodeType::Spec], "spec")?;
+        }
+        let mut outgoing: HashMap<u32, Vec<SpecConnection>> = HashMap::new();
+        for connection in self.get_spec_connections() {
+            outgoing
+                .entry(connection.from_spec_node_id)
+                .or_default()
+                .push(connection);
+        }
+
+        let mut paths = Vec::new();
+        extend_paths(
+            &outgoing,
+            from,
+            to,
+            &mut Vec::new(),
+            &mut BTreeSet::from([from]),
+            &mut paths,
+        );
+        paths.sort_by(|a: &SpecPath, b: &SpecPath| {
+            let key = |p: &SpecPath| {
+                p.iter()
+                    .map(|c| (c.to_spec_node_id, c.inlet_node_id))
+                    .collect::<Vec<_>>()
+            };
+            a.len().cmp(&b.len()).then_with(|| key(a).cmp(&key(b)))
+        });
+        Ok(paths)
+    }
+
+    fn reachable_from(
+        &self,
+        node_id: u32,
+        direction: Direction,
+    ) -> Result<Reachable, DefGraphError> {
+        let start = self.expect_node_type(
+            node_id,
+            &[DefGraphNodeType::Spec, DefGraphNodeType::StreamDef],
+            "spec or stream def",
+        )?;
+        // The port types passed on the way out of a spec and into the next one.
+        let (leaving, entering) = match direction {
+            Direction::Outgoing => ([DefGraphNodeType::Outlet], [DefGraphNodeType::Inlet]),
+            Direction::Incoming => ([DefGraphNodeType::Inlet], [DefGraphNodeType::Outlet]),
+        };
+
+        let mut specs = BTreeSet::new();
+        let mut streams = BTreeSet::new();
+        let mut aliases = BTreeSet::new();
+        let mut queue = VecDeque::from([start]);
+        let mut first = true;
+        while let Some(index) = queue.pop_front() {
+            let node = &self.graph[index];
+            let is_spec = node.node_type == DefGraphNodeType::Spec;
+            if !first {
+                let newly_reached = if is_spec {
+                    specs.insert(ReachedSpec {
+                        spec_node_id: index.index() as u32,
+                        spec_identifier: spec_identifier_of(node),
+                    })
+                } else {
+                    streams.insert(ReachedStream {
+                        stream_node_id: index.index() as u32,
+                        stream_def_id: node
+                            .stream_def_id
+                            .clone()
+                            .unwrap_or_else(|| node.label.clone()),
+                    })
+                };
+                if !newly_reached {
+                    continue;
+                }
+            }
+            first = false;
+
+            if is_spec {
+                for port in self.neighbor_indices_of_types(index, direction, &leaving) {
+                    aliases.extend(self.reached_port_aliases(index, port, direction));
+                    queue.extend(self.neighbor_indices_of_types(
+                        port,
+                        direction,
+                        &[DefGraphNodeType::StreamDef],
+                    ));
+                }
+            } else {
+                for port in self.neighbor_indices_of_types(index, direction, &entering) {
+                    queue.extend(self.neighbor_indices_of_types(
+                        port,
+                        direction,
+                        &[DefGraphNodeType::Spec],
+                    ));
+                }
+            }
+        }
+
+        Ok(Reachable {
+            specs: specs.into_iter().collect(),
+            streams: streams.into_iter().collect(),
+            aliases: aliases.into_iter().collect(),
+        })
+    }
+
+    /// Root aliases bound to `port` of `spec`. An output alias points at its outlet and an
+    /// input alias is pointed at by its inlet, so either way it sits on the same side of the
+    /// port as the spec.
+    fn reached_port_aliases(
+        &self,
+        spec: NodeIndex,
+        port: NodeIndex,
+        direction: Direction,
+    ) -> Vec<ReachedAlias> {
+        self.neighbor_indices_of_types(port, direction.opposite(), &[DefGraphNodeType::Alias])
+            .into_iter()
+            .filter_map(|alias| {
+                let node = &self.graph[alias];
+                Some(ReachedAlias {
+                    alias: node.alias.clone()?,
+                    direction: node.direction?,
+                    spec_node_id: spec.index() as u32,
+                    tag: self.graph[port].tag.clone().unwrap_or_default(),
+                })
+            })
+            .collect()
+    }
+
+    fn expect_node_type(
+        &self,
+        node_id: u32,
+        node_types: &[DefGraphNodeType],
+        expected: &str,
+    ) -> Result<NodeIndex, DefGraphError> {
+        let index = NodeIndex::new(node_id as usize);
+        match self.graph.node_weight(index) {
+            Some(node) if node_types.contains(&node.node_type) => Ok(index),
+            Some(_) => Err(DefGraphError::UnexpectedNodeType {
+                node_id,
+                expected: expected.to_string(),
+            }),
+            None => Err(DefGraphError::NodeNotFound(node_id)),
+        }
+    }
+}
+
+/// Depth-first search extending `path`, whose specs are `on_path`, towards `to`.
+fn extend_paths(
+    outgoing: &HashMap<u32, Vec<SpecConnection>>,
+    from: u32,
+    to: u32,
+    path: &mut SpecPath,
+    on_path: &mut BTreeSet<u32>,
+    paths: &mut Vec<SpecPath>,
+) {
+    let at = path.last().map_or(from, |hop| hop.to_spec_node_id);
+    for connection in outgoing.get(&at).into_iter().flatten() {
+        let target = connection.to_spec_node_id;
+        path.push(connection.clone());
+        if target == to {
+            paths.push(path.clone());
+        } else if on_path.insert(target) {
+            extend_paths(outgoing, from, to, path, on_path, paths);
+            on_path.remove(&target);
+        }
+        path.pop();
+    }
+}
@@ -0,0 +1,265 @@
+use crate::systems::def_graph::{
+    stream_def_key, DefGraph, DefGraphError, DefGraphNode, DefGraphNodeType, EdgeMeta,
+    PortDirection,
+};
+use crate::systems::def_graph_utils::{unique_spec_identifier, SpecTagInfo};
+use petgraph::graph::NodeIndex;
+use petgraph::Direction;
+
+use std::collections::BTreeSet;
+
+impl DefGraph {
+    /// Removes a spec together with its inlets, outlets and any aliases exposing them.
+    /// Stream defs left with no connections at all are removed too; streams still used by
+    /// another spec are kept. Returns the removed node ids in ascending order.
+    ///
+    /// Node ids of the remaining nodes do not change. Freed ids may be handed out again
+    /// to nodes added later.
+    pub fn remove_spec(
+        &mut self,
+        spec_name: &str,
+        unique_spec_label: Option<&str>,
+    ) -> Result<Vec<u32>, DefGraphError> {
+        let spec = self.spec_index(spec_name, unique_spec_label)?;
+
+        let mut doomed: BTreeSet<NodeIndex> = BTreeSet::new();
+        let mut streams: BTreeSet<NodeIndex> = BTreeSet::new();
+        doomed.insert(spec);
+        let inlets =
+            self.neighbor_indices_of_types(spec, Direction::Incoming, &[DefGraphNodeType::Inlet]);
+        let outlets =
+            self.neighbor_indices_of_types(spec, Direction::Outgoing, &[DefGraphNodeType::Outlet]);
+        for inlet in inlets {
+            doomed.insert(inlet);
+            doomed.extend(self.neighbor_indices_of_types(
+                inlet,
+                Direction::Outgoing,
+                &[DefGraphNodeType::Alias],
+            ));
+            streams.extend(self.neighbor_indices_of_types(
+                inlet,
+                Direction::Incoming,
+                &[DefGraphNodeType::StreamDef],
+            ));
+        }
+        for outlet in outlets {
+            doomed.insert(outlet);
+            doomed.extend(self.neighbor_indices_of_types(
+                outlet,
+                Direction::Incoming,
+                &[DefGraphNodeType::Alias],
+            ));
+            streams.extend(self.neighbor_indices_of_types(
+                outlet,
+                Direction::Outgoing,
+                &[DefGraphNodeType::StreamDef],
+            ));
+        }
+
+        for &index in &doomed {
+            self.graph.remove_node(index);
+        }
+        for stream in streams {
+            if self.graph.neighbors_undirected(stream).next().is_none() {
+                self.graph.remove_node(stream);
+                doomed.insert(stream);
+            }
+        }
+
+        self.forget_removed_nodes();
+        let spec_id =
+            unique_spec_identifier(spec_name.to_string(), unique_spec_label.map(|l| l.to_string()));
+        let in_prefix = stream_def_key(&spec_id, PortDirection::In, "");
+        let out_prefix = stream_def_key(&spec_id, PortDirection::Out, "");
+        self.stream_node_id_by_spec_identifier_type_and_tag
+            .retain(|key, _| !key.starts_with(&in_prefix) && !key.starts_with(&out_prefix));
+        self.composites.remove(&spec_id);
+
+        Ok(doomed
+            .into_iter()
+            .map(|index| index.index() as u32)
+            .collect())
+    }
+
+    /// Cuts the stream from `from`'s outlet into `to`'s inlet. Ports and streams that are
+    /// left serving nothing are removed, unless an alias still exposes them; the specs
+    /// themselves are kept, and an aliased inlet left unfed gets an unconnected stream of
+    /// its own. Returns the removed node ids in ascending order.
+    pub fn disconnect(
+        &mut self,
+        from: &SpecTagInfo,
+        to: &SpecTagInfo,
+    ) -> Result<Vec<u32>, DefGraphError> {
+        let (outlet, stream, inlet) = self.connection_nodes(from, to)?;
+        let stream_def_id = self.graph[stream].stream_def_id.clone();
+
+        let edge = self
+            .graph
+            .find_edge(stream, inlet)
+            .expect("edge checked above");
+        self.graph.remove_edge(edge);
+
+        let mut removed = Vec::new();
+        let inlet_unfed = self
+            .neighbor_indices_of_types(inlet, Direction::Incoming, &[DefGraphNodeType::StreamDef])
+            .is_empty();
+        if inlet_unfed && self.has_alias(inlet) {
+            self.attach_unconnected_stream(inlet, to);
+        } else if inlet_unfed {
+            self.graph.remove_node(inlet);
+            removed.push(inlet);
+        }
+        if !self.has_alias(outlet)
+            && self
+                .graph
+                .neighbors_directed(stream, Direction::Outgoing)
+                .next()
+                .is_none()
+        {
+            self.graph.remove_node(stream);
+            removed.push(stream);
+            if self
+                .graph
+                .neighbors_directed(outlet, Direction::Outgoing)
+                .next()
+                .is_none()
+            {
+                self.graph.remove_node(outlet);
+                removed.push(outlet);
+            }
+        }
+
+        let in_key = stream_def_key(
+            &unique_spec_identifier(to.spec_name.clone(), to.unique_spec_label.clone()),
+            PortDirection::In,
+            &to.tag,
+        );
+        if self
+            .stream_node_id_by_spec_identifier_type_and_tag
+            .get(&in_key)
+            == stream_def_id.as_ref()
+        {
+            self.stream_node_id_by_spec_identifier_type_and_tag
+                .remove(&in_key);
+        }
+        self.forget_removed_nodes();
+
+        removed.sort();
+        Ok(removed
+            .into_iter()
+            .map(|index| index.index() as u32)
+            .collect())
+    }
+
+    /// Detaches and removes the root-spec alias with the given name and direction.
+    /// Returns the removed alias node id.
+    pub fn remove_alias(
+        &mut self,
+        alias: &str,
+        direction: PortDirection,
+    ) -> Result<u32, DefGraphError> {
+        let alias_index = self
+            .indexes
+            .aliases_named(alias)
+            .find(|&index| self.graph[index].direction == Some(direction))
+            .ok_or_else(|| DefGraphError::AliasNotFound {
+                alias: alias.to_string(),
+                direction,
+            })?;
+        self.graph.remove_node(alias_index);
+        self.forget_removed_nodes();
+        Ok(alias_index.index() as u32)
+    }
+
+    fn spec_index(
+        &self,
+        spec_name: &str,
+        unique_spec_label: Option<&str>,
+    ) -> Result<NodeIndex, DefGraphError> {
+        self.find_spec_node(spec_name, unique_spec_label)
+            .map(|id| NodeIndex::new(id as usize))
+            .ok_or_else(|| DefGraphError::SpecNotFound {
+                spec_name: spec_name.to_string(),
+                unique_spec_label: unique_spec_label.map(|l| l.to_string()),
+            })
+    }
+
+    /// The Inlet (`Incoming`) or Outlet (`Outgoing`) of `spec` carrying `tag`.
+    /// Outlet, StreamDef and Inlet of a direct connection between two ports.
+    pub(crate) fn connection_nodes(
+        &self,
+        from: &SpecTagInfo,
+        to: &SpecTagInfo,
+    ) -> Result<(NodeIndex, NodeIndex, NodeIndex), DefGraphError> {
+        let from_spec = self.spec_index(&from.spec_name, from.unique_spec_label.as_deref())?;
+        let to_spec = self.spec_index(&to.spec_name, to.unique_spec_label.as_deref())?;
+        let outlet = self
+            .port_index(from_spec, Direction::Outgoing, &from.tag)
+            .ok_or_else(|| DefGraphError::PortNotFound {
+                spec_name: from.spec_name.clone(),
+                tag: from.tag.clone(),
+                direction: PortDirection::Out,
+            })?;
+        let inlet = self
+            .port_index(to_spec, Direction::Incoming, &to.tag)
+            .ok_or_else(|| DefGraphError::PortNotFound {
+                spec_name: to.spec_name.clone(),
+                tag: to.tag.clone(),
+                direction: PortDirection::In,
+            })?;
+        let stream = self
+            .neighbor_indices_of_types(outlet, Direction::Outgoing, &[DefGraphNodeType::StreamDef])
+            .into_iter()
+            .find(|&stream| self.graph.contains_edge(stream, inlet))
+            .ok_or_else(|| DefGraphError::NotConnected {
+                from: self.graph[outlet].label.clone(),
+                to: self.graph[inlet].label.clone(),
+            })?;
+        Ok((outlet, stream, inlet))
+    }
+
+    fn port_index(&self, spec: NodeIndex, direction: Direction, tag: &str) -> Option<NodeIndex> {
+        let direction = match direction {
+            Direction::Incoming => PortDirection::In,
+            Direction::Outgoing => PortDirection::Out,
+        };
+        self.indexes.port(spec, direction, tag)
+    }
+
+    /// Feeds `inlet` from a stream of its own, as [`DefGraph::ensure_inlet_and_stream`]
+    /// leaves an inlet that nothing is connected to yet.
+    fn attach_unconnected_stream(&mut self, inlet: NodeIndex, port: &SpecTagInfo) {
+        let stream_def_id = self.id_scheme.stream_def_id(None, Some(port));
+        let stream = self.ensure_node(
+            &stream_def_id,
+            DefGraphNode {
+                node_type: DefGraphNodeType::StreamDef,
+                stream_def_id: Some(stream_def_id.clone()),
+                label: stream_def_id.clone(),
+                ..Default::default()
+            },
+        );
+        self.graph
+            .add_edge(NodeIndex::new(stream as usize), inlet, EdgeMeta::default());
+    }
+
+    pub(crate) fn has_alias(&self, port: NodeIndex) -> bool {
+        self.graph
+            .neighbors_undirected(port)
+            .any(|neighbor| self.graph[neighbor].node_type == DefGraphNodeType::Alias)
+    }
+
+    /// Drops lookup entries that point at nodes or stream defs no longer in the graph.
+    fn forget_removed_nodes(&mut self) {
+        let graph = &self.graph;
+        self.node_indices
+            .retain(|_, index| graph.node_weight(*index).is_some());
+        self.indexes.retain_live(graph);
+        let live_stream_def_ids: BTreeSet<&String> = graph
+            .node_indices()
+            .filter_map(|index| graph[index].stream_def_id.as_ref())
+            .collect();
+        self.stream_node_id_by_spec_identifier_type_and_tag
+            .retain(|_, stream_def_id| live_stream_def_ids.contains(stream_def_id));
+    }
+}
@@ -0,0 +1,241 @@
+use crate::systems::def_graph::{DefGraph, DefGraphError, DefGraphNodeType};
+use crate::systems::def_graph_utils::{unique_spec_identifier, SpecTagInfo};
+use petgraph::algo::tarjan_scc;
+use petgraph::graph::{DiGraph, NodeIndex};
+use petgraph::Direction;
+use serde::{Deserialize, Serialize};
+
+use std::cmp::Reverse;
+use std::collections::{BTreeSet, BinaryHeap, HashMap};
+
+/// A spec-to-spec hop through `Outlet -> StreamDef -> Inlet`.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SpecConnection {
+    pub from_spec_node_id: u32,
+    pub to_spec_node_id: u32,
+    pub stream_node_id: u32,
+    pub stream_def_id: String,
+    pub inlet_node_id: u32,
+    /// Whether this connection was marked as an intended feedback connection.
+    pub is_feedback: bool,
+}
+
+/// A strongly connected group of specs that loops back on itself.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SpecCycle {
+    /// Spec node ids on the loop, in ascending id order.
+    pub spec_node_ids: Vec<u32>,
+    /// `unique_spec_identifier` of each spec, matching `spec_node_ids`.
+    pub spec_identifiers: Vec<String>,
+    /// Stream defs that connect the specs on the loop, sorted.
+    pub stream_def_ids: Vec<String>,
+}
+
+impl DefGraph {
+    /// Marks the connection from the outlet `from` to the inlet `to` as an intended feedback
+    /// edge, or unmarks it. Feedback connections are ignored by
+    /// [`DefGraph::topological_spec_order`] and [`DefGraph::find_spec_cycles`]; other
+    /// producers into the same inlet are not affected.
+    pub fn try_mark_feedback_connection(
+        &mut self,
+        from: &SpecTagInfo,
+        to: &SpecTagInfo,
+        is_feedback: bool,
+    ) -> Result<(), DefGraphError> {
+        let (_, stream, inlet) = self.connection_nodes(from, to)?;
+        let edge = self
+            .graph
+            .find_edge(stream, inlet)
+            .expect("connection_nodes checks the edge");
+        self.graph[edge].is_feedback = is_feedback.then_some(true);
+        Ok(())
+    }
+
+    /// Every spec-to-spec data-flow hop in the graph. RootSpec inputs and outputs are not
+    /// included since the root is both the source and the sink of the workflow.
+    pub fn get_spec_connections(&self) -> Vec<SpecConnection> {
+        let mut connections = Vec::new();
+        for from_index in self.indexes.of_type(&DefGraphNodeType::Spec) {
+            for outlet in self.neighbor_indices_of_types(
+                from_index,
+                Direction::Outgoing,
+                &[DefGraphNodeType::Outlet],
+            ) {
+                for stream in self.neighbor_indices_of_types(
+                    outlet,
+                    Direction::Outgoing,
+                    &[DefGraphNodeType::StreamDef],
+                ) {
+                    for inlet in self.neighbor_indices_of_types(
+                        stream,
+                        Direction::Outgoing,
+                        &[DefGraphNodeType::Inlet],
+                    ) {
+                        for to_index in self.neighbor_indices_of_types(
+                            inlet,
+                            Direction::Outgoing,
+                            &[DefGraphNodeType::Spec],
+                        ) {
+                            connections.push(SpecConnection {
+                                from_spec_node_id: from_index.index() as u32,
+                                to_spec_node_id: to_index.index() as u32,
+                                stream_node_id: stream.index() as u32,
+                                stream_def_id: self.graph[stream]
+                                    .stream_def_id
+                                    .clone()
+                                    .unwrap_or_else(|| self.graph[stream].label.clone()),
+                                inlet_node_id: inlet.index() as u32,
+                                is_feedback: self
+                                    .graph
+                                    .find_edge(stream, inlet)
+                                    .is_some_and(|edge| self.graph[edge].is_feedback == Some(true)),
+                            });
+                        }
+                    }
+                }
+            }
+        }
+        connections.sort_by_key(|c| (c.from_spec_node_id, c.to_spec_node_id, c.inlet_node_id));
+        connections
+    }
+
+    /// Returns the Spec node ids in data-flow order, ignoring connections marked as feedback.
+    /// Specs with no ordering constraint between them come out in ascending node id order.
+    /// Fails with the offending loops if an unmarked cycle exists.
+    pub fn topological_spec_order(&self) -> Result<Vec<u32>, Vec<SpecCycle>> {
+        let cycles = self.find_spec_cycles();
+        if !cycles.is_empty() {
+            return Err(cycles);
+        }
+
+        let spec_ids = self.get_spec_node_ids();
+        let mut in_degree: HashMap<u32, usize> = spec_ids.iter().map(|&id| (id, 0)).collect();
+        let mut successors: HashMap<u32, BTreeSet<u32>> = HashMap::new();
+        for connection in self.get_spec_connections() {
+            if connection.is_feedback {
+                continue;
+            }
+            if successors
+                .entry(connection.from_spec_node_id)
+                .or_default()
+                .insert(connection.to_spec_node_id)
+            {
+                *in_degree.entry(connection.to_spec_node_id).or_default() += 1;
+            }
+        }
+
+        let mut ready: BinaryHeap<Reverse<u32>> = in_degree
+            .iter()
+            .filter(|(_, &degree)| degree == 0)
+            .map(|(&id, _)| Reverse(id))
+            .collect();
+        let mut order = Vec::with_capacity(spec_ids.len());
+        while let Some(Reverse(id)) = ready.pop() {
+            order.push(id);
+            for &next in successors.get(&id).into_iter().flatten() {
+                let degree = in_degree.get_mut(&next).expect("successor must be a spec");
+                *degree -= 1;
+                if *degree == 0 {
+                    ready.push(Reverse(next));
+                }
+            }
+        }
+        Ok(order)
+    }
+
+    /// Reports every loop among specs that is not broken by a connection marked as feedback.
+    pub fn find_spec_cycles(&self) -> Vec<SpecCycle> {
+        self.spec_cycles(false)
+    }
+
+    /// Reports every loop among specs that only exists because of a connection marked as
+    /// feedback, i.e. the intended loops. A loop is reported with all the specs it is
+    /// strongly connected to, so when it shares specs with an unmarked loop, it is reported
+    /// here and the unmarked loop by [`DefGraph::find_spec_cycles`].
+    pub fn find_feedback_cycles(&self) -> Vec<SpecCycle> {
+        let connections = self.get_spec_connections();
+        self.spec_cycles(true)
+            .into_iter()
+            .filter(|cycle| !closes_without_feedback(&cycle.spec_node_ids, &connections))
+            .collect()
+    }
+
+    fn spec_cycles(&self, include_feedback: bool) -> Vec<SpecCycle> {
+        let connections: Vec<SpecConnection> = self
+            .get_spec_connections()
+            .into_iter()
+            .filter(|c| include_feedback || !c.is_feedback)
+            .collect();
+
+        let mut spec_graph = DiGraph::<u32, ()>::new();
+        let mut spec_index: HashMap<u32, NodeIndex> = HashMap::new();
+        for id in self.get_spec_node_ids() {
+            spec_index.insert(id, spec_graph.add_node(id));
+        }
+        for c in &connections {
+            spec_graph.add_edge(
+                spec_index[&c.from_spec_node_id],
+                spec_index[&c.to_spec_node_id],
+                (),
+            );
+        }
+
+        let mut cycles: Vec<SpecCycle> = tarjan_scc(&spec_graph)
+            .into_iter()
+            .filter(|component| {
+                component.len() > 1 || spec_graph.contains_edge(component[0], component[0])
+            })
+            .map(|component| {
+                let mut spec_node_ids: Vec<u32> =
+                    component.iter().map(|&index| spec_graph[index]).collect();
+                spec_node_ids.sort_unstable();
+                let spec_identifiers = spec_node_ids
+                    .iter()
+                    .map(|&id| {
+                        let node = &self.graph[NodeIndex::new(id as usize)];
+                        unique_spec_identifier(
+                            node.spec_name.clone().unwrap_or_default(),
+                            node.unique_spec_label.clone(),
+                        )
+                    })
+                    .collect();
+                let stream_def_ids: BTreeSet<String> = connections
+                    .iter()
+                    .filter(|c| {
+                        spec_node_ids.contains(&c.from_spec_node_id)
+                            && spec_node_ids.contains(&c.to_spec_node_id)
+                    })
+                    .map(|c| c.stream_def_id.clone())
+                    .collect();
+                SpecCycle {
+                    spec_node_ids,
+                    spec_identifiers,
+                    stream_def_ids: stream_def_ids.into_iter().collect(),
+                }
+            })
+            .collect();
+        cycles.sort_by(|a, b| a.spec_node_ids.cmp(&b.spec_node_ids));
+        cycles
+    }
+}
+
+/// Whether `spec_node_ids`, which loop back on themselves, still do so through their unmarked
+/// connections alone, in which case no feedback connection is needed to close the loop.
+fn closes_without_feedback(spec_node_ids: &[u32], connections: &[SpecConnection]) -> bool {
+    let mut graph = DiGraph::<u32, ()>::new();
+    let index: HashMap<u32, NodeIndex> = spec_node_ids
+        .iter()
+        .map(|&id| (id, graph.add_node(id)))
+        .collect();
+    for c in connections.iter().filter(|c| !c.is_feedback) {
+        if let (Some(&from), Some(&to)) = (
+            index.get(&c.from_spec_node_id),
+            index.get(&c.to_spec_node_id),
+        ) {
+            graph.add_edge(from, to, ());
+        }
+    }
+    tarjan_scc(&graph).len() == 1 && (spec_node_ids.len() > 1 || graph.edge_count() > 0)
+}
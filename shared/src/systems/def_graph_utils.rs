@@ -1,3 +1,16 @@
+//! Spec and stream identifiers, and the structs naming a spec port.
+//!
+//! A spec id is `name` or `name[label]`. A stream id is `from>>to`, each side either
+//! `(*)` for the outside world or a port: `name[label]/tag` on the from side and
+//! `name(label)/tag` on the to side. Inside names, labels and tags the characters
+//! `\ / [ ] ( ) >` are escaped with a `\`, so every id parses back into the parts it was
+//! built from. Parts free of those characters give the same ids as before escaping existed,
+//! apart from the from-side label, which used to close with `)`.
+//!
+//! Ids stored before that, unescaped and with `[label)` on the from side, are read with
+//! [`IdentifierSyntax::Legacy`].
+
+use crate::systems::def_graph::DefGraphError;
 use serde::Serialize;
 #[derive(Serialize, Debug, PartialEq, Clone)]
 #[serde(rename_all = "camelCase")]
@@ -24,62 +37,244 @@ pub struct ToSpecAndTag {
     pub unique_spec_label: Option<String>,
 }
 
+/// Label older callers pass to mean "no label". Stream ids leave it out.
+const DEFAULT_LABEL: &str = "default_label";
+
+/// Characters that delimit the parts of an id, escaped with a `\` inside a part.
+const RESERVED: [char; 7] = ['\\', '/', '[', ']', '(', ')', '>'];
+
+/// How [`parse_spec_identifier`] and [`parse_stream_identifier`] read an id.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum IdentifierSyntax {
+    /// Ids built by this module: escaped parts, `[label]` on the from side.
+    #[default]
+    Escaped,
+    /// Ids stored before escaping. Parts are taken verbatim, and a from-side label may end
+    /// with `)`. A reserved character inside a part makes the split a best guess: the
+    /// stream splits at the first `>>`, a port at its last `/`, and a label starts at the
+    /// first bracket.
+    Legacy,
+}
+
 /// Generates a unique identifier for a stream with optional unique labels for source and destination.
 // #[napi]
 pub fn unique_stream_identifier(from: Option<SpecTagInfo>, to: Option<SpecTagInfo>) -> String {
-    let from_str = match from {
-        Some(from) => {
-            format!(
-                "{}{}/{}",
-                from.spec_name,
-                match from.unique_spec_label {
-                    Some(label) => {
-                        if label != "default_label" {
-                            format!("[{})", label)
-                        } else {
-                            "".to_string()
-                        }
-                    }
-                    None => "".to_string(),
-                },
-                from.tag
-            )
-        }
-        None => "(*)".to_string(),
-    };
-    let to_str = match to {
-        Some(to) => {
-            format!(
-                "{}{}/{}",
-                to.spec_name,
-                match to.unique_spec_label {
-                    Some(label) => {
-                        if label != "default_label" {
-                            format!("({})", label)
-                        } else {
-                            "".to_string()
-                        }
-                    }
-                    None => "".to_string(),
-                },
-                to.tag
-            )
+    format!(
+        "{}>>{}",
+        stream_side(from, ('[', ']')),
+        stream_side(to, ('(', ')'))
+    )
+}
+
+fn stream_side(port: Option<SpecTagInfo>, (open, close): (char, char)) -> String {
+    match port {
+        Some(port) => {
+            let label = match port.unique_spec_label {
+                Some(label) if label != DEFAULT_LABEL => {
+                    format!("{}{}{}", open, escape(&label), close)
+                }
+                _ => String::new(),
+            };
+            format!("{}{}/{}", escape(&port.spec_name), label, escape(&port.tag))
         }
         None => "(*)".to_string(),
-    };
-    format!("{}>>{}", from_str, to_str)
+    }
 }
 
-
-/// Generateszz a unique identifier for a spec with an optional unique label.
+/// Generates a unique identifier for a spec with an optional unique label.
 pub fn unique_spec_identifier(spec_name: String, unique_spec_label: Option<String>) -> String {
     format!(
         "{}{}",
-        spec_name,
+        escape(&spec_name),
         match unique_spec_label {
-            Some(label) => format!("[{}]", label),
+            Some(label) => format!("[{}]", escape(&label)),
             None => "".to_string(),
         }
     )
 }
 
+/// Splits a spec id into the spec name and label it was built from.
+pub fn parse_spec_identifier(
+    id: &str,
+    syntax: IdentifierSyntax,
+) -> Result<(String, Option<String>), DefGraphError> {
+    let spec = match syntax {
+        IdentifierSyntax::Escaped => {
+            let tokens = tokenize(id)?;
+            parse_spec(id, &tokens, ('[', ']'))?
+        }
+        IdentifierSyntax::Legacy => legacy_spec(id, '[', &[']']),
+    };
+    Ok(spec)
+}
+
+/// Splits a stream id into its from and to ports; `None` stands for `(*)`. A
+/// `"default_label"` label does not survive, since ids leave it out.
+pub fn parse_stream_identifier(
+    id: &str,
+    syntax: IdentifierSyntax,
+) -> Result<(Option<SpecTagInfo>, Option<SpecTagInfo>), DefGraphError> {
+    match syntax {
+        IdentifierSyntax::Escaped => {
+            let tokens = tokenize(id)?;
+            let mut sides = tokens.split(|token| *token == Token::Arrow);
+            let (Some(from), Some(to), None) = (sides.next(), sides.next(), sides.next()) else {
+                return Err(invalid(id, "expected exactly one `>>`"));
+            };
+            Ok((
+                parse_stream_side(id, from, ('[', ']'))?,
+                parse_stream_side(id, to, ('(', ')'))?,
+            ))
+        }
+        IdentifierSyntax::Legacy => {
+            let (from, to) = id
+                .split_once(">>")
+                .ok_or_else(|| invalid(id, "expected `>>`"))?;
+            Ok((
+                legacy_stream_side(id, from, '[', &[']', ')'])?,
+                legacy_stream_side(id, to, '(', &[')'])?,
+            ))
+        }
+    }
+}
+
+fn escape(part: &str) -> String {
+    let mut escaped = String::with_capacity(part.len());
+    for c in part.chars() {
+        if RESERVED.contains(&c) {
+            escaped.push('\\');
+        }
+        escaped.push(c);
+    }
+    escaped
+}
+
+fn invalid(id: &str, reason: &str) -> DefGraphError {
+    DefGraphError::InvalidIdentifier {
+        id: id.to_string(),
+        reason: reason.to_string(),
+    }
+}
+
+#[derive(Debug, PartialEq)]
+enum Token {
+    /// Unescaped text between delimiters.
+    Part(String),
+    /// An unescaped reserved character other than `>`.
+    Delimiter(char),
+    /// `>>`.
+    Arrow,
+}
+
+fn tokenize(id: &str) -> Result<Vec<Token>, DefGraphError> {
+    let mut tokens = Vec::new();
+    let mut part: Option<String> = None;
+    let mut chars = id.chars();
+    while let Some(c) = chars.next() {
+        let delimiter = match c {
+            '\\' => {
+                let escaped = chars
+                    .next()
+                    .ok_or_else(|| invalid(id, "ends with an unfinished escape"))?;
+                part.get_or_insert_with(String::new).push(escaped);
+                continue;
+            }
+            '>' => {
+                if chars.next() != Some('>') {
+                    return Err(invalid(id, "unescaped `>` outside `>>`"));
+                }
+                Token::Arrow
+            }
+            c if RESERVED.contains(&c) => Token::Delimiter(c),
+            c => {
+                part.get_or_insert_with(String::new).push(c);
+                continue;
+            }
+        };
+        tokens.extend(part.take().map(Token::Part));
+        tokens.push(delimiter);
+    }
+    tokens.extend(part.take().map(Token::Part));
+    Ok(tokens)
+}
+
+/// Reads `name` or `name<open>label<close>`; either part may be empty.
+fn parse_spec(
+    id: &str,
+    tokens: &[Token],
+    (open, close): (char, char),
+) -> Result<(String, Option<String>), DefGraphError> {
+    let (name, rest) = take_part(tokens);
+    match rest {
+        [] => Ok((name, None)),
+        [Token::Delimiter(o), rest @ ..] if *o == open => match take_part(rest) {
+            (label, [Token::Delimiter(c)]) if *c == close => Ok((name, Some(label))),
+            _ => Err(invalid(id, &format!("label must end with `{}`", close))),
+        },
+        _ => Err(invalid(id, "unexpected delimiter in spec name")),
+    }
+}
+
+fn parse_stream_side(
+    id: &str,
+    tokens: &[Token],
+    brackets: (char, char),
+) -> Result<Option<SpecTagInfo>, DefGraphError> {
+    if let [Token::Delimiter('('), Token::Part(star), Token::Delimiter(')')] = tokens {
+        if star == "*" {
+            return Ok(None);
+        }
+    }
+    let slash = tokens
+        .iter()
+        .rposition(|token| *token == Token::Delimiter('/'))
+        .ok_or_else(|| invalid(id, "port has no `/`"))?;
+    let (spec_name, unique_spec_label) = parse_spec(id, &tokens[..slash], brackets)?;
+    let tag = match take_part(&tokens[slash + 1..]) {
+        (tag, []) => tag,
+        _ => return Err(invalid(id, "unexpected delimiter in tag")),
+    };
+    Ok(Some(SpecTagInfo {
+        spec_name,
+        tag,
+        unique_spec_label,
+    }))
+}
+
+/// The leading text part, or an empty one, and the tokens after it.
+fn take_part(tokens: &[Token]) -> (String, &[Token]) {
+    match tokens {
+        [Token::Part(part), rest @ ..] => (part.clone(), rest),
+        _ => (String::new(), tokens),
+    }
+}
+
+fn legacy_spec(spec: &str, open: char, closes: &[char]) -> (String, Option<String>) {
+    let Some(body) = spec.strip_suffix(closes) else {
+        return (spec.to_string(), None);
+    };
+    match body.find(open) {
+        Some(open) => (body[..open].to_string(), Some(body[open + 1..].to_string())),
+        None => (spec.to_string(), None),
+    }
+}
+
+fn legacy_stream_side(
+    id: &str,
+    side: &str,
+    open: char,
+    closes: &[char],
+) -> Result<Option<SpecTagInfo>, DefGraphError> {
+    if side == "(*)" {
+        return Ok(None);
+    }
+    let (spec, tag) = side
+        .rsplit_once('/')
+        .ok_or_else(|| invalid(id, "port has no `/`"))?;
+    let (spec_name, unique_spec_label) = legacy_spec(spec, open, closes);
+    Ok(Some(SpecTagInfo {
+        spec_name,
+        tag: tag.to_string(),
+        unique_spec_label,
+    }))
+}
@@ -0,0 +1,274 @@
+use crate::systems::def_graph::{
+    node_type_to_string, DefGraph, DefGraphError, DefGraphNodeType, PortDirection,
+};
+use petgraph::graph::NodeIndex;
+use petgraph::Direction;
+use serde::{Deserialize, Serialize};
+
+use std::collections::{HashMap, HashSet};
+
+/// A single structural problem found by [`DefGraph::validate`].
+///
+/// Node ids are the same `u32` ids handed out by the rest of the `DefGraph` API.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", tag = "kind")]
+pub enum DefGraphDiagnostic {
+    /// The graph has no RootSpec node.
+    MissingRootSpec,
+    /// The graph has more than one RootSpec node.
+    #[serde(rename_all = "camelCase")]
+    MultipleRootSpecs { node_ids: Vec<u32> },
+    /// An Inlet is fed by more than one StreamDef without declaring a merge policy.
+    #[serde(rename_all = "camelCase")]
+    InletHasMultipleStreamDefs {
+        inlet_node_id: u32,
+        stream_node_ids: Vec<u32>,
+    },
+    /// An Inlet is not attached to exactly one Spec/RootSpec.
+    #[serde(rename_all = "camelCase")]
+    InletNotAttachedToOneSpec {
+        inlet_node_id: u32,
+        spec_node_ids: Vec<u32>,
+    },
+    /// An Inlet without a transform is fed by an Outlet whose schema it does not accept.
+    #[serde(rename_all = "camelCase")]
+    IncompatiblePortSchemas {
+        outlet_node_id: u32,
+        inlet_node_id: u32,
+        reason: String,
+    },
+    /// An Outlet feeds more than one StreamDef.
+    #[serde(rename_all = "camelCase")]
+    OutletHasMultipleStreamDefs {
+        outlet_node_id: u32,
+        stream_node_ids: Vec<u32>,
+    },
+    /// An Outlet is not attached to exactly one Spec/RootSpec.
+    #[serde(rename_all = "camelCase")]
+    OutletNotAttachedToOneSpec {
+        outlet_node_id: u32,
+        spec_node_ids: Vec<u32>,
+    },
+    /// An Alias has no `direction`.
+    #[serde(rename_all = "camelCase")]
+    AliasMissingDirection { alias_node_id: u32 },
+    /// An Alias is not wired `Inlet -> Alias -> RootSpec` (for `"in"`) or
+    /// `RootSpec -> Alias -> Outlet` (for `"out"`).
+    #[serde(rename_all = "camelCase")]
+    AliasMiswired {
+        alias_node_id: u32,
+        direction: PortDirection,
+    },
+    /// A non-root node has no edges at all.
+    #[serde(rename_all = "camelCase")]
+    OrphanedNode { node_id: u32 },
+    /// A `node_indices` entry points at a node that does not exist.
+    #[serde(rename_all = "camelCase")]
+    IndexPointsToMissingNode { key: String, node_id: u32 },
+    /// A `node_indices` entry points at a node of a different type than its key claims.
+    #[serde(rename_all = "camelCase")]
+    IndexNodeTypeMismatch { key: String, node_id: u32 },
+    /// Several `node_indices` entries point at the same node.
+    #[serde(rename_all = "camelCase")]
+    IndexSharedByKeys { node_id: u32, keys: Vec<String> },
+    /// No `node_indices` entry points at the node, so lookups miss it and the builder
+    /// methods would add a duplicate of it.
+    #[serde(rename_all = "camelCase")]
+    UnindexedNode { node_id: u32 },
+}
+
+impl DefGraph {
+    /// Checks every structural invariant the rest of the code relies on and returns all
+    /// violations found. An empty result means the graph is well formed.
+    pub fn validate(&self) -> Vec<DefGraphDiagnostic> {
+        let mut diagnostics = Vec::new();
+
+        let root_spec_ids = self.node_ids_of_type(&DefGraphNodeType::RootSpec);
+        match root_spec_ids.len() {
+            0 => diagnostics.push(DefGraphDiagnostic::MissingRootSpec),
+            1 => {}
+            _ => diagnostics.push(DefGraphDiagnostic::MultipleRootSpecs {
+                node_ids: root_spec_ids,
+            }),
+        }
+
+        for index in self.graph.node_indices() {
+            let node = &self.graph[index];
+            let node_id = index.index() as u32;
+            match node.node_type {
+                DefGraphNodeType::Inlet => {
+                    let stream_node_ids = self.neighbors_of_types(
+                        index,
+                        Direction::Incoming,
+                        &[DefGraphNodeType::StreamDef],
+                    );
+                    if stream_node_ids.len() > 1 && node.merge_policy.is_none() {
+                        diagnostics.push(DefGraphDiagnostic::InletHasMultipleStreamDefs {
+                            inlet_node_id: node_id,
+                            stream_node_ids,
+                        });
+                    }
+                    let spec_node_ids = self.neighbors_of_types(
+                        index,
+                        Direction::Outgoing,
+                        &[DefGraphNodeType::Spec, DefGraphNodeType::RootSpec],
+                    );
+                    if spec_node_ids.len() != 1 {
+                        diagnostics.push(DefGraphDiagnostic::InletNotAttachedToOneSpec {
+                            inlet_node_id: node_id,
+                            spec_node_ids,
+                        });
+                    }
+                    if node.has_transform != Some(true) {
+                        for outlet in self.connected_outlets(index) {
+                            if let Err(e) = self.check_port_schemas(outlet, index) {
+                                diagnostics.push(DefGraphDiagnostic::IncompatiblePortSchemas {
+                                    outlet_node_id: outlet.index() as u32,
+                                    inlet_node_id: node_id,
+                                    reason: match e {
+                                        DefGraphError::IncompatibleSchemas { reason, .. } => reason,
+                                        other => other.to_string(),
+                                    },
+                                });
+                            }
+                        }
+                    }
+                }
+                DefGraphNodeType::Outlet => {
+                    let stream_node_ids = self.neighbors_of_types(
+                        index,
+                        Direction::Outgoing,
+                        &[DefGraphNodeType::StreamDef],
+                    );
+                    if stream_node_ids.len() > 1 {
+                        diagnostics.push(DefGraphDiagnostic::OutletHasMultipleStreamDefs {
+                            outlet_node_id: node_id,
+                            stream_node_ids,
+                        });
+                    }
+                    let spec_node_ids = self.neighbors_of_types(
+                        index,
+                        Direction::Incoming,
+                        &[DefGraphNodeType::Spec, DefGraphNodeType::RootSpec],
+                    );
+                    if spec_node_ids.len() != 1 {
+                        diagnostics.push(DefGraphDiagnostic::OutletNotAttachedToOneSpec {
+                            outlet_node_id: node_id,
+                            spec_node_ids,
+                        });
+                    }
+                }
+                DefGraphNodeType::Alias => {
+                    let Some(direction) = node.direction else {
+                        diagnostics.push(DefGraphDiagnostic::AliasMissingDirection {
+                            alias_node_id: node_id,
+                        });
+                        continue;
+                    };
+                    let (upstream, downstream) = match direction {
+                        PortDirection::In => (DefGraphNodeType::Inlet, DefGraphNodeType::RootSpec),
+                        PortDirection::Out => {
+                            (DefGraphNodeType::RootSpec, DefGraphNodeType::Outlet)
+                        }
+                    };
+                    let inbound: Vec<_> = self
+                        .graph
+                        .neighbors_directed(index, Direction::Incoming)
+                        .collect();
+                    let outbound: Vec<_> = self
+                        .graph
+                        .neighbors_directed(index, Direction::Outgoing)
+                        .collect();
+                    let wired = inbound.len() == 1
+                        && outbound.len() == 1
+                        && self.graph[inbound[0]].node_type == upstream
+                        && self.graph[outbound[0]].node_type == downstream;
+                    if !wired {
+                        diagnostics.push(DefGraphDiagnostic::AliasMiswired {
+                            alias_node_id: node_id,
+                            direction,
+                        });
+                    }
+                }
+                _ => {}
+            }
+
+            if node.node_type != DefGraphNodeType::RootSpec
+                && self.graph.neighbors_undirected(index).next().is_none()
+            {
+                diagnostics.push(DefGraphDiagnostic::OrphanedNode { node_id });
+            }
+        }
+
+        let mut keys_by_node: HashMap<NodeIndex, Vec<String>> = HashMap::new();
+        let mut keys: Vec<&String> = self.node_indices.keys().collect();
+        keys.sort();
+        for key in keys {
+            let index = self.node_indices[key];
+            let node_id = index.index() as u32;
+            match self.graph.node_weight(index) {
+                None => diagnostics.push(DefGraphDiagnostic::IndexPointsToMissingNode {
+                    key: key.clone(),
+                    node_id,
+                }),
+                Some(node) => {
+                    let prefix = format!("{}_", node_type_to_string(&node.node_type));
+                    // `DefGraph::new` registers the root spec under its bare identifier.
+                    let is_bare_root_key =
+                        node.node_type == DefGraphNodeType::RootSpec && key == &node.label;
+                    if !key.starts_with(&prefix) && !is_bare_root_key {
+                        diagnostics.push(DefGraphDiagnostic::IndexNodeTypeMismatch {
+                            key: key.clone(),
+                            node_id,
+                        });
+                    }
+                    keys_by_node.entry(index).or_default().push(key.clone());
+                }
+            }
+        }
+        let mut shared: Vec<(NodeIndex, Vec<String>)> = keys_by_node
+            .into_iter()
+            .filter(|(_, keys)| keys.len() > 1)
+            .collect();
+        shared.sort_by_key(|(index, _)| *index);
+        for (index, keys) in shared {
+            diagnostics.push(DefGraphDiagnostic::IndexSharedByKeys {
+                node_id: index.index() as u32,
+                keys,
+            });
+        }
+        let indexed: HashSet<NodeIndex> = self.node_indices.values().copied().collect();
+        for index in self.graph.node_indices() {
+            if !indexed.contains(&index) {
+                diagnostics.push(DefGraphDiagnostic::UnindexedNode {
+                    node_id: index.index() as u32,
+                });
+            }
+        }
+
+        diagnostics
+    }
+
+    fn node_ids_of_type(&self, node_type: &DefGraphNodeType) -> Vec<u32> {
+        self.graph
+            .node_indices()
+            .filter(|&index| &self.graph[index].node_type == node_type)
+            .map(|index| index.index() as u32)
+            .collect()
+    }
+
+    fn neighbors_of_types(
+        &self,
+        index: NodeIndex,
+        direction: Direction,
+        types: &[DefGraphNodeType],
+    ) -> Vec<u32> {
+        let mut ids: Vec<u32> = self
+            .neighbor_indices_of_types(index, direction, types)
+            .into_iter()
+            .map(|neighbor| neighbor.index() as u32)
+            .collect();
+        ids.sort_unstable();
+        ids
+    }
+}
@@ -0,0 +1,753 @@
+//! Versioned JSON layout used to save [`DefGraph`] and [`InstantiatedGraph`].
+//!
+//! Every document carries a top-level `version`. Documents without one are version 1, the
+//! raw petgraph layout plus internal lookup maps that was written before versioning existed.
+//! Loaders accept every version up to the current one and migrate it forward; writers
+//! always emit the current version.
+//!
+//! Version 2 `DefGraph`:
+//!
+//! ```json
+//! {
+//!   "version": 2,
+//!   "nodes": [{ "id": 0, "nodeType": "root-spec", "specName": "Root", "label": "Root" }],
+//!   "edges": [{ "from": 1, "to": 0 }]
+//! }
+//! ```
+//!
+//! Each node holds its id plus the [`DefGraphNode`] fields. Ids are the `u32` node ids used
+//! by the API and may have gaps left by removals, up to [`MAX_REMOVED_NODE_IDS`] of them;
+//! larger ids are rejected. Lookup maps are rebuilt on load.
+//!
+//! Version 3 `DefGraph` adds an optional `composites` object mapping each composite spec's
+//! `unique_spec_identifier` to the embedded `DefGraph` document. It is omitted when empty,
+//! so a version 2 document reads as a version 3 one without composites.
+//!
+//! In both graphs an edge may also carry a `meta` object holding its [`EdgeMeta`], e.g.
+//! `{ "from": 4, "to": 5, "meta": { "bufferLimit": 100 } }`. It is omitted when empty,
+//! and edges without it load with empty metadata.
+//!
+//! Version 4 `DefGraph` marks feedback connections with `isFeedback` in the `meta` of their
+//! StreamDef -> Inlet edge. Versions 2 and 3 set `isFeedback` on the Inlet node instead; on
+//! load, every connection into such an inlet is marked.
+//!
+//! Version 5 `DefGraph` and version 3 `InstantiatedGraph` save the graph's id scheme as an
+//! `idScheme` object in the [`BuiltinIdScheme`] layout, e.g.
+//! `{ "kind": "hashedShort", "length": 16 }`, and load it back. It is omitted for
+//! [`ReadableIds`], which is also what documents without it and custom schemes load with.
+//!
+//! Version 3 `InstantiatedGraph`:
+//!
+//! ```json
+//! {
+//!   "version": 3,
+//!   "contextId": "ctx",
+//!   "rootJobId": "rootJob",
+//!   "streamIdOverrides": {},
+//!   "inletHasTransformOverridesByTag": {},
+//!   "streamSourceSpecTypeByStreamId": {},
+//!   "defGraph": { "version": 5, "nodes": [], "edges": [] },
+//!   "nodes": [{ "id": 0, "key": "rootJob", "nodeType": "root-job", "label": "Root" }],
+//!   "edges": [{ "from": 1, "to": 0 }]
+//! }
+//! ```
+//!
+//! `key` is the id a node is looked up by: the job id for jobs, the stream id for streams,
+//! and the `DefGraph` node id for inlets, outlets and aliases.
+//!
+//! Version 4 `InstantiatedGraph` node ids may have gaps, left by
+//! [`InstantiatedGraph::apply_def_graph_change`], with the same [`MAX_REMOVED_NODE_IDS`]
+//! bound as `DefGraph` ids. Earlier versions always run from 0 without gaps.
+//!
+//! `to_json` on both graphs writes the canonical form from [`to_canonical_json`]: object keys
+//! sorted, maps included, nodes ordered by id and edges in insertion order, with no
+//! whitespace. Equal graphs always produce the same string, and loading that string and
+//! writing it again gives it back unchanged.
+
+use crate::systems::def_graph::{
+    stream_def_key, DefGraph, DefGraphError, DefGraphNode, DefGraphNodeType, EdgeMeta,
+    PortDirection,
+};
+use crate::systems::instantiated_graph::{
+    InstantiatedGraph, InstantiatedGraphNode, InstantiatedNodeType, StreamSourceSpecType,
+};
+use crate::systems::def_graph_utils::unique_spec_identifier;
+use crate::systems::graph_index::{DefGraphIndex, InstantiatedGraphIndex};
+use crate::systems::id_scheme::{BuiltinIdScheme, IdScheme, ReadableIds};
+use petgraph::graph::{DiGraph, NodeIndex};
+use petgraph::stable_graph::StableDiGraph;
+use petgraph::visit::{EdgeRef, IntoEdgeReferences};
+use petgraph::Direction;
+use serde::de::Error as _;
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use serde_json::Value;
+
+use std::collections::{BTreeMap, HashMap};
+use std::sync::Arc;
+
+/// Version written by [`DefGraph::to_json`].
+pub const DEF_GRAPH_FORMAT_VERSION: u32 = 5;
+/// Version written by [`InstantiatedGraph::to_json`].
+pub const INSTANTIATED_GRAPH_FORMAT_VERSION: u32 = 4;
+/// How many ids a graph document may leave unused. Each gap is filled with a
+/// placeholder node while loading, so node ids must be below the node count plus this.
+pub const MAX_REMOVED_NODE_IDS: usize = 1 << 16;
+
+#[derive(Serialize, Deserialize)]
+struct WireEdge {
+    from: u32,
+    to: u32,
+    #[serde(default, skip_serializing_if = "EdgeMeta::is_default")]
+    meta: EdgeMeta,
+}
+
+#[derive(Serialize)]
+struct WireDefGraphNodeRef<'a> {
+    id: u32,
+    #[serde(flatten)]
+    node: &'a DefGraphNode,
+}
+
+#[derive(Deserialize)]
+struct WireDefGraphNode {
+    id: u32,
+    /// Feedback flag of an Inlet, written by versions 2 and 3.
+    #[serde(default, rename = "isFeedback")]
+    is_feedback: Option<bool>,
+    #[serde(flatten)]
+    node: DefGraphNode,
+}
+
+#[derive(Serialize)]
+struct WireDefGraphRef<'a> {
+    version: u32,
+    nodes: Vec<WireDefGraphNodeRef<'a>>,
+    edges: Vec<WireEdge>,
+    #[serde(skip_serializing_if = "BTreeMap::is_empty")]
+    composites: &'a BTreeMap<String, DefGraph>,
+    #[serde(rename = "idScheme", skip_serializing_if = "Option::is_none")]
+    id_scheme: Option<BuiltinIdScheme>,
+}
+
+#[derive(Deserialize)]
+struct WireDefGraph {
+    nodes: Vec<WireDefGraphNode>,
+    edges: Vec<WireEdge>,
+    #[serde(default)]
+    composites: BTreeMap<String, DefGraph>,
+    #[serde(default, rename = "idScheme")]
+    id_scheme: Option<BuiltinIdScheme>,
+}
+
+/// Version 1: the derived serde layout of the petgraph-backed struct. Its `nodeIndices` and
+/// `streamNodeIdBySpecIdentifierTypeAndTag` maps are ignored and rebuilt from the graph.
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct DefGraphV1 {
+    graph: StableDiGraph<DefGraphNode, ()>,
+}
+
+#[derive(Serialize)]
+struct WireInstantiatedGraphNodeRef<'a> {
+    id: u32,
+    key: &'a str,
+    #[serde(flatten)]
+    node: &'a InstantiatedGraphNode,
+}
+
+#[derive(Deserialize)]
+struct WireInstantiatedGraphNode {
+    id: u32,
+    key: String,
+    #[serde(flatten)]
+    node: InstantiatedGraphNode,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct WireInstantiatedGraphRef<'a> {
+    version: u32,
+    context_id: &'a str,
+    root_job_id: &'a str,
+    stream_id_overrides: BTreeMap<&'a String, &'a String>,
+    inlet_has_transform_overrides_by_tag: BTreeMap<&'a String, &'a bool>,
+    stream_source_spec_type_by_stream_id: BTreeMap<&'a String, &'a StreamSourceSpecType>,
+    def_graph: &'a DefGraph,
+    nodes: Vec<WireInstantiatedGraphNodeRef<'a>>,
+    edges: Vec<WireEdge>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    id_scheme: Option<BuiltinIdScheme>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct WireInstantiatedGraph {
+    context_id: String,
+    root_job_id: String,
+    stream_id_overrides: HashMap<String, String>,
+    inlet_has_transform_overrides_by_tag: HashMap<String, bool>,
+    stream_source_spec_type_by_stream_id: HashMap<String, StreamSourceSpecType>,
+    def_graph: DefGraph,
+    nodes: Vec<WireInstantiatedGraphNode>,
+    edges: Vec<WireEdge>,
+    #[serde(default)]
+    id_scheme: Option<BuiltinIdScheme>,
+}
+
+/// Version 1: the derived serde layout of the petgraph-backed struct.
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct InstantiatedGraphV1 {
+    graph: DiGraph<InstantiatedGraphNode, ()>,
+    node_indices: HashMap<String, NodeIndex>,
+    inverse_node_indices: HashMap<NodeIndex, String>,
+    def_graph: DefGraph,
+    context_id: String,
+    root_job_id: String,
+    stream_id_overrides: HashMap<String, String>,
+    inlet_has_transform_overrides_by_tag: HashMap<String, bool>,
+    stream_source_spec_type_by_stream_id: HashMap<String, StreamSourceSpecType>,
+}
+
+/// Serializes `value` as canonical JSON: every object's keys in sorted order and no
+/// whitespace. Arrays keep their order.
+pub fn to_canonical_json<T: Serialize + ?Sized>(value: &T) -> Result<String, serde_json::Error> {
+    serde_json::to_string(&sort_keys(serde_json::to_value(value)?))
+}
+
+fn sort_keys(value: Value) -> Value {
+    match value {
+        Value::Object(map) => {
+            let mut entries: Vec<(String, Value)> = map.into_iter().collect();
+            entries.sort_by(|(a, _), (b, _)| a.cmp(b));
+            Value::Object(
+                entries
+                    .into_iter()
+                    .map(|(key, value)| (key, sort_keys(value)))
+                    .collect(),
+            )
+        }
+        Value::Array(items) => Value::Array(items.into_iter().map(sort_keys).collect()),
+        other => other,
+    }
+}
+
+/// The scheme saved with a graph: `None` for the default and for custom schemes.
+fn saved_id_scheme(id_scheme: &dyn IdScheme) -> Option<BuiltinIdScheme> {
+    id_scheme
+        .builtin()
+        .filter(|scheme| *scheme != BuiltinIdScheme::Readable)
+}
+
+/// Reads `version` from a document, treating a missing field as version 1.
+fn format_version(value: &Value) -> Result<u64, String> {
+    match value.get("version") {
+        None => Ok(1),
+        Some(version) => version
+            .as_u64()
+            .ok_or_else(|| format!("version must be a non-negative integer, found {}", version)),
+    }
+}
+
+impl DefGraph {
+    /// Builds a `DefGraph` from a parsed JSON document of any supported format version.
+    pub fn from_json_value(value: Value) -> Result<DefGraph, DefGraphError> {
+        let version = format_version(&value).map_err(DefGraphError::Deserialize)?;
+        let deserialize_error = |e: serde_json::Error| DefGraphError::Deserialize(e.to_string());
+        match version {
+            1 => {
+                let v1: DefGraphV1 = serde_json::from_value(value).map_err(deserialize_error)?;
+                let graph = v1.graph.map(|_, node| node.clone(), |_, _| EdgeMeta::default());
+                let mut def_graph = DefGraph {
+                    indexes: DefGraphIndex::build(&graph),
+                    graph,
+                    node_indices: HashMap::new(),
+                    stream_node_id_by_spec_identifier_type_and_tag: HashMap::new(),
+                    composites: BTreeMap::new(),
+                    id_scheme: Arc::new(ReadableIds),
+                };
+                def_graph.rebuild_lookup_indices();
+                Ok(def_graph)
+            }
+            2..=5 => {
+                let wire: WireDefGraph =
+                    serde_json::from_value(value).map_err(deserialize_error)?;
+                DefGraph::from_wire(wire)
+            }
+            _ => Err(DefGraphError::UnsupportedFormatVersion {
+                version,
+                latest: DEF_GRAPH_FORMAT_VERSION,
+            }),
+        }
+    }
+
+    fn from_wire(wire: WireDefGraph) -> Result<DefGraph, DefGraphError> {
+        let id_limit = wire.nodes.len() + MAX_REMOVED_NODE_IDS;
+        if let Some(node) = wire.nodes.iter().find(|n| n.id as usize >= id_limit) {
+            return Err(DefGraphError::Deserialize(format!(
+                "node id {} leaves more than {} unused ids below it",
+                node.id, MAX_REMOVED_NODE_IDS
+            )));
+        }
+        let slot_count = wire
+            .nodes
+            .iter()
+            .map(|n| n.id as usize + 1)
+            .max()
+            .unwrap_or(0);
+        let mut slots: Vec<Option<DefGraphNode>> = vec![None; slot_count];
+        let mut feedback_inlets = Vec::new();
+        for WireDefGraphNode {
+            id,
+            is_feedback,
+            node,
+        } in wire.nodes
+        {
+            if is_feedback == Some(true) && node.node_type == DefGraphNodeType::Inlet {
+                feedback_inlets.push(NodeIndex::new(id as usize));
+            }
+            if slots[id as usize].replace(node).is_some() {
+                return Err(DefGraphError::Deserialize(format!(
+                    "node id {} appears more than once",
+                    id
+                )));
+            }
+        }
+
+        // Fill gaps with placeholders and remove them afterwards so every node keeps its id.
+        let mut graph =
+            StableDiGraph::<DefGraphNode, EdgeMeta>::with_capacity(slot_count, wire.edges.len());
+        let mut holes = Vec::new();
+        for slot in slots {
+            let is_hole = slot.is_none();
+            let index = graph.add_node(slot.unwrap_or_else(|| DefGraphNode {
+                node_type: DefGraphNodeType::StreamDef,
+                spec_name: None,
+                unique_spec_label: None,
+                tag: None,
+                has_transform: None,
+                stream_def_id: None,
+                alias: None,
+                direction: None,
+                label: String::new(),
+                ..Default::default()
+            }));
+            if is_hole {
+                holes.push(index);
+            }
+        }
+        for hole in holes {
+            graph.remove_node(hole);
+        }
+        for WireEdge { from, to, meta } in wire.edges {
+            for id in [from, to] {
+                if !graph.contains_node(NodeIndex::new(id as usize)) {
+                    return Err(DefGraphError::NodeNotFound(id));
+                }
+            }
+            graph.add_edge(
+                NodeIndex::new(from as usize),
+                NodeIndex::new(to as usize),
+                meta,
+            );
+        }
+        for inlet in feedback_inlets {
+            let edges: Vec<_> = graph
+                .edges_directed(inlet, Direction::Incoming)
+                .filter(|edge| graph[edge.source()].node_type == DefGraphNodeType::StreamDef)
+                .map(|edge| edge.id())
+                .collect();
+            for edge in edges {
+                graph[edge].is_feedback = Some(true);
+            }
+        }
+
+        let mut def_graph = DefGraph {
+            indexes: DefGraphIndex::build(&graph),
+            graph,
+            node_indices: HashMap::new(),
+            stream_node_id_by_spec_identifier_type_and_tag: HashMap::new(),
+            composites: wire.composites,
+            id_scheme: wire.id_scheme.unwrap_or_default().build(),
+        };
+        def_graph.rebuild_lookup_indices();
+        Ok(def_graph)
+    }
+
+    /// Recomputes `node_indices` and `stream_node_id_by_spec_identifier_type_and_tag` from the
+    /// graph, producing the same entries the builder methods register.
+    fn rebuild_lookup_indices(&mut self) {
+        self.node_indices.clear();
+        self.stream_node_id_by_spec_identifier_type_and_tag.clear();
+
+        let indices: Vec<NodeIndex> = self.graph.node_indices().collect();
+        for index in indices {
+            let node = &self.graph[index];
+            let key = match node.node_type {
+                // `DefGraph::new` registers the root spec under its bare identifier.
+                DefGraphNodeType::RootSpec => Some(node.label.clone()),
+                DefGraphNodeType::Spec => Some(format!("Spec_{}", node.label)),
+                DefGraphNodeType::Alias => Some(format!("Alias_{}", node.label)),
+                DefGraphNodeType::Inlet => self
+                    .owning_node(index, Direction::Outgoing, ANY_SPEC)
+                    .map(|spec| format!("Inlet_{}/{}", self.graph[spec].label, tag_of(node))),
+                DefGraphNodeType::Outlet => self
+                    .owning_node(index, Direction::Incoming, ANY_SPEC)
+                    .map(|spec| format!("Outlet_{}/{}", self.graph[spec].label, tag_of(node))),
+                DefGraphNodeType::StreamDef => {
+                    let touches_spec = self.graph.neighbors_undirected(index).any(|port| {
+                        self.owning_node(port, Direction::Outgoing, ANY_SPEC).is_some()
+                            || self.owning_node(port, Direction::Incoming, ANY_SPEC).is_some()
+                    });
+                    touches_spec.then(|| {
+                        format!(
+                            "StreamDef_{}",
+                            node.stream_def_id.clone().unwrap_or_default()
+                        )
+                    })
+                }
+            };
+            if let Some(key) = key {
+                self.node_indices.insert(key, index);
+            }
+        }
+
+        let spec_id = |node: &DefGraphNode| {
+            unique_spec_identifier(
+                node.spec_name.clone().unwrap_or_default(),
+                node.unique_spec_label.clone(),
+            )
+        };
+        let mut entries = Vec::new();
+        for inlet in self.graph.node_indices() {
+            if self.graph[inlet].node_type != DefGraphNodeType::Inlet {
+                continue;
+            }
+            let Some(root) = self
+                .neighbor_indices_of_types(
+                    inlet,
+                    Direction::Outgoing,
+                    &[DefGraphNodeType::RootSpec],
+                )
+                .first()
+                .copied()
+            else {
+                continue;
+            };
+            for stream in self.neighbor_indices_of_types(
+                inlet,
+                Direction::Incoming,
+                &[DefGraphNodeType::StreamDef],
+            ) {
+                if let Some(stream_def_id) = self.graph[stream].stream_def_id.clone() {
+                    entries.push((
+                        stream_def_key(
+                            &spec_id(&self.graph[root]),
+                            PortDirection::In,
+                            &tag_of(&self.graph[inlet]),
+                        ),
+                        stream_def_id,
+                    ));
+                }
+            }
+        }
+        for outlet in self.graph.node_indices() {
+            if self.graph[outlet].node_type != DefGraphNodeType::Outlet {
+                continue;
+            }
+            let producer = self.neighbor_indices_of_types(
+                outlet,
+                Direction::Incoming,
+                &[DefGraphNodeType::Spec, DefGraphNodeType::RootSpec],
+            );
+            let Some(&producer) = producer.first() else {
+                continue;
+            };
+            let producer_node = &self.graph[producer];
+            let producer_id = spec_id(producer_node);
+            let output = tag_of(&self.graph[outlet]);
+            for stream in self.neighbor_indices_of_types(
+                outlet,
+                Direction::Outgoing,
+                &[DefGraphNodeType::StreamDef],
+            ) {
+                let Some(stream_def_id) = self.graph[stream].stream_def_id.clone() else {
+                    continue;
+                };
+                if producer_node.node_type == DefGraphNodeType::RootSpec {
+                    entries.push((
+                        stream_def_key(&producer_id, PortDirection::Out, &output),
+                        stream_def_id,
+                    ));
+                    continue;
+                }
+                let consumers: Vec<(String, String)> = self
+                    .neighbor_indices_of_types(
+                        stream,
+                        Direction::Outgoing,
+                        &[DefGraphNodeType::Inlet],
+                    )
+                    .into_iter()
+                    .filter_map(|inlet| {
+                        let spec = self.owning_spec(inlet, Direction::Outgoing)?;
+                        Some((spec_id(&self.graph[spec]), tag_of(&self.graph[inlet])))
+                    })
+                    .collect();
+                if consumers.is_empty() {
+                    continue;
+                }
+                entries.push((
+                    stream_def_key(&producer_id, PortDirection::Out, &output),
+                    stream_def_id.clone(),
+                ));
+                for (consumer_id, input) in consumers {
+                    entries.push((
+                        stream_def_key(&consumer_id, PortDirection::In, &input),
+                        stream_def_id.clone(),
+                    ));
+                }
+            }
+        }
+        self.stream_node_id_by_spec_identifier_type_and_tag
+            .extend(entries);
+    }
+
+    /// The non-root Spec a port is attached to, looking along `direction` from the port.
+    fn owning_spec(&self, port: NodeIndex, direction: Direction) -> Option<NodeIndex> {
+        self.owning_node(port, direction, &[DefGraphNodeType::Spec])
+    }
+
+    /// The node of one of `types` a port is attached to, looking along `direction` from it.
+    fn owning_node(
+        &self,
+        port: NodeIndex,
+        direction: Direction,
+        types: &[DefGraphNodeType],
+    ) -> Option<NodeIndex> {
+        let expected = match direction {
+            Direction::Outgoing => DefGraphNodeType::Inlet,
+            Direction::Incoming => DefGraphNodeType::Outlet,
+        };
+        if self.graph[port].node_type != expected {
+            return None;
+        }
+        self.neighbor_indices_of_types(port, direction, types)
+            .into_iter()
+            .next()
+    }
+}
+
+/// Spec and RootSpec nodes, both of which register their ports in `node_indices`.
+const ANY_SPEC: &[DefGraphNodeType] = &[DefGraphNodeType::Spec, DefGraphNodeType::RootSpec];
+
+fn tag_of(node: &DefGraphNode) -> String {
+    node.tag.clone().unwrap_or_default()
+}
+
+impl Serialize for DefGraph {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        WireDefGraphRef {
+            version: DEF_GRAPH_FORMAT_VERSION,
+            nodes: self
+                .graph
+                .node_indices()
+                .map(|index| WireDefGraphNodeRef {
+                    id: index.index() as u32,
+                    node: &self.graph[index],
+                })
+                .collect(),
+            edges: (&self.graph)
+                .edge_references()
+                .map(|edge| WireEdge {
+                    from: edge.source().index() as u32,
+                    to: edge.target().index() as u32,
+                    meta: edge.weight().clone(),
+                })
+                .collect(),
+            composites: &self.composites,
+            id_scheme: saved_id_scheme(self.id_scheme.as_ref()),
+        }
+        .serialize(serializer)
+    }
+}
+
+impl<'de> Deserialize<'de> for DefGraph {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let value = Value::deserialize(deserializer)?;
+        DefGraph::from_json_value(value).map_err(D::Error::custom)
+    }
+}
+
+impl InstantiatedGraph {
+    /// Restores an `InstantiatedGraph` saved by [`InstantiatedGraph::to_json`], in any
+    /// supported format version. Nodes and edges are taken as stored, not re-instantiated.
+    pub fn from_json(json_str: &str) -> Result<InstantiatedGraph, serde_json::Error> {
+        serde_json::from_str(json_str)
+    }
+
+    fn from_json_value(value: Value) -> Result<InstantiatedGraph, String> {
+        match format_version(&value)? {
+            1 => {
+                let v1: InstantiatedGraphV1 =
+                    serde_json::from_value(value).map_err(|e| e.to_string())?;
+                let graph = StableDiGraph::from(
+                    v1.graph.map(|_, node| node.clone(), |_, _| EdgeMeta::default()),
+                );
+                Ok(InstantiatedGraph {
+                    indexes: InstantiatedGraphIndex::build(&graph),
+                    graph,
+                    node_indices: v1.node_indices,
+                    inverse_node_indices: v1.inverse_node_indices,
+                    def_graph: v1.def_graph,
+                    context_id: v1.context_id,
+                    root_job_id: v1.root_job_id,
+                    stream_id_overrides: v1.stream_id_overrides,
+                    inlet_has_transform_overrides_by_tag: v1.inlet_has_transform_overrides_by_tag,
+                    stream_source_spec_type_by_stream_id: v1.stream_source_spec_type_by_stream_id,
+                    id_scheme: Arc::new(ReadableIds),
+                })
+            }
+            2..=4 => {
+                let wire: WireInstantiatedGraph =
+                    serde_json::from_value(value).map_err(|e| e.to_string())?;
+                InstantiatedGraph::from_wire(wire)
+            }
+            version => Err(DefGraphError::UnsupportedFormatVersion {
+                version,
+                latest: INSTANTIATED_GRAPH_FORMAT_VERSION,
+            }
+            .to_string()),
+        }
+    }
+
+    fn from_wire(wire: WireInstantiatedGraph) -> Result<InstantiatedGraph, String> {
+        let id_limit = wire.nodes.len() + MAX_REMOVED_NODE_IDS;
+        if let Some(node) = wire.nodes.iter().find(|n| n.id as usize >= id_limit) {
+            return Err(format!(
+                "node id {} leaves more than {} unused ids below it",
+                node.id, MAX_REMOVED_NODE_IDS
+            ));
+        }
+        let slot_count = wire
+            .nodes
+            .iter()
+            .map(|n| n.id as usize + 1)
+            .max()
+            .unwrap_or(0);
+        let mut slots: Vec<Option<(String, InstantiatedGraphNode)>> = vec![None; slot_count];
+        for WireInstantiatedGraphNode { id, key, node } in wire.nodes {
+            if slots[id as usize].replace((key, node)).is_some() {
+                return Err(format!("node id {} appears more than once", id));
+            }
+        }
+
+        // Fill gaps with placeholders and remove them afterwards so every node keeps its id.
+        let mut graph = StableDiGraph::<InstantiatedGraphNode, EdgeMeta>::with_capacity(
+            slot_count,
+            wire.edges.len(),
+        );
+        let mut node_indices = HashMap::new();
+        let mut inverse_node_indices = HashMap::new();
+        let mut holes = Vec::new();
+        for slot in slots {
+            match slot {
+                Some((key, node)) => {
+                    let index = graph.add_node(node);
+                    node_indices.insert(key.clone(), index);
+                    inverse_node_indices.insert(index, key);
+                }
+                None => holes.push(graph.add_node(InstantiatedGraphNode {
+                    node_type: InstantiatedNodeType::Stream,
+                    job_id: None,
+                    spec_name: None,
+                    unique_spec_label: None,
+                    stream_id: None,
+                    tag: None,
+                    has_transform: None,
+                    alias: None,
+                    direction: None,
+                    merge_policy: None,
+                    label: String::new(),
+                })),
+            }
+        }
+        for hole in holes {
+            graph.remove_node(hole);
+        }
+        for WireEdge { from, to, meta } in wire.edges {
+            let missing = |id: u32| !graph.contains_node(NodeIndex::new(id as usize));
+            if missing(from) || missing(to) {
+                return Err(format!("edge {} -> {} points at a missing node", from, to));
+            }
+            graph.add_edge(
+                NodeIndex::new(from as usize),
+                NodeIndex::new(to as usize),
+                meta,
+            );
+        }
+        Ok(InstantiatedGraph {
+            indexes: InstantiatedGraphIndex::build(&graph),
+            graph,
+            node_indices,
+            inverse_node_indices,
+            def_graph: wire.def_graph,
+            context_id: wire.context_id,
+            root_job_id: wire.root_job_id,
+            stream_id_overrides: wire.stream_id_overrides,
+            inlet_has_transform_overrides_by_tag: wire.inlet_has_transform_overrides_by_tag,
+            stream_source_spec_type_by_stream_id: wire.stream_source_spec_type_by_stream_id,
+            id_scheme: wire.id_scheme.unwrap_or_default().build(),
+        })
+    }
+}
+
+impl Serialize for InstantiatedGraph {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        WireInstantiatedGraphRef {
+            version: INSTANTIATED_GRAPH_FORMAT_VERSION,
+            context_id: &self.context_id,
+            root_job_id: &self.root_job_id,
+            stream_id_overrides: self.stream_id_overrides.iter().collect(),
+            inlet_has_transform_overrides_by_tag: self
+                .inlet_has_transform_overrides_by_tag
+                .iter()
+                .collect(),
+            stream_source_spec_type_by_stream_id: self
+                .stream_source_spec_type_by_stream_id
+                .iter()
+                .collect(),
+            def_graph: &self.def_graph,
+            nodes: self
+                .graph
+                .node_indices()
+                .map(|index| WireInstantiatedGraphNodeRef {
+                    id: index.index() as u32,
+                    key: self
+                        .inverse_node_indices
+                        .get(&index)
+                        .map(String::as_str)
+                        .unwrap_or_default(),
+                    node: &self.graph[index],
+                })
+                .collect(),
+            edges: self
+                .graph
+                .edge_references()
+                .map(|edge| WireEdge {
+                    from: edge.source().index() as u32,
+                    to: edge.target().index() as u32,
+                    meta: edge.weight().clone(),
+                })
+                .collect(),
+            id_scheme: saved_id_scheme(self.id_scheme.as_ref()),
+        }
+        .serialize(serializer)
+    }
+}
+
+impl<'de> Deserialize<'de> for InstantiatedGraph {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let value = Value::deserialize(deserializer)?;
+        InstantiatedGraph::from_json_value(value).map_err(D::Error::custom)
+    }
+}
@@ -0,0 +1,201 @@
+//! Secondary indexes over the nodes of [`DefGraph`] and [`InstantiatedGraph`], so lookups by
+//! node type, spec identifier, port, alias name, job id or stream id do not scan every node.
+//!
+//! The indexes are derived data: they are never serialized, are rebuilt from the graph on
+//! load, and are updated by the few methods that add nodes, add edges or remove nodes.
+//! Every set is ordered by node id, so indexed lookups return nodes in the same order a
+//! full scan would.
+//!
+//! [`DefGraph`]: crate::systems::def_graph::DefGraph
+//! [`InstantiatedGraph`]: crate::systems::instantiated_graph::InstantiatedGraph
+
+use crate::systems::def_graph::{DefGraphNode, DefGraphNodeType, EdgeMeta, PortDirection};
+use crate::systems::def_graph_utils::unique_spec_identifier;
+use crate::systems::instantiated_graph::{InstantiatedGraphNode, InstantiatedNodeType};
+use petgraph::graph::NodeIndex;
+use petgraph::stable_graph::StableDiGraph;
+use std::collections::{BTreeSet, HashMap};
+
+#[derive(Debug, Clone, Default)]
+pub(crate) struct DefGraphIndex {
+    by_type: HashMap<DefGraphNodeType, BTreeSet<NodeIndex>>,
+    /// Spec nodes by `unique_spec_identifier`. The RootSpec is only indexed by type.
+    spec_by_identifier: HashMap<String, NodeIndex>,
+    /// Inlets and Outlets by the Spec or RootSpec they belong to, their direction and tag.
+    /// Ports carry no spec name, so entries are added with the spec-port edge.
+    port_by_spec_and_tag: HashMap<(NodeIndex, PortDirection, String), NodeIndex>,
+    alias_by_name: HashMap<String, BTreeSet<NodeIndex>>,
+}
+
+impl DefGraphIndex {
+    pub(crate) fn build(graph: &StableDiGraph<DefGraphNode, EdgeMeta>) -> Self {
+        let mut index = DefGraphIndex::default();
+        for node_index in graph.node_indices() {
+            index.insert(node_index, &graph[node_index]);
+        }
+        for edge in graph.edge_indices() {
+            if let Some((from, to)) = graph.edge_endpoints(edge) {
+                index.insert_edge(graph, from, to);
+            }
+        }
+        index
+    }
+
+    pub(crate) fn insert(&mut self, index: NodeIndex, node: &DefGraphNode) {
+        self.by_type
+            .entry(node.node_type.clone())
+            .or_default()
+            .insert(index);
+        if let (DefGraphNodeType::Spec, Some(spec_name)) = (&node.node_type, &node.spec_name) {
+            self.spec_by_identifier.insert(
+                unique_spec_identifier(spec_name.clone(), node.unique_spec_label.clone()),
+                index,
+            );
+        }
+        if let (DefGraphNodeType::Alias, Some(alias)) = (&node.node_type, &node.alias) {
+            self.alias_by_name
+                .entry(alias.clone())
+                .or_default()
+                .insert(index);
+        }
+    }
+
+    /// Records the port an edge from `from` to `to` attaches to a spec, if it is one: an
+    /// Inlet feeding a spec, or a spec feeding an Outlet.
+    pub(crate) fn insert_edge(
+        &mut self,
+        graph: &StableDiGraph<DefGraphNode, EdgeMeta>,
+        from: NodeIndex,
+        to: NodeIndex,
+    ) {
+        let is_spec = |index: NodeIndex| {
+            matches!(
+                graph[index].node_type,
+                DefGraphNodeType::Spec | DefGraphNodeType::RootSpec
+            )
+        };
+        let (spec, port, direction) = match (&graph[from].node_type, &graph[to].node_type) {
+            (DefGraphNodeType::Inlet, _) if is_spec(to) => (to, from, PortDirection::In),
+            (_, DefGraphNodeType::Outlet) if is_spec(from) => (from, to, PortDirection::Out),
+            _ => return,
+        };
+        if let Some(tag) = &graph[port].tag {
+            self.port_by_spec_and_tag
+                .entry((spec, direction, tag.clone()))
+                .or_insert(port);
+        }
+    }
+
+    pub(crate) fn rename_alias(&mut self, index: NodeIndex, old: &str, new: &str) {
+        if let Some(indices) = self.alias_by_name.get_mut(old) {
+            indices.remove(&index);
+            if indices.is_empty() {
+                self.alias_by_name.remove(old);
+            }
+        }
+        self.alias_by_name
+            .entry(new.to_string())
+            .or_default()
+            .insert(index);
+    }
+
+    /// Drops every entry whose node is no longer in `graph`.
+    pub(crate) fn retain_live(&mut self, graph: &StableDiGraph<DefGraphNode, EdgeMeta>) {
+        for indices in self.by_type.values_mut() {
+            indices.retain(|&index| graph.contains_node(index));
+        }
+        self.spec_by_identifier
+            .retain(|_, &mut index| graph.contains_node(index));
+        self.port_by_spec_and_tag.retain(|(spec, _, _), &mut port| {
+            graph.contains_node(*spec) && graph.contains_node(port)
+        });
+        for indices in self.alias_by_name.values_mut() {
+            indices.retain(|&index| graph.contains_node(index));
+        }
+        self.alias_by_name.retain(|_, indices| !indices.is_empty());
+    }
+
+    pub(crate) fn of_type(
+        &self,
+        node_type: &DefGraphNodeType,
+    ) -> impl Iterator<Item = NodeIndex> + '_ {
+        self.by_type.get(node_type).into_iter().flatten().copied()
+    }
+
+    pub(crate) fn spec(
+        &self,
+        spec_name: &str,
+        unique_spec_label: Option<&str>,
+    ) -> Option<NodeIndex> {
+        self.spec_by_identifier
+            .get(&unique_spec_identifier(
+                spec_name.to_string(),
+                unique_spec_label.map(str::to_string),
+            ))
+            .copied()
+    }
+
+    /// The Inlet (`In`) or Outlet (`Out`) tagged `tag` of the spec or root spec `spec`.
+    pub(crate) fn port(
+        &self,
+        spec: NodeIndex,
+        direction: PortDirection,
+        tag: &str,
+    ) -> Option<NodeIndex> {
+        self.port_by_spec_and_tag
+            .get(&(spec, direction, tag.to_string()))
+            .copied()
+    }
+
+    /// Alias nodes named `alias`, of either direction.
+    pub(crate) fn aliases_named(&self, alias: &str) -> impl Iterator<Item = NodeIndex> + '_ {
+        self.alias_by_name.get(alias).into_iter().flatten().copied()
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+pub(crate) struct InstantiatedGraphIndex {
+    by_type: HashMap<InstantiatedNodeType, BTreeSet<NodeIndex>>,
+    /// Job and RootJob nodes by job id.
+    job_by_id: HashMap<String, NodeIndex>,
+    stream_by_id: HashMap<String, NodeIndex>,
+}
+
+impl InstantiatedGraphIndex {
+    pub(crate) fn build(graph: &StableDiGraph<InstantiatedGraphNode, EdgeMeta>) -> Self {
+        let mut index = InstantiatedGraphIndex::default();
+        for node_index in graph.node_indices() {
+            let node = &graph[node_index];
+            index
+                .by_type
+                .entry(node.node_type.clone())
+                .or_default()
+                .insert(node_index);
+            if let Some(job_id) = &node.job_id {
+                index.job_by_id.entry(job_id.clone()).or_insert(node_index);
+            }
+            if let Some(stream_id) = &node.stream_id {
+                index
+                    .stream_by_id
+                    .entry(stream_id.clone())
+                    .or_insert(node_index);
+            }
+        }
+        index
+    }
+
+    pub(crate) fn of_type(
+        &self,
+        node_type: &InstantiatedNodeType,
+    ) -> impl Iterator<Item = NodeIndex> + '_ {
+        self.by_type.get(node_type).into_iter().flatten().copied()
+    }
+
+    pub(crate) fn job(&self, job_id: &str) -> Option<NodeIndex> {
+        self.job_by_id.get(job_id).copied()
+    }
+
+    pub(crate) fn stream(&self, stream_id: &str) -> Option<NodeIndex> {
+        self.stream_by_id.get(stream_id).copied()
+    }
+}
@@ -0,0 +1,357 @@
+//! Graphviz DOT and Mermaid renderings of [`DefGraph`] and [`InstantiatedGraph`], for pasting
+//! into docs and incident notes.
+//!
+//! Node shapes follow node type, so specs/jobs, streams, ports and aliases are told apart at a
+//! glance. Nodes are emitted in node id order as `n{id}`, so output is stable for a given graph.
+
+use crate::systems::def_graph::{DefGraph, DefGraphNode, DefGraphNodeType, PortDirection};
+use crate::systems::instantiated_graph::{
+    InstantiatedGraph, InstantiatedGraphNode, InstantiatedNodeType,
+};
+use petgraph::visit::{EdgeRef, IntoEdgeReferences};
+
+/// Controls what [`DefGraph::to_dot`] and friends draw.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct RenderOptions {
+    /// Draw Inlet and Outlet nodes as labels on the edges passing through them instead of
+    /// as nodes of their own.
+    pub collapse_ports: bool,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Shape {
+    Root,
+    Unit,
+    Stream,
+    Inlet,
+    Outlet,
+    Alias,
+}
+
+impl Shape {
+    fn dot(self) -> &'static str {
+        match self {
+            Shape::Root => "doubleoctagon",
+            Shape::Unit => "box",
+            Shape::Stream => "ellipse",
+            Shape::Inlet => "invhouse",
+            Shape::Outlet => "house",
+            Shape::Alias => "note",
+        }
+    }
+
+    fn mermaid(self, label: &str) -> String {
+        let label = format!("\"{}\"", label.replace('"', "#quot;"));
+        match self {
+            Shape::Root => format!("{{{{{}}}}}", label),
+            Shape::Unit => format!("[{}]", label),
+            Shape::Stream => format!("([{}])", label),
+            Shape::Inlet => format!("[/{}\\]", label),
+            Shape::Outlet => format!("[\\{}/]", label),
+            Shape::Alias => format!(">{}]", label),
+        }
+    }
+
+    fn is_port(self) -> bool {
+        matches!(self, Shape::Inlet | Shape::Outlet)
+    }
+}
+
+struct RenderNode {
+    id: u32,
+    shape: Shape,
+    label: String,
+    /// Text put on the collapsed edge when this node is a port.
+    port_tag: String,
+}
+
+struct RenderEdge {
+    from: u32,
+    to: u32,
+    label: Option<String>,
+}
+
+/// Node- and edge-list form shared by both graphs and both output formats.
+struct Drawing {
+    name: &'static str,
+    nodes: Vec<RenderNode>,
+    edges: Vec<RenderEdge>,
+}
+
+impl Drawing {
+    /// Replaces each port `p` on a path `a -> p -> b` by an edge `a -> b` labelled with the
+    /// port's tag. An alias on a port is attached to the port's spec instead.
+    fn collapse_ports(self) -> Drawing {
+        let shape_of = |id: u32| self.nodes.iter().find(|n| n.id == id).map(|n| n.shape);
+        let is_port = |id: u32| shape_of(id).is_some_and(Shape::is_port);
+        let is_alias = |id: u32| shape_of(id) == Some(Shape::Alias);
+        let is_spec = |id: u32| matches!(shape_of(id), Some(Shape::Root | Shape::Unit));
+        let mut edges: Vec<RenderEdge> = self
+            .edges
+            .iter()
+            .filter(|e| !is_port(e.from) && !is_port(e.to))
+            .map(|e| RenderEdge {
+                from: e.from,
+                to: e.to,
+                label: e.label.clone(),
+            })
+            .collect();
+        for port in self.nodes.iter().filter(|n| n.shape.is_port()) {
+            let label = Some(port.port_tag.clone());
+            let into: Vec<u32> = self
+                .edges
+                .iter()
+                .filter(|e| e.to == port.id)
+                .map(|e| e.from)
+                .collect();
+            let out: Vec<u32> = self
+                .edges
+                .iter()
+                .filter(|e| e.from == port.id)
+                .map(|e| e.to)
+                .collect();
+            for &from in into.iter().filter(|&&id| !is_alias(id)) {
+                for &to in out.iter().filter(|&&id| !is_alias(id)) {
+                    edges.push(RenderEdge {
+                        from,
+                        to,
+                        label: label.clone(),
+                    });
+                }
+            }
+            let spec = into.iter().chain(&out).copied().find(|&id| is_spec(id));
+            if let Some(spec) = spec {
+                for &alias in into.iter().filter(|&&id| is_alias(id)) {
+                    edges.push(RenderEdge {
+                        from: alias,
+                        to: spec,
+                        label: label.clone(),
+                    });
+                }
+                for &alias in out.iter().filter(|&&id| is_alias(id)) {
+                    edges.push(RenderEdge {
+                        from: spec,
+                        to: alias,
+                        label: label.clone(),
+                    });
+                }
+            }
+        }
+        Drawing {
+            name: self.name,
+            nodes: self
+                .nodes
+                .into_iter()
+                .filter(|n| !n.shape.is_port())
+                .collect(),
+            edges,
+        }
+    }
+
+    fn render(self, options: &RenderOptions, mermaid: bool) -> String {
+        let drawing = if options.collapse_ports {
+            self.collapse_ports()
+        } else {
+            self
+        };
+        if mermaid {
+            drawing.mermaid()
+        } else {
+            drawing.dot()
+        }
+    }
+
+    fn dot(&self) -> String {
+        let escape = |s: &str| s.replace('\\', "\\\\").replace('"', "\\\"");
+        let mut out = format!("digraph {} {{\n  rankdir=LR;\n", self.name);
+        for node in &self.nodes {
+            out.push_str(&format!(
+                "  n{} [label=\"{}\", shape={}];\n",
+                node.id,
+                escape(&node.label),
+                node.shape.dot()
+            ));
+        }
+        for edge in &self.edges {
+            match &edge.label {
+                Some(label) => out.push_str(&format!(
+                    "  n{} -> n{} [label=\"{}\"];\n",
+                    edge.from,
+                    edge.to,
+                    escape(label)
+                )),
+                None => out.push_str(&format!("  n{} -> n{};\n", edge.from, edge.to)),
+            }
+        }
+        out.push_str("}\n");
+        out
+    }
+
+    fn mermaid(&self) -> String {
+        let mut out = String::from("flowchart LR\n");
+        for node in &self.nodes {
+            out.push_str(&format!(
+                "  n{}{}\n",
+                node.id,
+                node.shape.mermaid(&node.label)
+            ));
+        }
+        for edge in &self.edges {
+            match &edge.label {
+                Some(label) => out.push_str(&format!(
+                    "  n{} -->|\"{}\"| n{}\n",
+                    edge.from,
+                    label.replace('"', "#quot;"),
+                    edge.to
+                )),
+                None => out.push_str(&format!("  n{} --> n{}\n", edge.from, edge.to)),
+            }
+        }
+        out
+    }
+}
+
+fn port_label(direction: PortDirection, tag: &str, has_transform: Option<bool>) -> String {
+    match has_transform {
+        Some(true) => format!("{}: {} (transform)", direction, tag),
+        _ => format!("{}: {}", direction, tag),
+    }
+}
+
+fn alias_label(alias: Option<&str>, direction: Option<PortDirection>) -> String {
+    format!(
+        "alias {}/{}",
+        direction.map_or("?", PortDirection::as_str),
+        alias.unwrap_or_default()
+    )
+}
+
+fn def_node(id: u32, node: &DefGraphNode) -> RenderNode {
+    let tag = node.tag.clone().unwrap_or_default();
+    let (shape, label) = match node.node_type {
+        DefGraphNodeType::RootSpec => (Shape::Root, node.label.clone()),
+        DefGraphNodeType::Spec => (Shape::Unit, node.label.clone()),
+        DefGraphNodeType::StreamDef => (
+            Shape::Stream,
+            node.stream_def_id
+                .clone()
+                .unwrap_or_else(|| node.label.clone()),
+        ),
+        DefGraphNodeType::Inlet => (
+            Shape::Inlet,
+            port_label(PortDirection::In, &tag, node.has_transform),
+        ),
+        DefGraphNodeType::Outlet => (Shape::Outlet, port_label(PortDirection::Out, &tag, None)),
+        DefGraphNodeType::Alias => (
+            Shape::Alias,
+            alias_label(node.alias.as_deref(), node.direction),
+        ),
+    };
+    RenderNode {
+        id,
+        shape,
+        label,
+        port_tag: tag,
+    }
+}
+
+fn instantiated_node(id: u32, node: &InstantiatedGraphNode) -> RenderNode {
+    let tag = node.tag.clone().unwrap_or_default();
+    let (shape, label) = match node.node_type {
+        InstantiatedNodeType::RootJob => (
+            Shape::Root,
+            node.job_id.clone().unwrap_or_else(|| node.label.clone()),
+        ),
+        InstantiatedNodeType::Job => (
+            Shape::Unit,
+            match (&node.job_id, &node.spec_name) {
+                (Some(job_id), Some(spec_name)) => format!("{} ({})", job_id, spec_name),
+                _ => node.label.clone(),
+            },
+        ),
+        InstantiatedNodeType::Stream => (
+            Shape::Stream,
+            node.stream_id.clone().unwrap_or_else(|| node.label.clone()),
+        ),
+        InstantiatedNodeType::Inlet => (
+            Shape::Inlet,
+            port_label(PortDirection::In, &tag, node.has_transform),
+        ),
+        InstantiatedNodeType::Outlet => (Shape::Outlet, port_label(PortDirection::Out, &tag, None)),
+        InstantiatedNodeType::Alias => (
+            Shape::Alias,
+            alias_label(node.alias.as_deref(), node.direction),
+        ),
+    };
+    RenderNode {
+        id,
+        shape,
+        label,
+        port_tag: tag,
+    }
+}
+
+impl DefGraph {
+    /// Renders the graph as a Graphviz DOT `digraph`.
+    pub fn to_dot(&self, options: &RenderOptions) -> String {
+        self.drawing().render(options, false)
+    }
+
+    /// Renders the graph as a Mermaid `flowchart`.
+    pub fn to_mermaid(&self, options: &RenderOptions) -> String {
+        self.drawing().render(options, true)
+    }
+
+    fn drawing(&self) -> Drawing {
+        Drawing {
+            name: "DefGraph",
+            nodes: self
+                .graph
+                .node_indices()
+                .map(|index| def_node(index.index() as u32, &self.graph[index]))
+                .collect(),
+            edges: (&self.graph)
+                .edge_references()
+                .map(|edge| RenderEdge {
+                    from: edge.source().index() as u32,
+                    to: edge.target().index() as u32,
+                    label: None,
+                })
+                .collect(),
+        }
+    }
+}
+
+impl InstantiatedGraph {
+    /// Renders the graph as a Graphviz DOT `digraph`. Streams are labelled with their
+    /// resolved stream ids.
+    pub fn to_dot(&self, options: &RenderOptions) -> String {
+        self.drawing().render(options, false)
+    }
+
+    /// Renders the graph as a Mermaid `flowchart`. Streams are labelled with their resolved
+    /// stream ids.
+    pub fn to_mermaid(&self, options: &RenderOptions) -> String {
+        self.drawing().render(options, true)
+    }
+
+    fn drawing(&self) -> Drawing {
+        Drawing {
+            name: "InstantiatedGraph",
+            nodes: self
+                .graph
+                .node_indices()
+                .map(|index| instantiated_node(index.index() as u32, &self.graph[index]))
+                .collect(),
+            edges: self
+                .graph
+                .edge_references()
+                .map(|edge| RenderEdge {
+                    from: edge.source().index() as u32,
+                    to: edge.target().index() as u32,
+                    label: None,
+                })
+                .collect(),
+        }
+    }
+}
@@ -0,0 +1,173 @@
+//! How the ids that leave a graph are generated: `stream_def_id`s in a [`DefGraph`], and job
+//! and stream ids in an [`InstantiatedGraph`].
+//!
+//! [`ReadableIds`] is the default and produces the ids graphs have always had, e.g.
+//! `"[ctx]Summarizer[short]"`. [`HashedIds`] replaces them with short hex digests for
+//! deployments with tight key-length budgets, and [`NamespacedIds`] prefixes them with a
+//! project id. Other deployments can implement [`IdScheme`] themselves.
+//!
+//! Spec identifiers are not part of the scheme. They are parsed back into a spec name and
+//! label (see `parse_spec_identifier`), so they cannot be hashed or prefixed; they only leave
+//! a graph inside job ids, and [`IdScheme::job_id`] is given the spec name and label to
+//! build those from.
+//!
+//! The built-in schemes are saved with a graph and restored when it is loaded. A custom
+//! scheme is not: a graph saved with one loads with [`ReadableIds`], so call
+//! `set_id_scheme` on it again before adding to it.
+//!
+//! [`DefGraph`]: crate::systems::def_graph::DefGraph
+//! [`InstantiatedGraph`]: crate::systems::instantiated_graph::InstantiatedGraph
+
+use crate::systems::def_graph_utils::{
+    unique_spec_identifier, unique_stream_identifier, SpecTagInfo,
+};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+use std::fmt;
+use std::sync::Arc;
+
+/// Generates stream definition, job and stream ids. Every method defaults to the readable
+/// form, so a scheme only overrides the ids it changes.
+///
+/// Ids must be deterministic: instantiating the same graph twice has to give the same ids.
+pub trait IdScheme: fmt::Debug + Send + Sync {
+    /// `stream_def_id` of a new StreamDef. `from` is `None` for a stream with no producer
+    /// yet and `to` is `None` for one with no consumer yet.
+    fn stream_def_id(&self, from: Option<&SpecTagInfo>, to: Option<&SpecTagInfo>) -> String {
+        unique_stream_identifier(from.cloned(), to.cloned())
+    }
+
+    /// Id of the job that runs a spec within the context `context_id`.
+    fn job_id(&self, context_id: &str, spec_name: &str, unique_spec_label: Option<&str>) -> String {
+        format!(
+            "[{}]{}",
+            context_id,
+            unique_spec_identifier(spec_name.to_string(), unique_spec_label.map(str::to_string))
+        )
+    }
+
+    /// Id of the stream a StreamDef becomes within the context `context_id`, unless a
+    /// stream id override names it.
+    fn stream_id(&self, context_id: &str, stream_def_id: &str) -> String {
+        format!("[{}]{}", context_id, stream_def_id)
+    }
+
+    /// The built-in scheme this is, saved with graphs that use it. `None` for custom schemes.
+    fn builtin(&self) -> Option<BuiltinIdScheme> {
+        None
+    }
+}
+
+/// The default: ids readable as the specs and ports they come from.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct ReadableIds;
+
+impl IdScheme for ReadableIds {
+    fn builtin(&self) -> Option<BuiltinIdScheme> {
+        Some(BuiltinIdScheme::Readable)
+    }
+}
+
+/// The first `length` hex digits of the SHA-256 of each readable id. Ids get no shorter
+/// than one digit or longer than the full 64.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct HashedIds {
+    pub length: usize,
+}
+
+impl HashedIds {
+    /// 16 hex digits, i.e. 64 bits: collisions are negligible well past millions of ids.
+    pub const DEFAULT_LENGTH: usize = 16;
+
+    fn hash(&self, readable: String) -> String {
+        let digest: String = Sha256::digest(readable.as_bytes())
+            .iter()
+            .map(|byte| format!("{:02x}", byte))
+            .collect();
+        digest[..self.length.clamp(1, digest.len())].to_string()
+    }
+}
+
+impl Default for HashedIds {
+    fn default() -> Self {
+        HashedIds {
+            length: Self::DEFAULT_LENGTH,
+        }
+    }
+}
+
+impl IdScheme for HashedIds {
+    fn stream_def_id(&self, from: Option<&SpecTagInfo>, to: Option<&SpecTagInfo>) -> String {
+        self.hash(ReadableIds.stream_def_id(from, to))
+    }
+
+    fn job_id(&self, context_id: &str, spec_name: &str, unique_spec_label: Option<&str>) -> String {
+        self.hash(ReadableIds.job_id(context_id, spec_name, unique_spec_label))
+    }
+
+    fn stream_id(&self, context_id: &str, stream_def_id: &str) -> String {
+        self.hash(ReadableIds.stream_id(context_id, stream_def_id))
+    }
+
+    fn builtin(&self) -> Option<BuiltinIdScheme> {
+        Some(BuiltinIdScheme::HashedShort {
+            length: self.length,
+        })
+    }
+}
+
+/// Readable job and stream ids prefixed with `"{namespace}:"`, e.g. a project UUID.
+/// `stream_def_id`s stay readable: they only have to be unique within their graph.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct NamespacedIds {
+    pub namespace: String,
+}
+
+impl IdScheme for NamespacedIds {
+    fn job_id(&self, context_id: &str, spec_name: &str, unique_spec_label: Option<&str>) -> String {
+        format!(
+            "{}:{}",
+            self.namespace,
+            ReadableIds.job_id(context_id, spec_name, unique_spec_label)
+        )
+    }
+
+    fn stream_id(&self, context_id: &str, stream_def_id: &str) -> String {
+        format!(
+            "{}:{}",
+            self.namespace,
+            ReadableIds.stream_id(context_id, stream_def_id)
+        )
+    }
+
+    fn builtin(&self) -> Option<BuiltinIdScheme> {
+        Some(BuiltinIdScheme::Namespaced {
+            namespace: self.namespace.clone(),
+        })
+    }
+}
+
+/// Names one of the built-in schemes, for callers such as the wasm and Python bindings
+/// that cannot pass an [`IdScheme`] implementation.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", tag = "kind")]
+pub enum BuiltinIdScheme {
+    /// [`ReadableIds`].
+    #[default]
+    Readable,
+    /// [`HashedIds`] with `length` hex digits.
+    HashedShort { length: usize },
+    /// [`NamespacedIds`] under `namespace`.
+    Namespaced { namespace: String },
+}
+
+impl BuiltinIdScheme {
+    pub fn build(self) -> Arc<dyn IdScheme> {
+        match self {
+            BuiltinIdScheme::Readable => Arc::new(ReadableIds),
+            BuiltinIdScheme::HashedShort { length } => Arc::new(HashedIds { length }),
+            BuiltinIdScheme::Namespaced { namespace } => Arc::new(NamespacedIds { namespace }),
+        }
+    }
+}
@@ -1,15 +1,20 @@
 use crate::systems::def_graph::{
-    DefGraph, /* DefGraphNode, */ DefGraphNodeType, 
+    DefGraph, DefGraphError, /* DefGraphNode, */ DefGraphNodeType, EdgeMeta, MergePolicy,
+    PortDirection,
     StreamConnectionSource, StreamConnectionTarget,
 };
-use crate::systems::def_graph_utils::unique_spec_identifier;
-use petgraph::graph::{DiGraph, NodeIndex, EdgeIndex};
-use petgraph::visit::EdgeRef;
+use crate::systems::graph_format::to_canonical_json;
+use crate::systems::graph_index::InstantiatedGraphIndex;
+use crate::systems::id_scheme::IdScheme;
+use petgraph::graph::{NodeIndex, EdgeIndex};
+use petgraph::stable_graph::StableDiGraph;
+use petgraph::visit::{EdgeRef, IntoEdgeReferences};
 use petgraph::Direction;
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
+use std::sync::Arc;
 
-#[derive(Debug, PartialEq, Serialize, Deserialize, Clone)]
+#[derive(Debug, PartialEq, Eq, Hash, Serialize, Deserialize, Clone)]
 #[serde(rename_all = "kebab-case")]
 pub enum InstantiatedNodeType {
     RootJob,
@@ -47,7 +52,11 @@ pub struct InstantiatedGraphNode {
     pub alias: Option<String>,
 
     #[serde(skip_serializing_if = "Option::is_none")]
-    pub direction: Option<String>,
+    pub direction: Option<PortDirection>,
+
+    /// Copied from the DefGraph Inlet when it merges several upstream streams.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub merge_policy: Option<MergePolicy>,
 
     /// Each node retains a `label` from the original DefGraph node or a generated one.
     pub label: String,
@@ -87,17 +96,89 @@ pub struct StreamSourceSpecType {
     pub tag: String,
 }
 
-#[derive(Serialize, Deserialize, Debug)]
-#[serde(rename_all = "camelCase")]
+/// Why a [`DefGraph`] could not be instantiated. Each variant names the DefGraph node at
+/// fault by its node id and label.
+#[derive(Debug, Clone, PartialEq)]
+pub enum InstantiationError {
+    /// A Spec node has no `spec_name` to derive its job id from.
+    MissingSpecName { node_id: u32, label: String },
+    /// A StreamDef node has no `stream_def_id` and no stream id override names it.
+    MissingStreamDefId { node_id: u32, label: String },
+    /// An edge refers to a node id the DefGraph does not contain.
+    MissingNode { node_id: u32 },
+    /// The id scheme gave a Spec node a job id another node already has.
+    DuplicateJobId {
+        node_id: u32,
+        label: String,
+        job_id: String,
+    },
+    /// An edge end has no instantiated counterpart, so the edge cannot be copied.
+    UnresolvedEdge {
+        from_node_id: u32,
+        to_node_id: u32,
+        node_id: u32,
+        label: String,
+    },
+    /// The composite specs in the DefGraph could not be flattened.
+    Flatten(DefGraphError),
+}
+
+impl std::fmt::Display for InstantiationError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            InstantiationError::MissingSpecName { node_id, label } => write!(
+                f,
+                "DefGraph node {} ({}) is a Spec without a spec_name",
+                node_id, label
+            ),
+            InstantiationError::MissingStreamDefId { node_id, label } => write!(
+                f,
+                "DefGraph node {} ({}) is a StreamDef without a stream_def_id",
+                node_id, label
+            ),
+            InstantiationError::MissingNode { node_id } => {
+                write!(f, "DefGraph node {} is referenced but does not exist", node_id)
+            }
+            InstantiationError::DuplicateJobId {
+                node_id,
+                label,
+                job_id,
+            } => write!(
+                f,
+                "DefGraph node {} ({}) would get job id {}, which is already taken",
+                node_id, label, job_id
+            ),
+            InstantiationError::UnresolvedEdge {
+                from_node_id,
+                to_node_id,
+                node_id,
+                label,
+            } => write!(
+                f,
+                "DefGraph edge {} -> {} cannot be instantiated: node {} ({}) has no instantiated node",
+                from_node_id, to_node_id, node_id, label
+            ),
+            InstantiationError::Flatten(e) => {
+                write!(f, "Composite specs could not be flattened: {}", e)
+            }
+        }
+    }
+}
+
+impl std::error::Error for InstantiationError {}
+
+/// Serialized through the versioned layout in [`crate::systems::graph_format`].
+#[derive(Debug)]
 pub struct InstantiatedGraph {
-    /// Internal directed graph of InstantiatedGraphNodes.
-    graph: DiGraph<InstantiatedGraphNode, ()>,
+    /// Internal directed graph of InstantiatedGraphNodes. Stable, so node ids survive
+    /// [`InstantiatedGraph::apply_def_graph_change`].
+    pub(crate) graph: StableDiGraph<InstantiatedGraphNode, EdgeMeta>,
 
     /// Mapping from an old DefGraph node's label to the new node index.
-    node_indices: HashMap<String, NodeIndex>,
+    pub(crate) node_indices: HashMap<String, NodeIndex>,
 
     /// Mapping from a new node index to the old DefGraph node's label.
-    inverse_node_indices: HashMap<NodeIndex, String>,
+    pub(crate) inverse_node_indices: HashMap<NodeIndex, String>,
 
     /// The original DefGraph.
     pub def_graph: DefGraph,
@@ -116,11 +197,19 @@ pub struct InstantiatedGraph {
 
     /// Optional - store e.g. (specName, tag) for a given stream ID (unused in the basic tests).
     pub stream_source_spec_type_by_stream_id: HashMap<String, StreamSourceSpecType>,
+
+    /// Nodes by type, job id and stream id; derived from `graph`, never serialized.
+    pub(crate) indexes: InstantiatedGraphIndex,
+    /// Generates job ids and default stream ids. Never serialized.
+    pub(crate) id_scheme: Arc<dyn IdScheme>,
 }
 
 impl InstantiatedGraph {
     /// Creates a new InstantiatedGraph from the given DefGraph, storing the context,
-    /// root job ID, and override maps. Instantiation is performed immediately.
+    /// root job ID, and override maps. Instantiation is performed immediately, with ids
+    /// generated by the DefGraph's id scheme.
+    ///
+    /// Panics if the DefGraph is malformed; see [`InstantiatedGraph::try_new`].
     pub fn new(
         context_id: String,
         root_job_id: String,
@@ -129,7 +218,100 @@ impl InstantiatedGraph {
         stream_source_spec_type_by_stream_id: HashMap<String, StreamSourceSpecType>,
         def_graph: &DefGraph,
     ) -> Self {
-        let graph = DiGraph::<InstantiatedGraphNode, ()>::new();
+        Self::try_new(
+            context_id,
+            root_job_id,
+            stream_id_overrides,
+            inlet_has_transform_overrides_by_tag,
+            stream_source_spec_type_by_stream_id,
+            def_graph,
+        )
+        .unwrap_or_else(|e| panic!("{}", e))
+    }
+
+    /// Like [`InstantiatedGraph::new`], with job ids and default stream ids generated by
+    /// `id_scheme`. The root job id and stream id overrides are used as given.
+    pub fn new_with_id_scheme(
+        context_id: String,
+        root_job_id: String,
+        stream_id_overrides: HashMap<String, String>,
+        inlet_has_transform_overrides_by_tag: HashMap<String, bool>,
+        stream_source_spec_type_by_stream_id: HashMap<String, StreamSourceSpecType>,
+        def_graph: &DefGraph,
+        id_scheme: Arc<dyn IdScheme>,
+    ) -> Self {
+        Self::try_new_with_id_scheme(
+            context_id,
+            root_job_id,
+            stream_id_overrides,
+            inlet_has_transform_overrides_by_tag,
+            stream_source_spec_type_by_stream_id,
+            def_graph,
+            id_scheme,
+        )
+        .unwrap_or_else(|e| panic!("{}", e))
+    }
+
+    /// Fallible version of [`InstantiatedGraph::new`]: a malformed DefGraph gives an
+    /// [`InstantiationError`] naming the node at fault instead of a panic.
+    pub fn try_new(
+        context_id: String,
+        root_job_id: String,
+        stream_id_overrides: HashMap<String, String>,
+        inlet_has_transform_overrides_by_tag: HashMap<String, bool>,
+        stream_source_spec_type_by_stream_id: HashMap<String, StreamSourceSpecType>,
+        def_graph: &DefGraph,
+    ) -> Result<Self, InstantiationError> {
+        Self::try_new_with_id_scheme(
+            context_id,
+            root_job_id,
+            stream_id_overrides,
+            inlet_has_transform_overrides_by_tag,
+            stream_source_spec_type_by_stream_id,
+            def_graph,
+            def_graph.id_scheme().clone(),
+        )
+    }
+
+    /// Fallible version of [`InstantiatedGraph::new_with_id_scheme`].
+    pub fn try_new_with_id_scheme(
+        context_id: String,
+        root_job_id: String,
+        stream_id_overrides: HashMap<String, String>,
+        inlet_has_transform_overrides_by_tag: HashMap<String, bool>,
+        stream_source_spec_type_by_stream_id: HashMap<String, StreamSourceSpecType>,
+        def_graph: &DefGraph,
+        id_scheme: Arc<dyn IdScheme>,
+    ) -> Result<Self, InstantiationError> {
+        // Composite specs are instantiated through their flattened contents.
+        let def_graph = if def_graph.get_composite_spec_identifiers().is_empty() {
+            def_graph.clone()
+        } else {
+            def_graph.flatten().map_err(InstantiationError::Flatten)?
+        };
+        Self::try_new_without_flattening(
+            context_id,
+            root_job_id,
+            stream_id_overrides,
+            inlet_has_transform_overrides_by_tag,
+            stream_source_spec_type_by_stream_id,
+            def_graph,
+            id_scheme,
+        )
+    }
+
+    /// Instantiates `def_graph` as it is: a composite spec becomes a single job, for the
+    /// levels of an [`crate::systems::instantiation_tree::InstantiationTree`].
+    pub(crate) fn try_new_without_flattening(
+        context_id: String,
+        root_job_id: String,
+        stream_id_overrides: HashMap<String, String>,
+        inlet_has_transform_overrides_by_tag: HashMap<String, bool>,
+        stream_source_spec_type_by_stream_id: HashMap<String, StreamSourceSpecType>,
+        def_graph: DefGraph,
+        id_scheme: Arc<dyn IdScheme>,
+    ) -> Result<Self, InstantiationError> {
+        let graph = StableDiGraph::<InstantiatedGraphNode, EdgeMeta>::new();
         let node_indices = HashMap::new();
         let inverse_node_indices = HashMap::new();
         let mut instantiated_graph = InstantiatedGraph {
@@ -141,17 +323,20 @@ impl InstantiatedGraph {
             stream_id_overrides,
             inlet_has_transform_overrides_by_tag,
             stream_source_spec_type_by_stream_id,
-            def_graph: def_graph.clone(),
+            def_graph,
+            indexes: InstantiatedGraphIndex::default(),
+            id_scheme,
         };
-        instantiated_graph.instantiate();
-        instantiated_graph
+        instantiated_graph.instantiate()?;
+        instantiated_graph.indexes = InstantiatedGraphIndex::build(&instantiated_graph.graph);
+        Ok(instantiated_graph)
     }
 
     /// Core routine that does a multi-pass creation of the InstantiatedGraph.
     ///  - In pass 1, we convert each DefGraph node into an InstantiatedGraph node or a suitable ID,
     ///    replicating the logic from the original TypeScript InstantiatedGraph.ts
     ///  - In pass 2, we re-map edges similarly, hooking them up to the new IDs/nodes.
-    fn instantiate(&mut self) {
+    fn instantiate(&mut self) -> Result<(), InstantiationError> {
         let node_ids = self.def_graph.node_indices();
 
         // Holds the newly generated "job node ID" for each old Spec node index.
@@ -164,7 +349,7 @@ impl InstantiatedGraph {
             let node_data = self
                 .def_graph
                 .node_weight(old_index)
-                .expect("DefGraph node missing")
+                .ok_or(InstantiationError::MissingNode { node_id: old_index })?
                 .clone();
 
             let def_node_id_str = old_index.to_string();
@@ -182,6 +367,7 @@ impl InstantiatedGraph {
                         has_transform: None,
                         alias: None,
                         direction: None,
+                        merge_policy: None,
                         label: node_data.label.clone(),
                     };
                     let idx = self.graph.add_node(new_node);
@@ -189,12 +375,21 @@ impl InstantiatedGraph {
                     self.inverse_node_indices.insert(idx, self.root_job_id.clone());
                 }
                 DefGraphNodeType::Spec => {
-                    let spec_identifier = unique_spec_identifier(
-                        node_data.spec_name.clone().unwrap_or_else(|| {
-                        panic!("spec_name is None");
-                    }), 
-                    node_data.unique_spec_label.clone());
-                    let job_id = format!("[{}]{}", self.context_id, spec_identifier);
+                    let spec_name = node_data.spec_name.clone().ok_or_else(|| {
+                        InstantiationError::MissingSpecName {
+                            node_id: old_index,
+                            label: node_data.label.clone(),
+                        }
+                    })?;
+                    let job_id =
+                        self.job_id_for_spec(spec_name, node_data.unique_spec_label.clone());
+                    if self.node_indices.contains_key(&job_id) {
+                        return Err(InstantiationError::DuplicateJobId {
+                            node_id: old_index,
+                            label: node_data.label.clone(),
+                            job_id,
+                        });
+                    }
                     child_job_node_by_node_index.insert(old_index, job_id.clone());
 
                     let new_node = InstantiatedGraphNode {
@@ -207,6 +402,7 @@ impl InstantiatedGraph {
                         has_transform: None,
                         alias: None,
                         direction: None,
+                        merge_policy: None,
                         label: job_id.clone(),
                     };
                     let idx = self.graph.add_node(new_node);
@@ -253,14 +449,14 @@ impl InstantiatedGraph {
 
                     // If still none, default to "[{contextId}]{streamDefId or label}"
                     if chosen_stream_id.is_none() {
-                        // assume node_data.stream_def_id exists or throw 
-                        let fallback_id = node_data
-                            .stream_def_id
-                            .clone()
-                            .unwrap_or_else(|| {
-                                panic!("stream_def_id is None");
-                            });
-                        let default_stream_id = format!("[{}]{}", self.context_id, fallback_id);
+                        let fallback_id = node_data.stream_def_id.clone().ok_or_else(|| {
+                            InstantiationError::MissingStreamDefId {
+                                node_id: old_index,
+                                label: node_data.label.clone(),
+                            }
+                        })?;
+                        let default_stream_id =
+                            self.id_scheme.stream_id(&self.context_id, &fallback_id);
                         chosen_stream_id = Some(default_stream_id);
                     }
 
@@ -281,6 +477,7 @@ impl InstantiatedGraph {
                             has_transform: None,
                             alias: None,
                             direction: None,
+                            merge_policy: None,
                             label: final_stream_id.clone(),
                         };
                         let idx = self.graph.add_node(new_node);
@@ -310,6 +507,7 @@ impl InstantiatedGraph {
                         has_transform: override_transform.or(node_data.has_transform),
                         alias: None,
                         direction: None,
+                        merge_policy: node_data.merge_policy.clone(),
                         label: node_data.label.clone(),
                     };
                     let idx = self.graph.add_node(new_node);
@@ -327,6 +525,7 @@ impl InstantiatedGraph {
                         has_transform: None,
                         alias: None,
                         direction: None,
+                        merge_policy: None,
                         label: node_data.label.clone(),
                     };
                     let idx = self.graph.add_node(new_node);
@@ -343,7 +542,8 @@ impl InstantiatedGraph {
                         tag: None,
                         has_transform: None,
                         alias: node_data.alias.clone(),
-                        direction: node_data.direction.clone(),
+                        direction: node_data.direction,
+                        merge_policy: None,
                         label: node_data.label.clone(),
                     };
                     let idx = self.graph.add_node(new_node);
@@ -355,10 +555,25 @@ impl InstantiatedGraph {
 
         // Second pass: Re-map edges. This matches the TypeScript: we adjust the from/to if they're
         // a Spec => child_job_node_by_node_index, a RootSpec => root_job_id, or a StreamDef => stream_node_by_node_index, etc.
-        let edge_list = self.def_graph.raw_edges();
-        for (from_index, to_index) in edge_list {
-            let from_node = self.def_graph.node_weight(from_index).unwrap();
-            let to_node = self.def_graph.node_weight(to_index).unwrap();
+        let edge_list: Vec<(u32, u32, EdgeMeta)> = (&self.def_graph.graph)
+            .edge_references()
+            .map(|edge| {
+                (
+                    edge.source().index() as u32,
+                    edge.target().index() as u32,
+                    edge.weight().clone(),
+                )
+            })
+            .collect();
+        for (from_index, to_index, meta) in edge_list {
+            let from_node = self
+                .def_graph
+                .node_weight(from_index)
+                .ok_or(InstantiationError::MissingNode { node_id: from_index })?;
+            let to_node = self
+                .def_graph
+                .node_weight(to_index)
+                .ok_or(InstantiationError::MissingNode { node_id: to_index })?;
 
             let new_from = match from_node.node_type {
                 DefGraphNodeType::Spec => {
@@ -366,10 +581,8 @@ impl InstantiatedGraph {
                     if let Some(job_node_id) = child_job_node_by_node_index.get(&from_index) {
                         job_node_id.clone()
                     } else {
-                        // fallback: use from_index converted to string
-                        // cast from_index to NodeIndex
-                        let from_index_node_index = NodeIndex::new(from_index as usize);
-                        self.inverse_node_indices.get(&from_index_node_index).unwrap().to_string()
+                        // Non-job nodes are keyed by their def graph node id
+                        from_index.to_string()
                     }
                 }
                 DefGraphNodeType::RootSpec => self.root_job_id.clone(),
@@ -377,16 +590,14 @@ impl InstantiatedGraph {
                     if let Some(stream_node_id) = stream_node_by_node_index.get(&from_index) {
                         stream_node_id.clone()
                     } else {
-                        // cast from_index to NodeIndex
-                        let from_index_node_index = NodeIndex::new(from_index as usize);
-                        self.inverse_node_indices.get(&from_index_node_index).unwrap().to_string()
+                        // Non-job nodes are keyed by their def graph node id
+                        from_index.to_string()
                     }
                 }
                 // For all else, just use the old label
                 _ => {
-                    // cast from_index to NodeIndex
-                    let from_index_node_index = NodeIndex::new(from_index as usize);
-                    self.inverse_node_indices.get(&from_index_node_index).unwrap().to_string()
+                    // Non-job nodes are keyed by their def graph node id
+                    from_index.to_string()
                 }
             };
 
@@ -395,9 +606,8 @@ impl InstantiatedGraph {
                     if let Some(job_node_id) = child_job_node_by_node_index.get(&to_index) {
                         job_node_id.clone()
                     } else {
-                        // cast to_index to NodeIndex
-                        let to_index_node_index = NodeIndex::new(to_index as usize);
-                        self.inverse_node_indices.get(&to_index_node_index).unwrap().to_string()
+                        // Non-job nodes are keyed by their def graph node id
+                        to_index.to_string()
                     }
                 }
                 DefGraphNodeType::RootSpec => self.root_job_id.clone(),
@@ -405,15 +615,13 @@ impl InstantiatedGraph {
                     if let Some(stream_node_id) = stream_node_by_node_index.get(&to_index) {
                         stream_node_id.clone()
                     } else {
-                        // cast to_index to NodeIndex
-                        let to_index_node_index = NodeIndex::new(to_index as usize);
-                        self.inverse_node_indices.get(&to_index_node_index).unwrap().to_string()
+                        // Non-job nodes are keyed by their def graph node id
+                        to_index.to_string()
                     }
                 }
                 _ => {
-                    // cast to_index to NodeIndex
-                    let to_index_node_index = NodeIndex::new(to_index as usize);
-                    self.inverse_node_indices.get(&to_index_node_index).unwrap().to_string()
+                    // Non-job nodes are keyed by their def graph node id
+                    to_index.to_string()
                 }
             };
 
@@ -422,20 +630,57 @@ impl InstantiatedGraph {
                 (self.node_indices.get(&new_from), self.node_indices.get(&new_to))
             {
                 if !self.graph.contains_edge(*f_idx, *t_idx) {
-                    self.graph.add_edge(*f_idx, *t_idx, ());
+                    self.graph.add_edge(*f_idx, *t_idx, meta);
                 }
             } else {
-                // panic if we don't have the node indices
-                if !self.node_indices.contains_key(&new_from) {
-                    // println!("node_indices: {:?}", self.node_indices);
-                    panic!("Node index not found for new_from: {}. Available node indices: {:?}", new_from, self.node_indices);
-                }
-                if !self.node_indices.contains_key(&new_to) {
-                    // println!("node_indices: {:?}", self.node_indices);
-                    panic!("Node index not found for new_to: {}. Available node indices: {:?}", new_to, self.node_indices);
-                }
+                let (node_id, node) = if self.node_indices.contains_key(&new_from) {
+                    (to_index, to_node)
+                } else {
+                    (from_index, from_node)
+                };
+                return Err(InstantiationError::UnresolvedEdge {
+                    from_node_id: from_index,
+                    to_node_id: to_index,
+                    node_id,
+                    label: node.label.clone(),
+                });
             }
         }
+        Ok(())
+    }
+
+    /// Job ID for a child spec, e.g. "[contextId]SpecName[label]" with the default scheme.
+    fn job_id_for_spec(&self, spec_name: String, unique_spec_label: Option<String>) -> String {
+        self.id_scheme
+            .job_id(&self.context_id, &spec_name, unique_spec_label.as_deref())
+    }
+
+    /// The scheme that generated this graph's job ids and default stream ids.
+    pub fn id_scheme(&self) -> &Arc<dyn IdScheme> {
+        &self.id_scheme
+    }
+
+    /// Replaces the id scheme, e.g. on a graph loaded from JSON that was saved with a custom
+    /// scheme. Ids already in the graph are kept.
+    pub fn set_id_scheme(&mut self, id_scheme: Arc<dyn IdScheme>) {
+        self.id_scheme = id_scheme;
+    }
+
+    /// Child job node IDs in the data-flow order of their specs, so upstream jobs can be
+    /// started first. Falls back to DefGraph node order if the specs form an unmarked cycle.
+    pub fn get_job_node_ids_in_start_order(&self) -> Vec<u32> {
+        let spec_node_ids = self
+            .def_graph
+            .topological_spec_order()
+            .unwrap_or_else(|_| self.def_graph.get_spec_node_ids());
+        spec_node_ids
+            .into_iter()
+            .filter_map(|spec_node_id| {
+                let node = self.def_graph.node_weight(spec_node_id)?;
+                let job_id = self.job_id_for_spec(node.spec_name?, node.unique_spec_label);
+                self.node_indices.get(&job_id).map(|idx| idx.index() as u32)
+            })
+            .collect()
     }
 
     /// Return an InstantiatedGraphNode reference for a given node index, if it exists.
@@ -503,8 +748,8 @@ impl InstantiatedGraph {
         } else {
             None
         };
-        
-        return source;
+
+        source
     }
 
     pub fn inbound_neighbors(&self, node_id: u32) -> Vec<u32> {
@@ -555,53 +800,51 @@ impl InstantiatedGraph {
 
     /// Replicates findStreamNodeIdConnectedToJob({ jobId, type, tag }) from the TS version.
     /// We search for the first "Stream" node that matches the desired in/out connection.
-    /// - If `type == "out"`, we check the stream's source to see if it's from a RootJob node
+    /// - If `direction` is `Out`, we check the stream's source to see if it's from a RootJob node
     ///   with the correct outlet `tag`.
-    /// - If `type == "in"`, we see if at least one target is a job node with `job_id == jobId`
+    /// - If `direction` is `In`, we see if at least one target is a job node with `job_id == jobId`
     ///   and the inlet has `tag`.
+    ///
     /// Returns the final *stream node's label* if found, or None.
     pub fn find_stream_node_id_connected_to_job(
         &self,
         job_id: &str,
-        direction: &str,
+        direction: PortDirection,
         tag: &str,
     ) -> Option<u32> {
-        // Search over all nodes for node_type == Stream
-        for node_idx in self.graph.node_indices() {
-            if let Some(node) = self.graph.node_weight(node_idx) {
-                if node.node_type == InstantiatedNodeType::Stream {
-                    // We'll examine the connections for this stream
-                    let stream_id = node_idx.index() as u32;
-                    let connections = self.get_nodes_connected_to_stream(node_idx.index() as u32);
-
-                    match direction {
-                        "out" => {
-                            // Must come from rootJob outgoing with an outlet node that has the correct tag
-                            if let Some(src) = connections.source {
-                                // Check if the source node is root-job
-                                if src.origin.node_type == InstantiatedNodeType::RootJob
-                                    && src.outlet_node.tag.as_deref() == Some(tag)
-                                {
-                                    return Some(stream_id);
-                                }
-                            }
-                        }
-                        "in" => {
-                            // Must go to a job that has job_id = jobId with an inlet that has the correct tag
-                            let matched_target = connections.targets.iter().any(|t| {
-                                t.destination.job_id.as_deref() == Some(job_id)
-                                    && t.inlet_node.tag.as_deref() == Some(tag)
-                            });
-                            if matched_target {
-                                return Some(stream_id);
-                            }
-                        }
-                        _ => {}
-                    }
-                }
-            }
-        }
-        None
+        // Walk from the job's ports instead of checking every stream: the RootJob's outlets
+        // for "out", the job's inlets for "in".
+        let (job, port_type) = match direction {
+            PortDirection::Out => (
+                self.indexes.of_type(&InstantiatedNodeType::RootJob).next()?,
+                InstantiatedNodeType::Outlet,
+            ),
+            PortDirection::In => (self.indexes.job(job_id)?, InstantiatedNodeType::Inlet),
+        };
+        let neighbors = match direction {
+            PortDirection::Out => Direction::Outgoing,
+            PortDirection::In => Direction::Incoming,
+        };
+        self.graph
+            .neighbors_directed(job, neighbors)
+            .filter(|&port| {
+                let port = &self.graph[port];
+                port.node_type == port_type && port.tag.as_deref() == Some(tag)
+            })
+            .flat_map(|port| self.graph.neighbors_directed(port, neighbors))
+            .filter(|&stream| self.graph[stream].node_type == InstantiatedNodeType::Stream)
+            .min()
+            .map(|stream| stream.index() as u32)
+    }
+
+    /// Node id of the Job or RootJob with this job id.
+    pub fn find_job_node_id(&self, job_id: &str) -> Option<u32> {
+        self.indexes.job(job_id).map(|index| index.index() as u32)
+    }
+
+    /// Node id of the Stream with this resolved stream id.
+    pub fn find_stream_node_id(&self, stream_id: &str) -> Option<u32> {
+        self.indexes.stream(stream_id).map(|index| index.index() as u32)
     }
 
     
@@ -670,6 +913,12 @@ impl InstantiatedGraph {
     // --------------------------------------------------------------------
     // NEW: Provide source(edgeId) and target(edgeId)
     // --------------------------------------------------------------------
+    /// Connection metadata carried over from the DefGraph edge, or `None` for an unknown
+    /// edge ID.
+    pub fn edge_meta(&self, edge_id: u32) -> Option<&EdgeMeta> {
+        self.graph.edge_weight(EdgeIndex::new(edge_id as usize))
+    }
+
     /// Given an "edge ID," return the node ID of that edge's source.
     pub fn source(&self, edge_id: u32) -> u32 {
         let e_idx = EdgeIndex::new(edge_id as usize);
@@ -711,8 +960,10 @@ impl InstantiatedGraph {
         node_idx.index() as u32
     }
 
+    /// Serializes the graph to canonical JSON in the current format version; override maps
+    /// are written in key order, so equal graphs give equal strings.
     pub fn to_json(&self) -> Result<String, serde_json::Error> {
-        serde_json::to_string(&self)
+        to_canonical_json(self)
     }
     
 }
@@ -0,0 +1,301 @@
+//! Re-instantiating a running workflow after its [`DefGraph`] changed, and reporting what a
+//! provisioner has to do about it.
+//!
+//! Ids are derived from the DefGraph exactly as at construction, with the same context, root
+//! job id, overrides and id scheme, so every job and stream that survives the change keeps
+//! its id. The [`InstantiationDelta`] then only lists what is new, gone or connected
+//! differently.
+//!
+//! The graph is updated in place rather than replaced. Jobs, streams, ports and aliases that
+//! survive keep their `u32` node ids, and only the nodes and edges that changed are added or
+//! removed. A removed node's id may be reused by a node added by a later change.
+
+use crate::systems::def_graph::{DefGraph, EdgeMeta, PortDirection};
+use crate::systems::graph_index::InstantiatedGraphIndex;
+use crate::systems::instantiated_graph::{
+    InstantiatedGraph, InstantiatedNodeType, InstantiationError,
+};
+use petgraph::graph::NodeIndex;
+use petgraph::visit::{EdgeRef, IntoEdgeReferences};
+use petgraph::Direction;
+use serde::{Deserialize, Serialize};
+
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet};
+
+/// An input of a job, which a worker subscribes to a stream through.
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct InletRef {
+    pub job_id: String,
+    pub tag: String,
+}
+
+/// What [`InstantiatedGraph::apply_def_graph_change`] changed. Every list is sorted.
+///
+/// Jobs and inlets are those of child jobs, i.e. the workers to start and stop; the root
+/// job is run by the caller. Streams include the root job's inputs and outputs. A job,
+/// stream or inlet present before and after counts as rewired when the streams it is
+/// connected to, or the ends a stream connects, differ.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct InstantiationDelta {
+    pub added_jobs: Vec<String>,
+    pub removed_jobs: Vec<String>,
+    pub rewired_jobs: Vec<String>,
+    pub added_streams: Vec<String>,
+    pub removed_streams: Vec<String>,
+    pub rewired_streams: Vec<String>,
+    pub added_inlets: Vec<InletRef>,
+    pub removed_inlets: Vec<InletRef>,
+    pub rewired_inlets: Vec<InletRef>,
+}
+
+impl InstantiationDelta {
+    /// Whether the change left every job, stream and inlet as it was.
+    pub fn is_empty(&self) -> bool {
+        *self == InstantiationDelta::default()
+    }
+}
+
+/// (job id, port tag) at one end of a stream.
+type Endpoint = (String, String);
+
+/// What a node stands for, independent of its node id: jobs by job id, streams by stream
+/// id, ports by their job, direction and tag, and aliases by name and direction.
+#[derive(PartialEq, Eq, Hash)]
+enum NodeKey {
+    Job(String),
+    Stream(String),
+    Port(PortDirection, String, String),
+    Alias(String, Option<PortDirection>),
+}
+
+/// How a graph's jobs, streams and inlets are connected, keyed by their ids.
+#[derive(Default)]
+struct Wiring {
+    /// Job id -> (direction, tag, stream id) of each stream it reads or writes.
+    jobs: BTreeMap<String, BTreeSet<(PortDirection, String, String)>>,
+    /// Stream id -> (producer, consumers).
+    streams: BTreeMap<String, (Option<Endpoint>, BTreeSet<Endpoint>)>,
+    /// Inlet -> ids of the streams feeding it.
+    inlets: BTreeMap<InletRef, BTreeSet<String>>,
+}
+
+impl InstantiatedGraph {
+    /// Re-instantiates the graph from `new_def`, updating its nodes and edges in place, and
+    /// returns what changed.
+    ///
+    /// Context, root job id, overrides and id scheme are kept, so jobs and streams that
+    /// still exist keep their job and stream ids, and their nodes keep their node ids. On
+    /// error the graph is left as it was.
+    pub fn apply_def_graph_change(
+        &mut self,
+        new_def: &DefGraph,
+    ) -> Result<InstantiationDelta, InstantiationError> {
+        let next = InstantiatedGraph::try_new_with_id_scheme(
+            self.context_id.clone(),
+            self.root_job_id.clone(),
+            self.stream_id_overrides.clone(),
+            self.inlet_has_transform_overrides_by_tag.clone(),
+            self.stream_source_spec_type_by_stream_id.clone(),
+            new_def,
+            self.id_scheme.clone(),
+        )?;
+        let (before, after) = (self.wiring(), next.wiring());
+        self.update_in_place(next);
+
+        let (added_jobs, removed_jobs, rewired_jobs) = compare(&before.jobs, &after.jobs);
+        let (added_streams, removed_streams, rewired_streams) =
+            compare(&before.streams, &after.streams);
+        let (added_inlets, removed_inlets, rewired_inlets) = compare(&before.inlets, &after.inlets);
+        Ok(InstantiationDelta {
+            added_jobs,
+            removed_jobs,
+            rewired_jobs,
+            added_streams,
+            removed_streams,
+            rewired_streams,
+            added_inlets,
+            removed_inlets,
+            rewired_inlets,
+        })
+    }
+
+    /// Makes the graph match `next`, a fresh instantiation with the same context: nodes
+    /// with a [`NodeKey`] in both keep their node id, the rest are removed or added, and
+    /// edges are only touched where they differ.
+    fn update_in_place(&mut self, next: InstantiatedGraph) {
+        let mut existing: HashMap<NodeKey, NodeIndex> = self
+            .graph
+            .node_indices()
+            .filter_map(|index| Some((self.node_key(index)?, index)))
+            .collect();
+        // Node of `next` -> node of `self` it becomes.
+        let mut kept: HashMap<NodeIndex, NodeIndex> = next
+            .graph
+            .node_indices()
+            .filter_map(|index| Some((index, existing.remove(&next.node_key(index)?)?)))
+            .collect();
+        let survivors: HashSet<NodeIndex> = kept.values().copied().collect();
+        let stale: Vec<NodeIndex> = self
+            .graph
+            .node_indices()
+            .filter(|index| !survivors.contains(index))
+            .collect();
+        for index in stale {
+            self.graph.remove_node(index);
+        }
+        for index in next.graph.node_indices() {
+            let node = next.graph[index].clone();
+            match kept.get(&index) {
+                Some(&target) => self.graph[target] = node,
+                None => {
+                    kept.insert(index, self.graph.add_node(node));
+                }
+            }
+        }
+
+        let mut wanted: HashMap<(NodeIndex, NodeIndex), EdgeMeta> = (&next.graph)
+            .edge_references()
+            .map(|edge| {
+                let ends = (kept[&edge.source()], kept[&edge.target()]);
+                (ends, edge.weight().clone())
+            })
+            .collect();
+        let edges: Vec<_> = self.graph.edge_indices().collect();
+        for edge in edges {
+            let ends = self.graph.edge_endpoints(edge).expect("edge index is live");
+            match wanted.remove(&ends) {
+                Some(meta) => self.graph[edge] = meta,
+                None => {
+                    self.graph.remove_edge(edge);
+                }
+            }
+        }
+        // In `next`'s order, so the result does not depend on hashing.
+        for edge in (&next.graph).edge_references() {
+            let (from, to) = (kept[&edge.source()], kept[&edge.target()]);
+            if let Some(meta) = wanted.remove(&(from, to)) {
+                self.graph.add_edge(from, to, meta);
+            }
+        }
+
+        self.node_indices = next
+            .node_indices
+            .into_iter()
+            .map(|(key, index)| (key, kept[&index]))
+            .collect();
+        self.inverse_node_indices = self
+            .node_indices
+            .iter()
+            .map(|(key, &index)| (index, key.clone()))
+            .collect();
+        self.def_graph = next.def_graph;
+        self.indexes = InstantiatedGraphIndex::build(&self.graph);
+    }
+
+    fn node_key(&self, index: NodeIndex) -> Option<NodeKey> {
+        let node = &self.graph[index];
+        let owner = |towards: Direction| {
+            self.graph
+                .neighbors_directed(index, towards)
+                .find(|&neighbor| {
+                    matches!(
+                        self.graph[neighbor].node_type,
+                        InstantiatedNodeType::Job | InstantiatedNodeType::RootJob
+                    )
+                })
+                .and_then(|job| self.graph[job].job_id.clone())
+        };
+        Some(match node.node_type {
+            InstantiatedNodeType::RootJob | InstantiatedNodeType::Job => {
+                NodeKey::Job(node.job_id.clone()?)
+            }
+            InstantiatedNodeType::Stream => NodeKey::Stream(node.stream_id.clone()?),
+            InstantiatedNodeType::Inlet => NodeKey::Port(
+                PortDirection::In,
+                owner(Direction::Outgoing)?,
+                node.tag.clone()?,
+            ),
+            InstantiatedNodeType::Outlet => NodeKey::Port(
+                PortDirection::Out,
+                owner(Direction::Incoming)?,
+                node.tag.clone()?,
+            ),
+            InstantiatedNodeType::Alias => NodeKey::Alias(node.alias.clone()?, node.direction),
+        })
+    }
+
+    fn wiring(&self) -> Wiring {
+        let mut wiring = Wiring::default();
+        for id in self.indexes.of_type(&InstantiatedNodeType::Job) {
+            let job_id = self.graph[id].job_id.clone().unwrap_or_default();
+            wiring.jobs.insert(job_id, BTreeSet::new());
+        }
+
+        for stream in self.indexes.of_type(&InstantiatedNodeType::Stream) {
+            let stream_id = self.graph[stream].stream_id.clone().unwrap_or_default();
+            let connections = self.get_nodes_connected_to_stream(stream.index() as u32);
+            let endpoint = |job: &Option<String>, tag: &Option<String>| {
+                (
+                    job.clone().unwrap_or_default(),
+                    tag.clone().unwrap_or_default(),
+                )
+            };
+
+            let source = connections
+                .source
+                .map(|source| endpoint(&source.origin.job_id, &source.outlet_node.tag));
+            let mut targets = BTreeSet::new();
+            for target in &connections.targets {
+                let (job_id, tag) = endpoint(&target.destination.job_id, &target.inlet_node.tag);
+                if target.destination.node_type == InstantiatedNodeType::Job {
+                    wiring
+                        .inlets
+                        .entry(InletRef {
+                            job_id: job_id.clone(),
+                            tag: tag.clone(),
+                        })
+                        .or_default()
+                        .insert(stream_id.clone());
+                }
+                targets.insert((job_id, tag));
+            }
+
+            let ports = source
+                .iter()
+                .map(|end| (PortDirection::Out, end))
+                .chain(targets.iter().map(|end| (PortDirection::In, end)));
+            for (direction, (job_id, tag)) in ports {
+                if let Some(streams) = wiring.jobs.get_mut(job_id) {
+                    streams.insert((direction, tag.clone(), stream_id.clone()));
+                }
+            }
+            wiring.streams.insert(stream_id, (source, targets));
+        }
+        wiring
+    }
+}
+
+/// (added, removed, rewired) keys between two snapshots.
+fn compare<K: Ord + Clone, V: PartialEq>(
+    before: &BTreeMap<K, V>,
+    after: &BTreeMap<K, V>,
+) -> (Vec<K>, Vec<K>, Vec<K>) {
+    let added = after
+        .keys()
+        .filter(|key| !before.contains_key(key))
+        .cloned()
+        .collect();
+    let removed = before
+        .keys()
+        .filter(|key| !after.contains_key(key))
+        .cloned()
+        .collect();
+    let rewired = after
+        .iter()
+        .filter(|(key, value)| before.get(key).is_some_and(|old| old != *value))
+        .map(|(key, _)| key.clone())
+        .collect();
+    (added, removed, rewired)
+}
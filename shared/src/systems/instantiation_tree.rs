@@ -0,0 +1,195 @@
+//! Instantiating composite specs level by level instead of flattening them.
+//!
+//! [`InstantiatedGraph::new`] flattens composite specs into their parent, so a workflow runs
+//! as one root job and its direct child jobs. An [`InstantiationTree`] keeps the nesting: the
+//! parent graph runs a composite spec as one job, and the graph behind it is instantiated
+//! separately with that job as its root job, the way `parentJobId` links job records.
+//!
+//! A level's context id is its root job id, so the ids of its jobs and streams never clash
+//! with those of other levels. The streams at a composite's ports are shared: inside the
+//! child level, the streams of the specs its aliases point at take the ids of the parent's
+//! streams at the matching composite ports.
+
+use crate::systems::def_graph::{DefGraph, PortDirection};
+use crate::systems::graph_index::InstantiatedGraphIndex;
+use crate::systems::id_scheme::IdScheme;
+use crate::systems::instantiated_graph::{
+    InstantiatedGraph, InstantiatedNodeType, InstantiationError, StreamSourceSpecType,
+};
+use petgraph::graph::NodeIndex;
+use petgraph::Direction;
+
+use std::collections::{BTreeMap, HashMap};
+
+/// One level of a nested instantiation, with the levels of its composite jobs below it.
+#[derive(Debug)]
+pub struct InstantiationTree {
+    /// This level. Composite specs appear in it as ordinary jobs.
+    pub graph: InstantiatedGraph,
+    /// The job that spawned this level's root job, i.e. the parent level's root job.
+    /// `None` at the top.
+    pub parent_job_id: Option<String>,
+    /// The level behind each composite job of `graph`, keyed by that job's id.
+    pub children: BTreeMap<String, InstantiationTree>,
+}
+
+impl InstantiationTree {
+    /// Instantiates `def_graph` and, recursively, every composite spec in it. Arguments are
+    /// those of [`InstantiatedGraph::try_new`] and apply to the top level; every level uses
+    /// the id scheme of `def_graph`.
+    pub fn try_new(
+        context_id: String,
+        root_job_id: String,
+        stream_id_overrides: HashMap<String, String>,
+        inlet_has_transform_overrides_by_tag: HashMap<String, bool>,
+        stream_source_spec_type_by_stream_id: HashMap<String, StreamSourceSpecType>,
+        def_graph: &DefGraph,
+    ) -> Result<Self, InstantiationError> {
+        let graph = InstantiatedGraph::try_new_without_flattening(
+            context_id,
+            root_job_id,
+            stream_id_overrides,
+            inlet_has_transform_overrides_by_tag,
+            stream_source_spec_type_by_stream_id,
+            def_graph.clone(),
+            def_graph.id_scheme().clone(),
+        )?;
+        Self::with_children(graph, def_graph, None)
+    }
+
+    fn with_children(
+        graph: InstantiatedGraph,
+        def_graph: &DefGraph,
+        parent_job_id: Option<String>,
+    ) -> Result<Self, InstantiationError> {
+        let id_scheme = graph.id_scheme().clone();
+        let mut children = BTreeMap::new();
+        for spec in def_graph.get_spec_node_ids() {
+            let Some(node) = def_graph.node_weight(spec) else {
+                continue;
+            };
+            let spec_name = node.spec_name.unwrap_or_default();
+            let label = node.unique_spec_label.as_deref();
+            let Some(child_def) = def_graph.get_composite(&spec_name, label) else {
+                continue;
+            };
+            let job_id = id_scheme.job_id(&graph.context_id, &spec_name, label);
+            let mut child_graph = InstantiatedGraph::try_new_without_flattening(
+                job_id.clone(),
+                job_id.clone(),
+                HashMap::new(),
+                HashMap::new(),
+                HashMap::new(),
+                child_def.clone(),
+                id_scheme.clone(),
+            )?;
+            child_graph.adopt_port_streams(&graph, &job_id, child_def, id_scheme.as_ref());
+            let child =
+                Self::with_children(child_graph, child_def, Some(graph.root_job_id.clone()))?;
+            children.insert(job_id, child);
+        }
+        Ok(InstantiationTree {
+            graph,
+            parent_job_id,
+            children,
+        })
+    }
+
+    /// The context id of this level, which is also its root job id below the top.
+    pub fn context_id(&self) -> &str {
+        &self.graph.context_id
+    }
+
+    /// This level and every level below it, depth first with parents before children.
+    pub fn levels(&self) -> Vec<&InstantiationTree> {
+        let mut levels = vec![self];
+        for child in self.children.values() {
+            levels.extend(child.levels());
+        }
+        levels
+    }
+
+    /// The level that runs `job_id` as one of its child jobs.
+    pub fn level_of_job(&self, job_id: &str) -> Option<&InstantiationTree> {
+        self.levels().into_iter().find(|level| {
+            level
+                .graph
+                .find_job_node_id(job_id)
+                .and_then(|id| level.graph.node_weight(id))
+                .is_some_and(|node| node.node_type == InstantiatedNodeType::Job)
+        })
+    }
+
+    /// The job that spawned `job_id`: the root job of the level running it. `None` for the
+    /// top root job and for unknown jobs.
+    pub fn parent_job_id_of(&self, job_id: &str) -> Option<&str> {
+        self.level_of_job(job_id)
+            .map(|level| level.graph.root_job_id.as_str())
+    }
+
+    /// Root job of the outermost level containing the stream. A stream at a composite's port
+    /// belongs to the level outside the composite, where it connects sibling jobs.
+    pub fn root_job_owning_stream(&self, stream_id: &str) -> Option<&str> {
+        self.levels()
+            .into_iter()
+            .find(|level| level.graph.find_stream_node_id(stream_id).is_some())
+            .map(|level| level.graph.root_job_id.as_str())
+    }
+}
+
+impl InstantiatedGraph {
+    /// Renames the streams of the inner ports behind `def_graph`'s aliases after the streams
+    /// at the same-named ports of `composite_job_id` in `parent`.
+    fn adopt_port_streams(
+        &mut self,
+        parent: &InstantiatedGraph,
+        composite_job_id: &str,
+        def_graph: &DefGraph,
+        id_scheme: &dyn IdScheme,
+    ) {
+        for alias in def_graph.aliases() {
+            let Some(outer) = parent.port_stream(composite_job_id, alias.direction, &alias.alias)
+            else {
+                continue;
+            };
+            let job_id = id_scheme.job_id(
+                &self.context_id,
+                &alias.spec_name,
+                alias.unique_spec_label.as_deref(),
+            );
+            if let Some(inner) = self.port_stream(&job_id, alias.direction, &alias.tag) {
+                let stream_id = parent.graph[outer].stream_id.clone().unwrap_or_default();
+                self.rename_stream(inner, stream_id);
+            }
+        }
+        self.indexes = InstantiatedGraphIndex::build(&self.graph);
+    }
+
+    /// The stream at a job's inlet or outlet with this tag.
+    fn port_stream(&self, job_id: &str, direction: PortDirection, tag: &str) -> Option<NodeIndex> {
+        let (port_type, towards) = match direction {
+            PortDirection::In => (InstantiatedNodeType::Inlet, Direction::Incoming),
+            PortDirection::Out => (InstantiatedNodeType::Outlet, Direction::Outgoing),
+        };
+        let job = self.indexes.job(job_id)?;
+        self.graph
+            .neighbors_directed(job, towards)
+            .filter(|&port| {
+                let port = &self.graph[port];
+                port.node_type == port_type && port.tag.as_deref() == Some(tag)
+            })
+            .flat_map(|port| self.graph.neighbors_directed(port, towards))
+            .filter(|&stream| self.graph[stream].node_type == InstantiatedNodeType::Stream)
+            .min()
+    }
+
+    fn rename_stream(&mut self, stream: NodeIndex, stream_id: String) {
+        if let Some(old) = self.inverse_node_indices.insert(stream, stream_id.clone()) {
+            self.node_indices.remove(&old);
+        }
+        self.node_indices.insert(stream_id.clone(), stream);
+        let node = &mut self.graph[stream];
+        node.label = stream_id.clone();
+        node.stream_id = Some(stream_id);
+    }
+}
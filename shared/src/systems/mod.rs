@@ -1,5 +1,22 @@
 pub mod def_graph;
+pub mod def_graph_aliases;
+pub mod def_graph_composite;
+pub mod def_graph_diff;
+pub mod def_graph_fingerprint;
+pub mod def_graph_lint;
+pub mod def_graph_reachability;
+pub mod def_graph_removal;
+pub mod def_graph_topology;
 pub mod def_graph_utils;
+pub mod def_graph_validation;
+pub mod graph_format;
+pub mod graph_index;
+pub mod graph_render;
+pub mod id_scheme;
+pub mod port_schema;
 pub mod system_a;
 pub mod system_b;
-pub mod instantiated_graph;
\ No newline at end of file
+pub mod workflow_file;
+pub mod instantiated_graph;
+pub mod instantiated_graph_delta;
+pub mod instantiation_tree;
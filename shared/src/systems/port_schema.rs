@@ -0,0 +1,284 @@
+//! JSON Schema descriptors on Inlet and Outlet nodes, and the check that an outlet's values
+//! fit the inlet it feeds.
+//!
+//! The check is structural and conservative about what it understands: `type`, `enum`,
+//! `const`, `required`, `properties`, `additionalProperties: false` and `items`. Keywords it
+//! does not understand (`anyOf`, `$ref`, `format`, numeric bounds, ...) never cause a
+//! rejection, so a connection is only refused when a mismatch is certain. An outlet that
+//! narrows its values through `anyOf`, `oneOf`, `allOf`, `$ref`, `not` or `if` may well
+//! produce only what the inlet accepts, so it is not checked at all.
+
+use crate::systems::def_graph::{DefGraph, DefGraphError, DefGraphNodeType, PortDirection};
+use crate::systems::def_graph_utils::SpecTagInfo;
+use petgraph::graph::NodeIndex;
+use petgraph::Direction;
+use serde_json::Value;
+
+/// Outlet keywords that can narrow the values by themselves, in ways the check cannot follow.
+const OPAQUE_KEYWORDS: &[&str] = &["anyOf", "oneOf", "allOf", "$ref", "not", "if"];
+
+impl DefGraph {
+    /// Sets (or clears, with `None`) the JSON Schema of an existing port. Fails without
+    /// changing anything if the schema is not valid JSON, or if it makes a connection
+    /// without a transform incompatible.
+    pub fn set_port_schema(
+        &mut self,
+        port: &SpecTagInfo,
+        direction: PortDirection,
+        json_schema_str: Option<String>,
+    ) -> Result<(), DefGraphError> {
+        let port_index = self.schema_port_index(port, direction)?;
+        if let Some(schema) = &json_schema_str {
+            parse_schema(schema).map_err(|reason| DefGraphError::InvalidSchema {
+                port: self.graph[port_index].label.clone(),
+                reason,
+            })?;
+        }
+
+        let previous =
+            std::mem::replace(&mut self.graph[port_index].json_schema_str, json_schema_str);
+        let connections = match direction {
+            PortDirection::In => self
+                .connected_outlets(port_index)
+                .into_iter()
+                .map(|outlet| (outlet, port_index))
+                .collect::<Vec<_>>(),
+            PortDirection::Out => self
+                .connected_inlets(port_index)
+                .into_iter()
+                .map(|inlet| (port_index, inlet))
+                .collect(),
+        };
+        for (outlet, inlet) in connections {
+            if self.graph[inlet].has_transform == Some(true) {
+                continue;
+            }
+            if let Err(e) = self.check_port_schemas(outlet, inlet) {
+                self.graph[port_index].json_schema_str = previous;
+                return Err(e);
+            }
+        }
+        Ok(())
+    }
+
+    /// The JSON Schema of a port, if one was set.
+    pub fn port_schema(
+        &self,
+        port: &SpecTagInfo,
+        direction: PortDirection,
+    ) -> Result<Option<&str>, DefGraphError> {
+        let port_index = self.schema_port_index(port, direction)?;
+        Ok(self.graph[port_index].json_schema_str.as_deref())
+    }
+
+    /// Fails with [`DefGraphError::IncompatibleSchemas`] when both ports carry a schema and
+    /// the outlet's values do not all fit the inlet's schema.
+    pub(crate) fn check_port_schemas(
+        &self,
+        outlet: NodeIndex,
+        inlet: NodeIndex,
+    ) -> Result<(), DefGraphError> {
+        let (Some(outlet_schema), Some(inlet_schema)) = (
+            self.graph[outlet].json_schema_str.as_deref(),
+            self.graph[inlet].json_schema_str.as_deref(),
+        ) else {
+            return Ok(());
+        };
+        let incompatible = |reason: String| DefGraphError::IncompatibleSchemas {
+            from: self.graph[outlet].label.clone(),
+            to: self.graph[inlet].label.clone(),
+            reason,
+        };
+        let outlet_schema = parse_schema(outlet_schema).map_err(&incompatible)?;
+        let inlet_schema = parse_schema(inlet_schema).map_err(&incompatible)?;
+        check_schema_compatibility(&outlet_schema, &inlet_schema).map_err(incompatible)
+    }
+
+    fn schema_port_index(
+        &self,
+        port: &SpecTagInfo,
+        direction: PortDirection,
+    ) -> Result<NodeIndex, DefGraphError> {
+        let spec = self
+            .find_spec_node(&port.spec_name, port.unique_spec_label.as_deref())
+            .ok_or_else(|| DefGraphError::SpecNotFound {
+                spec_name: port.spec_name.clone(),
+                unique_spec_label: port.unique_spec_label.clone(),
+            })?;
+        self.port_node(spec, direction, &port.tag)
+            .map(|index| NodeIndex::new(index as usize))
+            .ok_or_else(|| DefGraphError::PortNotFound {
+                spec_name: port.spec_name.clone(),
+                tag: port.tag.clone(),
+                direction,
+            })
+    }
+
+    /// Outlets feeding an inlet through a StreamDef.
+    pub(crate) fn connected_outlets(&self, inlet: NodeIndex) -> Vec<NodeIndex> {
+        self.neighbor_indices_of_types(inlet, Direction::Incoming, &[DefGraphNodeType::StreamDef])
+            .into_iter()
+            .flat_map(|stream| {
+                self.neighbor_indices_of_types(
+                    stream,
+                    Direction::Incoming,
+                    &[DefGraphNodeType::Outlet],
+                )
+            })
+            .collect()
+    }
+
+    /// Inlets fed by an outlet through a StreamDef.
+    pub(crate) fn connected_inlets(&self, outlet: NodeIndex) -> Vec<NodeIndex> {
+        self.neighbor_indices_of_types(outlet, Direction::Outgoing, &[DefGraphNodeType::StreamDef])
+            .into_iter()
+            .flat_map(|stream| {
+                self.neighbor_indices_of_types(
+                    stream,
+                    Direction::Outgoing,
+                    &[DefGraphNodeType::Inlet],
+                )
+            })
+            .collect()
+    }
+}
+
+fn parse_schema(json_schema_str: &str) -> Result<Value, String> {
+    let schema: Value = serde_json::from_str(json_schema_str).map_err(|e| e.to_string())?;
+    match schema {
+        Value::Object(_) | Value::Bool(_) => Ok(schema),
+        other => Err(format!(
+            "a schema must be an object or a boolean, found {}",
+            other
+        )),
+    }
+}
+
+/// Checks that every value `outlet` allows is also allowed by `inlet`, as far as the
+/// keywords listed in the module docs can tell. The error says where the first mismatch is.
+pub fn check_schema_compatibility(outlet: &Value, inlet: &Value) -> Result<(), String> {
+    check_at("", outlet, inlet)
+}
+
+fn check_at(path: &str, outlet: &Value, inlet: &Value) -> Result<(), String> {
+    let at = if path.is_empty() { "value" } else { path };
+    let unconstrained = serde_json::Map::new();
+    let (outlet, inlet) = match (outlet, inlet) {
+        (_, Value::Bool(true)) | (Value::Bool(false), _) => return Ok(()),
+        (_, Value::Bool(false)) => return Err(format!("{} is never accepted", at)),
+        // `true` allows what `{}` allows.
+        (Value::Bool(true), Value::Object(inlet)) => (&unconstrained, inlet),
+        (Value::Object(outlet), Value::Object(inlet)) => (outlet, inlet),
+        _ => return Ok(()),
+    };
+    if OPAQUE_KEYWORDS.iter().any(|k| outlet.contains_key(*k)) {
+        return Ok(());
+    }
+
+    if let Some(accepted) = inlet.get("type").map(type_set) {
+        let Some(produced) = outlet.get("type").map(type_set) else {
+            return Err(format!(
+                "{} may be any type but must be {}",
+                at,
+                accepted.join(" or ")
+            ));
+        };
+        for t in &produced {
+            let fits =
+                accepted.contains(t) || (t == "integer" && accepted.iter().any(|a| a == "number"));
+            if !fits {
+                return Err(format!(
+                    "{} may be {} but must be {}",
+                    at,
+                    t,
+                    accepted.join(" or ")
+                ));
+            }
+        }
+    }
+
+    if let Some(allowed) = allowed_values(inlet) {
+        let Some(produced) = allowed_values(outlet) else {
+            return Err(format!(
+                "{} must be one of {} but is not restricted",
+                at,
+                Value::Array(allowed)
+            ));
+        };
+        if let Some(extra) = produced.iter().find(|v| !allowed.contains(v)) {
+            return Err(format!("{} may be {} which is not accepted", at, extra));
+        }
+    }
+
+    let required = |schema: &serde_json::Map<String, Value>| -> Vec<String> {
+        schema
+            .get("required")
+            .and_then(Value::as_array)
+            .map(|names| {
+                names
+                    .iter()
+                    .filter_map(|n| n.as_str().map(str::to_string))
+                    .collect()
+            })
+            .unwrap_or_default()
+    };
+    let outlet_required = required(outlet);
+    for name in required(inlet) {
+        if !outlet_required.contains(&name) {
+            return Err(format!("{}.{} is required but may be missing", at, name));
+        }
+    }
+
+    let properties = |schema: &serde_json::Map<String, Value>| {
+        schema
+            .get("properties")
+            .and_then(Value::as_object)
+            .cloned()
+            .unwrap_or_default()
+    };
+    let (outlet_properties, inlet_properties) = (properties(outlet), properties(inlet));
+    let inlet_closed = inlet.get("additionalProperties") == Some(&Value::Bool(false));
+    for (name, produced) in &outlet_properties {
+        let child_path = format!("{}.{}", at, name);
+        match inlet_properties.get(name) {
+            Some(accepted) => check_at(&child_path, produced, accepted)?,
+            None if inlet_closed => {
+                return Err(format!("{} is not an accepted property", child_path))
+            }
+            None => {}
+        }
+    }
+    if inlet_closed && outlet.get("additionalProperties") != Some(&Value::Bool(false)) {
+        let is_object = outlet
+            .get("type")
+            .map(type_set)
+            .is_none_or(|types| types.iter().any(|t| t == "object"));
+        if is_object {
+            return Err(format!("{} may carry properties that are not accepted", at));
+        }
+    }
+
+    if let (Some(produced), Some(accepted)) = (outlet.get("items"), inlet.get("items")) {
+        check_at(&format!("{}[]", at), produced, accepted)?;
+    }
+    Ok(())
+}
+
+fn type_set(value: &Value) -> Vec<String> {
+    match value {
+        Value::String(t) => vec![t.clone()],
+        Value::Array(types) => types
+            .iter()
+            .filter_map(|t| t.as_str().map(str::to_string))
+            .collect(),
+        _ => Vec::new(),
+    }
+}
+
+/// The finite set of values a schema allows through `const` or `enum`, if it has one.
+fn allowed_values(schema: &serde_json::Map<String, Value>) -> Option<Vec<Value>> {
+    if let Some(value) = schema.get("const") {
+        return Some(vec![value.clone()]);
+    }
+    schema.get("enum").and_then(Value::as_array).cloned()
+}
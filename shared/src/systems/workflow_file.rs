@@ -0,0 +1,460 @@
+//! Human-authored workflow documents, in YAML, TOML or JSON, compiled into a [`DefGraph`].
+//!
+//! ```yaml
+//! root:
+//!   name: Meeting
+//!   inputs: [audio]
+//!   outputs: [summary]
+//! specs:
+//!   - name: Transcriber
+//!     inputs: [audio]
+//!   - name: Summarizer
+//!     label: short
+//!     outputs: [summary]
+//! connections:
+//!   - from: Transcriber/text
+//!     to: Summarizer[short]/text
+//!     transform: true
+//! aliases:
+//!   - name: audio
+//!     direction: in
+//!     port: Transcriber/audio
+//!   - name: summary
+//!     direction: out
+//!     port: Summarizer[short]/summary
+//! ```
+//!
+//! Ports are written `Spec/tag`, or `Spec[label]/tag` for a labelled spec, with the
+//! characters `\ / [ ] ( ) >` inside names, labels and tags escaped by a `\` as in spec
+//! identifiers. Every spec a connection or alias mentions must be declared under `specs`.
+//! Every declared spec is in the graph, connected or not; its `inputs` and `outputs` list
+//! ports that exist even when nothing is connected to them, so they can be aliased.
+//! The graph is built with [`DefGraph::new`], [`DefGraph::try_add_connected_dual_specs`] and
+//! [`DefGraph::try_assign_alias`], in document order.
+//!
+//! Errors carry the 1-based line of the offending entry: the parser's position for syntax
+//! and shape errors, and the line the parser finds the connection, alias or spec entry
+//! starting on for the rest.
+
+use crate::systems::def_graph::{DefGraph, PortDirection};
+use crate::systems::def_graph_utils::{
+    parse_spec_identifier, unique_spec_identifier, FromSpecAndTag, IdentifierSyntax, SpecTagInfo,
+    ToSpecAndTag,
+};
+use serde::de::{self, DeserializeSeed, Deserializer, IgnoredAny, MapAccess, SeqAccess, Visitor};
+use serde::Deserialize;
+use std::collections::HashSet;
+use std::fmt;
+
+/// Syntax of a workflow document.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum WorkflowFormat {
+    Yaml,
+    Toml,
+    Json,
+}
+
+impl WorkflowFormat {
+    /// The format for a file extension (`yaml`, `yml`, `toml` or `json`), case-insensitive.
+    pub fn from_extension(extension: &str) -> Option<WorkflowFormat> {
+        match extension.to_ascii_lowercase().as_str() {
+            "yaml" | "yml" => Some(WorkflowFormat::Yaml),
+            "toml" => Some(WorkflowFormat::Toml),
+            "json" => Some(WorkflowFormat::Json),
+            _ => None,
+        }
+    }
+}
+
+/// Why a workflow document could not be compiled, and where.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct WorkflowError {
+    /// 1-based line in the source document, when known.
+    pub line: Option<usize>,
+    /// 1-based column, when the parser reported one.
+    pub column: Option<usize>,
+    pub message: String,
+}
+
+impl fmt::Display for WorkflowError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match (self.line, self.column) {
+            (Some(line), Some(column)) => {
+                write!(f, "line {}, column {}: {}", line, column, self.message)
+            }
+            (Some(line), None) => write!(f, "line {}: {}", line, self.message),
+            _ => write!(f, "{}", self.message),
+        }
+    }
+}
+
+impl std::error::Error for WorkflowError {}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct WorkflowDocument {
+    root: RootDecl,
+    #[serde(default)]
+    specs: Vec<SpecDecl>,
+    #[serde(default)]
+    connections: Vec<ConnectionDecl>,
+    #[serde(default)]
+    aliases: Vec<AliasDecl>,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct RootDecl {
+    name: String,
+    #[serde(default)]
+    inputs: Vec<String>,
+    #[serde(default)]
+    outputs: Vec<String>,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct SpecDecl {
+    name: String,
+    label: Option<String>,
+    #[serde(default)]
+    inputs: Vec<String>,
+    #[serde(default)]
+    outputs: Vec<String>,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct ConnectionDecl {
+    from: PortRef,
+    to: PortRef,
+    #[serde(default)]
+    transform: bool,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct AliasDecl {
+    name: String,
+    direction: PortDirection,
+    port: PortRef,
+}
+
+/// A `Spec/tag` or `Spec[label]/tag` reference, keeping the text it was written as.
+#[derive(Deserialize)]
+#[serde(try_from = "String")]
+struct PortRef {
+    text: String,
+    spec_name: String,
+    unique_spec_label: Option<String>,
+    tag: String,
+}
+
+impl TryFrom<String> for PortRef {
+    type Error = String;
+
+    fn try_from(text: String) -> Result<Self, Self::Error> {
+        let invalid = || format!("expected `Spec/tag` or `Spec[label]/tag`, found `{}`", text);
+        // The tag starts after the last `/` that is not escaped.
+        let mut escaped = false;
+        let mut slash = None;
+        for (i, c) in text.char_indices() {
+            match c {
+                _ if escaped => escaped = false,
+                '\\' => escaped = true,
+                '/' => slash = Some(i),
+                _ => {}
+            }
+        }
+        let slash = slash.ok_or_else(invalid)?;
+        let parse = |part: &str| {
+            parse_spec_identifier(part, IdentifierSyntax::Escaped).map_err(|e| e.to_string())
+        };
+        let (spec_name, unique_spec_label) = parse(&text[..slash])?;
+        // A tag escapes like a spec name, so a label in it means an unescaped bracket.
+        let (tag, None) = parse(&text[slash + 1..])? else {
+            return Err(invalid());
+        };
+        if spec_name.is_empty() || tag.is_empty() || unique_spec_label.as_deref() == Some("") {
+            return Err(invalid());
+        }
+        Ok(PortRef {
+            spec_name,
+            unique_spec_label,
+            tag,
+            text,
+        })
+    }
+}
+
+impl PortRef {
+    fn spec_tag_info(&self) -> SpecTagInfo {
+        SpecTagInfo {
+            spec_name: self.spec_name.clone(),
+            unique_spec_label: self.unique_spec_label.clone(),
+            tag: self.tag.clone(),
+        }
+    }
+
+    fn spec_key(&self) -> (String, Option<String>) {
+        (self.spec_name.clone(), self.unique_spec_label.clone())
+    }
+}
+
+/// Compiles a workflow document into a [`DefGraph`].
+pub fn load_workflow(source: &str, format: WorkflowFormat) -> Result<DefGraph, WorkflowError> {
+    let document = parse(source, format)?;
+    let line_of = |section: &str, index: usize| entry_line(source, format, section, index);
+    let at = |line: Option<usize>, message: String| WorkflowError {
+        line,
+        column: None,
+        message,
+    };
+
+    let mut declared = HashSet::new();
+    for (i, spec) in document.specs.iter().enumerate() {
+        let key = (spec.name.clone(), spec.label.clone());
+        if !declared.insert(key) {
+            return Err(at(
+                line_of("specs", i),
+                format!("spec `{}` is declared more than once", spec_display(spec)),
+            ));
+        }
+    }
+    let check_declared = |port: &PortRef, line: Option<usize>| {
+        if declared.contains(&port.spec_key()) {
+            Ok(())
+        } else {
+            Err(at(
+                line,
+                format!("`{}` refers to an undeclared spec", port.text),
+            ))
+        }
+    };
+
+    let mut graph = DefGraph::new(
+        document.root.name.clone(),
+        document.root.inputs.clone(),
+        document.root.outputs.clone(),
+    );
+
+    for (i, connection) in document.connections.iter().enumerate() {
+        let line = line_of("connections", i);
+        check_declared(&connection.from, line)?;
+        check_declared(&connection.to, line)?;
+        graph
+            .try_add_connected_dual_specs(
+                &FromSpecAndTag {
+                    spec_name: connection.from.spec_name.clone(),
+                    output: connection.from.tag.clone(),
+                    unique_spec_label: connection.from.unique_spec_label.clone(),
+                },
+                &ToSpecAndTag {
+                    spec_name: connection.to.spec_name.clone(),
+                    input: connection.to.tag.clone(),
+                    has_transform: connection.transform,
+                    unique_spec_label: connection.to.unique_spec_label.clone(),
+                },
+            )
+            .map_err(|e| at(line, e.to_string()))?;
+    }
+
+    for spec in &document.specs {
+        graph.ensure_spec_node(&spec.name, spec.label.as_deref());
+        let port = |tag: &String| SpecTagInfo {
+            spec_name: spec.name.clone(),
+            unique_spec_label: spec.label.clone(),
+            tag: tag.clone(),
+        };
+        for tag in &spec.inputs {
+            graph.ensure_port_node(&port(tag), PortDirection::In);
+        }
+        for tag in &spec.outputs {
+            graph.ensure_port_node(&port(tag), PortDirection::Out);
+        }
+    }
+
+    for (i, alias) in document.aliases.iter().enumerate() {
+        let line = line_of("aliases", i);
+        check_declared(&alias.port, line)?;
+        let port = alias.port.spec_tag_info();
+        graph
+            .try_assign_alias(
+                &alias.name,
+                &port.spec_name,
+                &document.root.name,
+                port.unique_spec_label.as_deref(),
+                alias.direction,
+                &port.tag,
+            )
+            .map_err(|e| at(line, e.to_string()))?;
+    }
+
+    Ok(graph)
+}
+
+fn spec_display(spec: &SpecDecl) -> String {
+    unique_spec_identifier(spec.name.clone(), spec.label.clone())
+}
+
+fn parse(source: &str, format: WorkflowFormat) -> Result<WorkflowDocument, WorkflowError> {
+    match format {
+        WorkflowFormat::Json => serde_json::from_str(source).map_err(|e| WorkflowError {
+            line: Some(e.line()).filter(|&line| line > 0),
+            column: Some(e.column()).filter(|&column| column > 0),
+            message: strip_position(&e.to_string()),
+        }),
+        WorkflowFormat::Yaml => serde_yaml::from_str(source).map_err(|e| {
+            let location = e.location();
+            WorkflowError {
+                line: location.as_ref().map(|l| l.line()),
+                column: location.as_ref().map(|l| l.column()),
+                message: strip_position(&e.to_string()),
+            }
+        }),
+        WorkflowFormat::Toml => toml::from_str(source).map_err(|e| {
+            let (line, column) = match e.span() {
+                Some(span) => {
+                    let (line, column) = position_of(source, span.start);
+                    (Some(line), Some(column))
+                }
+                None => (None, None),
+            };
+            WorkflowError {
+                line,
+                column,
+                message: e.message().to_string(),
+            }
+        }),
+    }
+}
+
+/// Drops the ` at line L column C` suffix the JSON and YAML parsers add, since the position
+/// is reported separately.
+fn strip_position(message: &str) -> String {
+    match message.rfind(" at line ") {
+        Some(index) => message[..index].to_string(),
+        None => message.to_string(),
+    }
+}
+
+/// 1-based line and column of a byte offset.
+fn position_of(source: &str, offset: usize) -> (usize, usize) {
+    let before = &source[..offset.min(source.len())];
+    let line = before.matches('\n').count() + 1;
+    let column = before.len() - before.rfind('\n').map_or(0, |i| i + 1) + 1;
+    (line, column)
+}
+
+/// Line where entry `index` of the top-level list `section` starts, as the parser reports
+/// it: the document is parsed again with a seed that rejects that entry, and the parser
+/// attaches its position to the rejection.
+fn entry_line(source: &str, format: WorkflowFormat, section: &str, index: usize) -> Option<usize> {
+    let seed = EntrySeed { section, index };
+    match format {
+        WorkflowFormat::Json => {
+            let err = seed
+                .deserialize(&mut serde_json::Deserializer::from_str(source))
+                .err()?;
+            Some(err.line()).filter(|&line| line > 0)
+        }
+        WorkflowFormat::Yaml => {
+            let err = seed
+                .deserialize(serde_yaml::Deserializer::from_str(source))
+                .err()?;
+            err.location().map(|l| l.line())
+        }
+        WorkflowFormat::Toml => {
+            let err = seed.deserialize(toml::Deserializer::new(source)).err()?;
+            err.span().map(|span| position_of(source, span.start).0)
+        }
+    }
+}
+
+/// Walks a workflow document down to `section[index]` and fails there. Succeeds, finding
+/// nothing, when the entry does not exist.
+#[derive(Clone, Copy)]
+struct EntrySeed<'a> {
+    section: &'a str,
+    index: usize,
+}
+
+impl<'de> DeserializeSeed<'de> for EntrySeed<'_> {
+    type Value = ();
+
+    fn deserialize<D: Deserializer<'de>>(self, deserializer: D) -> Result<(), D::Error> {
+        deserializer.deserialize_map(self)
+    }
+}
+
+impl<'de> Visitor<'de> for EntrySeed<'_> {
+    type Value = ();
+
+    fn expecting(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "a workflow document")
+    }
+
+    fn visit_map<A: MapAccess<'de>>(self, mut map: A) -> Result<(), A::Error> {
+        while let Some(key) = map.next_key::<String>()? {
+            if key == self.section {
+                map.next_value_seed(SectionSeed(self.index))?;
+            } else {
+                map.next_value::<IgnoredAny>()?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Skips `.0` entries of a list and fails on the next one.
+struct SectionSeed(usize);
+
+impl<'de> DeserializeSeed<'de> for SectionSeed {
+    type Value = ();
+
+    fn deserialize<D: Deserializer<'de>>(self, deserializer: D) -> Result<(), D::Error> {
+        deserializer.deserialize_seq(self)
+    }
+}
+
+impl<'de> Visitor<'de> for SectionSeed {
+    type Value = ();
+
+    fn expecting(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "a list of entries")
+    }
+
+    fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> Result<(), A::Error> {
+        for _ in 0..self.0 {
+            if seq.next_element::<IgnoredAny>()?.is_none() {
+                return Ok(());
+            }
+        }
+        seq.next_element_seed(RejectEntry)?;
+        while seq.next_element::<IgnoredAny>()?.is_some() {}
+        Ok(())
+    }
+}
+
+/// Rejects whatever entry it is given, once the parser has started reading it.
+struct RejectEntry;
+
+impl<'de> DeserializeSeed<'de> for RejectEntry {
+    type Value = ();
+
+    fn deserialize<D: Deserializer<'de>>(self, deserializer: D) -> Result<(), D::Error> {
+        deserializer.deserialize_any(self)
+    }
+}
+
+impl<'de> Visitor<'de> for RejectEntry {
+    type Value = ();
+
+    fn expecting(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "nothing")
+    }
+
+    fn visit_map<A: MapAccess<'de>>(self, _map: A) -> Result<(), A::Error> {
+        Err(de::Error::custom("entry located"))
+    }
+}
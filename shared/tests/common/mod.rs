@@ -0,0 +1,45 @@
+//! Helpers shared by the integration tests. Each test crate uses a subset of them.
+#![allow(dead_code)]
+
+use livestack_shared::systems::def_graph::{DefGraph, DefGraphError};
+use livestack_shared::systems::def_graph_utils::{FromSpecAndTag, SpecTagInfo, ToSpecAndTag};
+
+/// The port `tag` of the unlabelled instance of `spec_name`.
+pub fn port(spec_name: &str, tag: &str) -> SpecTagInfo {
+    labelled_port(spec_name, None, tag)
+}
+
+pub fn labelled_port(spec_name: &str, label: Option<&str>, tag: &str) -> SpecTagInfo {
+    SpecTagInfo {
+        spec_name: spec_name.to_string(),
+        tag: tag.to_string(),
+        unique_spec_label: label.map(str::to_string),
+    }
+}
+
+/// Connects `from`'s outlet `output` to `to`'s inlet `input`, unlabelled and without a transform.
+pub fn connect(graph: &mut DefGraph, from: &str, output: &str, to: &str, input: &str) {
+    connect_ports(graph, &port(from, output), &port(to, input), false).unwrap();
+}
+
+/// Connects two ports, returning what [`DefGraph::try_add_connected_dual_specs`] returns.
+pub fn connect_ports(
+    graph: &mut DefGraph,
+    from: &SpecTagInfo,
+    to: &SpecTagInfo,
+    has_transform: bool,
+) -> Result<(u32, u32, u32, u32, u32), DefGraphError> {
+    graph.try_add_connected_dual_specs(
+        &FromSpecAndTag {
+            spec_name: from.spec_name.clone(),
+            output: from.tag.clone(),
+            unique_spec_label: from.unique_spec_label.clone(),
+        },
+        &ToSpecAndTag {
+            spec_name: to.spec_name.clone(),
+            input: to.tag.clone(),
+            has_transform,
+            unique_spec_label: to.unique_spec_label.clone(),
+        },
+    )
+}
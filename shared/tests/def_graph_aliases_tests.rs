@@ -0,0 +1,120 @@
+mod common;
+
+use common::connect;
+use livestack_shared::systems::def_graph::{
+    try_load_from_json, DefGraph, DefGraphError, PortDirection,
+};
+use livestack_shared::systems::def_graph_aliases::AliasInfo;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A -> B, with A's input and B's output exposed as "request" and "reply".
+    fn exposed() -> DefGraph {
+        let mut graph = DefGraph::new("Root".to_string(), vec![], vec![]);
+        connect(&mut graph, "A", "out", "B", "in");
+        connect(&mut graph, "Source", "out", "A", "in");
+        connect(&mut graph, "B", "out", "Sink", "in");
+        graph.assign_alias("request", "A", "Root", None, PortDirection::In, "in");
+        graph.assign_alias("reply", "B", "Root", None, PortDirection::Out, "out");
+        graph
+    }
+
+    fn info(alias: &AliasInfo) -> (PortDirection, &str, &str, &str) {
+        (alias.direction, &alias.alias, &alias.spec_name, &alias.tag)
+    }
+
+    #[test]
+    fn aliases_lists_inputs_then_outputs_with_their_ports() {
+        let graph = exposed();
+
+        let aliases = graph.aliases();
+
+        assert_eq!(
+            aliases.iter().map(info).collect::<Vec<_>>(),
+            vec![
+                (PortDirection::In, "request", "A", "in"),
+                (PortDirection::Out, "reply", "B", "out"),
+            ]
+        );
+        assert_eq!(
+            graph.node_weight(aliases[0].alias_node_id).unwrap().alias,
+            Some("request".to_string())
+        );
+    }
+
+    #[test]
+    fn assigning_a_bound_alias_elsewhere_is_a_conflict() {
+        let mut graph = exposed();
+
+        let other_port =
+            graph.try_assign_alias("request", "B", "Root", None, PortDirection::In, "in");
+        let other_direction =
+            graph.try_assign_alias("request", "B", "Root", None, PortDirection::Out, "out");
+        let same_port =
+            graph.try_assign_alias("request", "A", "Root", None, PortDirection::In, "in");
+
+        let conflict = Err(DefGraphError::AliasConflict {
+            alias: "request".to_string(),
+            direction: PortDirection::In,
+            port: "A/in".to_string(),
+        });
+        assert_eq!(other_port, conflict);
+        assert_eq!(other_direction, conflict);
+        assert_eq!(same_port, Ok(()));
+        assert_eq!(graph.aliases().len(), 2);
+    }
+
+    #[test]
+    fn rename_alias_keeps_the_port_and_survives_a_round_trip() {
+        let mut graph = exposed();
+        let id = graph.aliases()[0].alias_node_id;
+
+        assert_eq!(
+            graph.rename_alias("request", PortDirection::In, "query"),
+            Ok(id)
+        );
+        assert_eq!(
+            graph.rename_alias("query", PortDirection::In, "query"),
+            Ok(id)
+        );
+
+        let reloaded = try_load_from_json(graph.to_json().unwrap()).unwrap();
+        assert_eq!(
+            reloaded
+                .lookup_spec_and_tag_by_alias("query".to_string(), PortDirection::In)
+                .map(|p| p.spec_name),
+            Some("A".to_string())
+        );
+        assert_eq!(
+            reloaded.lookup_spec_and_tag_by_alias("request".to_string(), PortDirection::In),
+            None
+        );
+        // The old name is free again.
+        graph.assign_alias("request", "B", "Root", None, PortDirection::In, "in");
+        assert_eq!(graph.aliases().len(), 3);
+    }
+
+    #[test]
+    fn rename_alias_rejects_missing_and_taken_names() {
+        let mut graph = exposed();
+
+        assert_eq!(
+            graph.rename_alias("request", PortDirection::Out, "query"),
+            Err(DefGraphError::AliasNotFound {
+                alias: "request".to_string(),
+                direction: PortDirection::Out,
+            })
+        );
+        assert_eq!(
+            graph.rename_alias("request", PortDirection::In, "reply"),
+            Err(DefGraphError::AliasConflict {
+                alias: "reply".to_string(),
+                direction: PortDirection::Out,
+                port: "B/out".to_string(),
+            })
+        );
+        assert!(graph.validate().is_empty(), "{:?}", graph.validate());
+    }
+}
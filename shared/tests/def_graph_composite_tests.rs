@@ -0,0 +1,281 @@
+mod common;
+
+use common::{connect, connect_ports, labelled_port, port};
+use livestack_shared::systems::def_graph::{
+    load_from_json, DefGraph, DefGraphError, DefGraphNodeType, PortDirection,
+};
+use livestack_shared::systems::instantiated_graph::InstantiatedGraph;
+use std::collections::HashMap;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// VAD -> ASR -> Punctuation, taking `audio` in and giving `text` out.
+    fn voice_pipeline() -> DefGraph {
+        let mut graph = DefGraph::new(
+            "VoicePipeline".to_string(),
+            vec!["audio".to_string()],
+            vec!["text".to_string()],
+        );
+        connect(&mut graph, "VAD", "speech", "ASR", "speech");
+        connect(&mut graph, "ASR", "raw", "Punctuation", "raw");
+        graph.ensure_inlet_and_stream(port("VAD", "audio"), false);
+        graph.ensure_outlet_and_stream(port("Punctuation", "text"));
+        graph.assign_alias("audio", "VAD", "VoicePipeline", None, PortDirection::In, "audio");
+        graph.assign_alias("text", "Punctuation", "VoicePipeline", None, PortDirection::Out, "text");
+        graph
+    }
+
+    fn spec_labels(graph: &DefGraph) -> Vec<String> {
+        let mut labels: Vec<String> = graph
+            .get_spec_node_ids()
+            .into_iter()
+            .map(|id| graph.node_weight(id).unwrap().label)
+            .collect();
+        labels.sort();
+        labels
+    }
+
+    /// Mic -> [VoicePipeline x2] -> Chat, with the first copy's text exposed as `transcript`.
+    fn assistant() -> DefGraph {
+        let mut graph = DefGraph::new("Assistant".to_string(), vec![], vec![]);
+        graph
+            .try_add_composite_spec(voice_pipeline(), Some("en"))
+            .unwrap();
+        graph
+            .try_add_composite_spec(voice_pipeline(), Some("fr"))
+            .unwrap();
+        for lang in ["en", "fr"] {
+            connect_ports(
+                &mut graph,
+                &port("Mic", "pcm"),
+                &labelled_port("VoicePipeline", Some(lang), "audio"),
+                false,
+            )
+            .unwrap();
+            connect_ports(
+                &mut graph,
+                &labelled_port("VoicePipeline", Some(lang), "text"),
+                &port("Chat", lang),
+                false,
+            )
+            .unwrap();
+        }
+        graph.assign_alias(
+            "transcript",
+            "VoicePipeline",
+            "Assistant",
+            Some("en"),
+            PortDirection::Out,
+            "text",
+        );
+        graph
+    }
+
+    #[test]
+    fn nested_view_shows_composite_as_one_spec() {
+        let graph = assistant();
+
+        assert_eq!(
+            spec_labels(&graph),
+            vec!["Chat", "Mic", "VoicePipeline[en]", "VoicePipeline[fr]"]
+        );
+        assert_eq!(
+            graph.get_composite_spec_identifiers(),
+            vec!["VoicePipeline[en]", "VoicePipeline[fr]"]
+        );
+        let child = graph.get_composite("VoicePipeline", Some("en")).unwrap();
+        assert_eq!(spec_labels(child), vec!["ASR", "Punctuation", "VAD"]);
+    }
+
+    #[test]
+    fn flattening_prefixes_inner_specs_with_composite_identifier() {
+        let flat = assistant().flatten().unwrap();
+
+        assert!(flat.get_composite_spec_identifiers().is_empty());
+        assert_eq!(
+            spec_labels(&flat),
+            vec![
+                "ASR[VoicePipeline\\[en\\]]",
+                "ASR[VoicePipeline\\[fr\\]]",
+                "Chat",
+                "Mic",
+                "Punctuation[VoicePipeline\\[en\\]]",
+                "Punctuation[VoicePipeline\\[fr\\]]",
+                "VAD[VoicePipeline\\[en\\]]",
+                "VAD[VoicePipeline\\[fr\\]]",
+            ]
+        );
+        assert_eq!(
+            flat.lookup_root_spec_alias(
+                "Punctuation".to_string(),
+                Some("VoicePipeline[en]".to_string()),
+                "text".to_string(),
+                PortDirection::Out
+            ),
+            Some("transcript".to_string())
+        );
+        assert!(flat.topological_spec_order().is_ok());
+        assert!(flat.validate().is_empty(), "{:?}", flat.validate());
+    }
+
+    #[test]
+    fn composites_sharing_a_name_or_label_keep_their_specs_apart() {
+        fn asr_only(root: &str) -> DefGraph {
+            let mut graph = DefGraph::new(root.to_string(), vec!["audio".to_string()], vec![]);
+            graph.ensure_inlet_and_stream(port("ASR", "audio"), false);
+            graph.assign_alias("audio", "ASR", root, None, PortDirection::In, "audio");
+            graph
+        }
+        let mut graph = DefGraph::new("Root".to_string(), vec![], vec![]);
+        graph
+            .try_add_composite_spec(asr_only("pipeA"), Some("voice"))
+            .unwrap();
+        graph
+            .try_add_composite_spec(asr_only("pipeB"), Some("voice"))
+            .unwrap();
+        graph.try_add_composite_spec(asr_only("voice"), None).unwrap();
+        graph
+            .try_add_composite_spec(asr_only("X"), Some("voice"))
+            .unwrap();
+
+        let flat = graph.flatten().unwrap();
+
+        assert_eq!(
+            spec_labels(&flat),
+            vec![
+                "ASR[X\\[voice\\]]",
+                "ASR[pipeA\\[voice\\]]",
+                "ASR[pipeB\\[voice\\]]",
+                "ASR[voice]",
+            ]
+        );
+    }
+
+    #[test]
+    fn nested_composites_get_label_paths() {
+        let mut middle = DefGraph::new("Middle".to_string(), vec![], vec![]);
+        middle
+            .try_add_composite_spec(voice_pipeline(), Some("inner"))
+            .unwrap();
+        connect_ports(
+            &mut middle,
+            &labelled_port("VoicePipeline", Some("inner"), "text"),
+            &port("Sink", "text"),
+            false,
+        )
+        .unwrap();
+        middle.assign_alias(
+            "audio",
+            "VoicePipeline",
+            "Middle",
+            Some("inner"),
+            PortDirection::In,
+            "audio",
+        );
+
+        let mut outer = DefGraph::new("Outer".to_string(), vec![], vec![]);
+        outer.try_add_composite_spec(middle, Some("outer")).unwrap();
+        connect_ports(
+            &mut outer,
+            &port("Mic", "pcm"),
+            &labelled_port("Middle", Some("outer"), "audio"),
+            false,
+        )
+        .unwrap();
+
+        let flat = outer.flatten().unwrap();
+
+        assert_eq!(
+            spec_labels(&flat),
+            vec![
+                "ASR[Middle\\[outer\\]\\/VoicePipeline\\[inner\\]]",
+                "Mic",
+                "Punctuation[Middle\\[outer\\]\\/VoicePipeline\\[inner\\]]",
+                "Sink[Middle\\[outer\\]]",
+                "VAD[Middle\\[outer\\]\\/VoicePipeline\\[inner\\]]",
+            ]
+        );
+        let path = "Middle[outer]/VoicePipeline[inner]";
+        let asr = flat.find_spec_node("ASR", Some(path)).unwrap();
+        assert_eq!(
+            flat.node_weight(asr).unwrap().unique_spec_label.as_deref(),
+            Some(path)
+        );
+        assert!(flat.validate().is_empty(), "{:?}", flat.validate());
+    }
+
+    #[test]
+    fn connecting_to_an_unknown_composite_port_fails() {
+        let mut graph = DefGraph::new("Assistant".to_string(), vec![], vec![]);
+        graph
+            .try_add_composite_spec(voice_pipeline(), None)
+            .unwrap();
+
+        assert_eq!(
+            connect_ports(
+                &mut graph,
+                &port("Mic", "pcm"),
+                &port("VoicePipeline", "video"),
+                false,
+            ),
+            Err(DefGraphError::PortNotFound {
+                spec_name: "VoicePipeline".to_string(),
+                tag: "video".to_string(),
+                direction: PortDirection::In,
+            })
+        );
+        assert_eq!(
+            graph.try_add_composite_spec(voice_pipeline(), None),
+            Err(DefGraphError::SpecAlreadyExists {
+                spec_identifier: "VoicePipeline".to_string(),
+            })
+        );
+    }
+
+    #[test]
+    fn composites_survive_json_round_trip() {
+        let graph = assistant();
+        let reloaded = load_from_json(graph.to_json().unwrap());
+
+        assert_eq!(
+            reloaded.get_composite_spec_identifiers(),
+            graph.get_composite_spec_identifiers()
+        );
+        assert_eq!(
+            spec_labels(&reloaded.flatten().unwrap()),
+            spec_labels(&graph.flatten().unwrap())
+        );
+    }
+
+    #[test]
+    fn instantiation_uses_flattened_view() {
+        let instantiated = InstantiatedGraph::new(
+            "ctx".to_string(),
+            "rootJob".to_string(),
+            HashMap::new(),
+            HashMap::new(),
+            HashMap::new(),
+            &assistant(),
+        );
+
+        let job_ids: Vec<String> = instantiated
+            .node_indices()
+            .into_iter()
+            .filter_map(|id| instantiated.node_weight(id).unwrap().job_id.clone())
+            .collect();
+        assert!(job_ids.contains(&"[ctx]ASR[VoicePipeline\\[en\\]]".to_string()));
+        assert!(job_ids.contains(&"[ctx]ASR[VoicePipeline\\[fr\\]]".to_string()));
+        assert!(!job_ids.iter().any(|id| id.starts_with("[ctx]VoicePipeline")));
+        let spec_count = instantiated
+            .def_graph
+            .node_indices()
+            .into_iter()
+            .filter(|&id| {
+                instantiated.def_graph.node_weight(id).unwrap().node_type == DefGraphNodeType::Spec
+            })
+            .count();
+        assert_eq!(spec_count, 8);
+    }
+}
@@ -0,0 +1,154 @@
+mod common;
+
+use common::{connect, connect_ports, port};
+use livestack_shared::systems::def_graph::{DefGraph, PortDirection};
+use livestack_shared::systems::def_graph_diff::{
+    AliasChange, AliasRetarget, ConnectionChange, InletTransformChange, PortChange, PortIdentity,
+};
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn port_identity(spec_identifier: &str, tag: &str) -> PortIdentity {
+        PortIdentity {
+            spec_identifier: spec_identifier.to_string(),
+            tag: tag.to_string(),
+        }
+    }
+
+    #[test]
+    fn identical_graphs_built_in_different_order_have_no_diff() {
+        let mut a = DefGraph::new("Root".to_string(), vec![], vec![]);
+        connect(&mut a, "A", "out", "B", "in");
+        connect_ports(&mut a, &port("B", "out"), &port("C", "in"), true).unwrap();
+        a.assign_alias("entry", "A", "Root", None, PortDirection::Out, "out");
+
+        let mut b = DefGraph::new("Root".to_string(), vec![], vec![]);
+        connect_ports(&mut b, &port("B", "out"), &port("C", "in"), true).unwrap();
+        connect(&mut b, "A", "out", "B", "in");
+        b.assign_alias("entry", "A", "Root", None, PortDirection::Out, "out");
+
+        let diff = a.diff(&b);
+        assert!(diff.is_empty(), "{:?}", diff);
+        assert_eq!(diff.to_string(), "");
+    }
+
+    #[test]
+    fn reports_added_and_removed_specs_and_streams() {
+        let mut before = DefGraph::new("Root".to_string(), vec![], vec![]);
+        connect(&mut before, "A", "out", "B", "in");
+        let mut after = DefGraph::new("Root".to_string(), vec![], vec![]);
+        connect(&mut after, "A", "out", "C", "in");
+
+        let diff = before.diff(&after);
+
+        assert_eq!(diff.added_specs, vec!["C".to_string()]);
+        assert_eq!(diff.removed_specs, vec!["B".to_string()]);
+        assert_eq!(diff.added_streams, vec!["A/out>>C/in".to_string()]);
+        assert_eq!(diff.removed_streams, vec!["A/out>>B/in".to_string()]);
+        assert_eq!(
+            diff.to_string(),
+            "+ spec C\n- spec B\n+ inlet C/in\n- inlet B/in\n\
+             + stream A/out>>C/in\n- stream A/out>>B/in\n\
+             + connection A/out -> C/in\n- connection A/out -> B/in\n"
+        );
+    }
+
+    #[test]
+    fn reports_connections_that_reuse_existing_specs_ports_and_streams() {
+        let mut before = DefGraph::new("Root".to_string(), vec![], vec![]);
+        connect(&mut before, "A", "out", "B", "in");
+        connect(&mut before, "A", "out", "C", "in");
+        connect(&mut before, "Y", "out", "C", "z");
+        let mut after = DefGraph::new("Root".to_string(), vec![], vec![]);
+        connect(&mut after, "A", "out", "B", "in");
+        connect(&mut after, "Y", "out", "C", "z");
+
+        let diff = before.diff(&after);
+
+        assert!(!diff.is_empty());
+        assert_eq!(
+            diff.removed_connections,
+            vec![ConnectionChange {
+                from: port_identity("A", "out"),
+                to: port_identity("C", "in"),
+            }]
+        );
+        assert_eq!(
+            diff.removed_ports,
+            vec![PortChange {
+                direction: PortDirection::In,
+                port: port_identity("C", "in"),
+            }]
+        );
+        assert!(diff.added_connections.is_empty() && diff.added_ports.is_empty());
+        assert!(diff.removed_specs.is_empty());
+    }
+
+    #[test]
+    fn reports_inlet_transform_changes() {
+        let mut before = DefGraph::new("Root".to_string(), vec![], vec![]);
+        connect(&mut before, "A", "out", "B", "in");
+        let mut after = DefGraph::new("Root".to_string(), vec![], vec![]);
+        connect_ports(&mut after, &port("A", "out"), &port("B", "in"), true).unwrap();
+
+        let diff = before.diff(&after);
+
+        assert_eq!(
+            diff.changed_inlet_transforms,
+            vec![InletTransformChange {
+                spec_identifier: "B".to_string(),
+                tag: "in".to_string(),
+                before: false,
+                after: true,
+            }]
+        );
+        assert!(diff.added_specs.is_empty() && diff.added_streams.is_empty());
+    }
+
+    #[test]
+    fn reports_added_removed_and_retargeted_aliases() {
+        let mut before = DefGraph::new("Root".to_string(), vec![], vec![]);
+        connect(&mut before, "A", "out", "B", "in");
+        before.assign_alias("result", "B", "Root", None, PortDirection::In, "in");
+        before.assign_alias("legacy", "A", "Root", None, PortDirection::Out, "out");
+
+        let mut after = DefGraph::new("Root".to_string(), vec![], vec![]);
+        connect(&mut after, "A", "out", "B", "in");
+        connect(&mut after, "B", "out", "C", "in");
+        after.assign_alias("result", "C", "Root", None, PortDirection::In, "in");
+        after.assign_alias("fresh", "B", "Root", None, PortDirection::Out, "out");
+
+        let diff = before.diff(&after);
+
+        assert_eq!(
+            diff.added_aliases,
+            vec![AliasChange {
+                alias: "fresh".to_string(),
+                direction: PortDirection::Out,
+                target: port_identity("B", "out"),
+            }]
+        );
+        assert_eq!(
+            diff.removed_aliases,
+            vec![AliasChange {
+                alias: "legacy".to_string(),
+                direction: PortDirection::Out,
+                target: port_identity("A", "out"),
+            }]
+        );
+        assert_eq!(
+            diff.retargeted_aliases,
+            vec![AliasRetarget {
+                alias: "result".to_string(),
+                direction: PortDirection::In,
+                before: port_identity("B", "in"),
+                after: port_identity("C", "in"),
+            }]
+        );
+        assert!(diff
+            .to_string()
+            .contains("~ alias in/result: B/in -> C/in\n"));
+    }
+}
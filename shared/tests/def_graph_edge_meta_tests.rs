@@ -0,0 +1,136 @@
+mod common;
+
+use common::port;
+use livestack_shared::systems::def_graph::{
+    load_from_json, DefGraph, DefGraphError, Durability, EdgeMeta,
+};
+use livestack_shared::systems::def_graph_utils::{FromSpecAndTag, ToSpecAndTag};
+use livestack_shared::systems::instantiated_graph::{InstantiatedGraph, InstantiatedNodeType};
+use std::collections::HashMap;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use assert_matches::assert_matches;
+
+    fn buffered_meta() -> EdgeMeta {
+        EdgeMeta {
+            transform: Some("lowercase".to_string()),
+            buffer_limit: Some(100),
+            durability: Some(Durability::Persistent),
+            partition_key: Some("userId".to_string()),
+            ..Default::default()
+        }
+    }
+
+    /// Recorder -> Transcriber, with settings on the connection.
+    fn graph_with_meta() -> (DefGraph, (u32, u32, u32, u32, u32)) {
+        let mut graph = DefGraph::new("Root".to_string(), vec![], vec![]);
+        let ids = graph
+            .try_add_connected_dual_specs_with_meta(
+                &FromSpecAndTag {
+                    spec_name: "Recorder".to_string(),
+                    output: "audio".to_string(),
+                    unique_spec_label: None,
+                },
+                &ToSpecAndTag {
+                    spec_name: "Transcriber".to_string(),
+                    input: "audio".to_string(),
+                    has_transform: true,
+                    unique_spec_label: None,
+                },
+                buffered_meta(),
+            )
+            .unwrap();
+        (graph, ids)
+    }
+
+    #[test]
+    fn meta_lives_on_the_connection_edge_only() {
+        let (graph, (from_spec, to_spec, stream, outlet, inlet)) = graph_with_meta();
+
+        assert_eq!(graph.edge_meta(stream, inlet), Some(&buffered_meta()));
+        assert_eq!(
+            graph.connection_meta(&port("Recorder", "audio"), &port("Transcriber", "audio")),
+            Ok(&buffered_meta())
+        );
+        for (from, to) in [(from_spec, outlet), (outlet, stream), (inlet, to_spec)] {
+            assert_eq!(graph.edge_meta(from, to), Some(&EdgeMeta::default()));
+        }
+    }
+
+    #[test]
+    fn set_connection_meta_replaces_settings() {
+        let (mut graph, _) = graph_with_meta();
+        let from = port("Recorder", "audio");
+        let to = port("Transcriber", "audio");
+        let meta = EdgeMeta {
+            durability: Some(Durability::Ephemeral),
+            ..EdgeMeta::default()
+        };
+
+        graph.set_connection_meta(&from, &to, meta.clone()).unwrap();
+
+        assert_eq!(graph.connection_meta(&from, &to), Ok(&meta));
+    }
+
+    #[test]
+    fn set_connection_meta_requires_a_connection() {
+        let (mut graph, _) = graph_with_meta();
+        graph.ensure_outlet_and_stream(port("Transcriber", "text"));
+        graph.ensure_inlet_and_stream(port("Recorder", "control"), false);
+
+        let err = graph
+            .set_connection_meta(
+                &port("Transcriber", "text"),
+                &port("Recorder", "control"),
+                buffered_meta(),
+            )
+            .unwrap_err();
+
+        assert_matches!(err, DefGraphError::NotConnected { .. });
+    }
+
+    #[test]
+    fn meta_survives_json_round_trip() {
+        let (graph, (_, _, stream, _, inlet)) = graph_with_meta();
+        let json = graph.to_json().unwrap();
+        let value: serde_json::Value = serde_json::from_str(&json).unwrap();
+        let edges = value["edges"].as_array().unwrap();
+        assert_eq!(edges.iter().filter(|e| e.get("meta").is_some()).count(), 1);
+
+        let reloaded = load_from_json(json);
+
+        assert_eq!(reloaded.edge_meta(stream, inlet), Some(&buffered_meta()));
+    }
+
+    #[test]
+    fn instantiation_carries_meta_into_the_inlet() {
+        let (graph, _) = graph_with_meta();
+
+        let instantiated = InstantiatedGraph::new(
+            "ctx".to_string(),
+            "rootJob".to_string(),
+            HashMap::new(),
+            HashMap::new(),
+            HashMap::new(),
+            &graph,
+        );
+
+        let inlet = instantiated
+            .node_indices()
+            .into_iter()
+            .find(|&id| {
+                instantiated.node_weight(id).unwrap().node_type == InstantiatedNodeType::Inlet
+            })
+            .unwrap();
+        let inbound = instantiated.inbound_edges(inlet);
+        assert_eq!(inbound.len(), 1);
+        assert_eq!(instantiated.edge_meta(inbound[0]), Some(&buffered_meta()));
+        let outbound = instantiated.outbound_edges(inlet);
+        assert_eq!(
+            instantiated.edge_meta(outbound[0]),
+            Some(&EdgeMeta::default())
+        );
+    }
+}
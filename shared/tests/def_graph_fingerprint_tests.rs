@@ -0,0 +1,101 @@
+mod common;
+
+use common::{connect, connect_ports, port};
+use livestack_shared::systems::def_graph::{load_from_json, DefGraph, PortDirection};
+use livestack_shared::systems::def_graph_utils::SpecTagInfo;
+use livestack_shared::systems::id_scheme::{HashedIds, IdScheme, ReadableIds};
+use std::sync::Arc;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Fetcher -> Parser -> Indexer, plus a standalone Auditor, with both ends
+    /// aliased.
+    fn pipeline(steps: &[(&str, &str)]) -> DefGraph {
+        let mut graph = DefGraph::new("Ingest".to_string(), vec![], vec![]);
+        for &(from, to) in steps {
+            connect(&mut graph, from, "out", to, "in");
+        }
+        graph.ensure_inlet_and_stream(port("Auditor", "in"), false);
+        graph.ensure_inlet_and_stream(port("Fetcher", "in"), false);
+        graph.ensure_outlet_and_stream(port("Indexer", "out"));
+        graph.assign_alias("url", "Fetcher", "Ingest", None, PortDirection::In, "in");
+        graph.assign_alias("indexed", "Indexer", "Ingest", None, PortDirection::Out, "out");
+        graph
+    }
+
+    #[test]
+    fn insertion_order_does_not_matter() {
+        let forward = pipeline(&[("Fetcher", "Parser"), ("Parser", "Indexer")]);
+        let backward = pipeline(&[("Parser", "Indexer"), ("Fetcher", "Parser")]);
+
+        assert_eq!(forward.fingerprint(), backward.fingerprint());
+        assert_eq!(forward.fingerprint().len(), 64);
+    }
+
+    #[test]
+    fn stream_def_ids_do_not_matter() {
+        // The fan-out stream is named after whichever target was connected first.
+        let fan_out = |targets: &[&str], id_scheme: Arc<dyn IdScheme>| {
+            let mut graph =
+                DefGraph::new_with_id_scheme("Ingest".to_string(), vec![], vec![], id_scheme);
+            connect(&mut graph, "Fetcher", "out", "Parser", "in");
+            for &target in targets {
+                connect(&mut graph, "Parser", "out", target, "in");
+            }
+            graph
+        };
+        let readable = fan_out(&["Indexer", "Auditor"], Arc::new(ReadableIds));
+        let reordered = fan_out(&["Auditor", "Indexer"], Arc::new(ReadableIds));
+        let hashed = fan_out(&["Auditor", "Indexer"], Arc::new(HashedIds::default()));
+
+        assert_eq!(readable.fingerprint(), reordered.fingerprint());
+        assert_eq!(readable.fingerprint(), hashed.fingerprint());
+    }
+
+    #[test]
+    fn fingerprint_survives_json_round_trip() {
+        let graph = pipeline(&[("Fetcher", "Parser"), ("Parser", "Indexer")]);
+
+        let reloaded = load_from_json(graph.to_json().unwrap());
+
+        assert_eq!(reloaded.fingerprint(), graph.fingerprint());
+    }
+
+    #[test]
+    fn semantic_changes_change_the_fingerprint() {
+        let steps = [("Fetcher", "Parser"), ("Parser", "Indexer")];
+        let base = pipeline(&steps).fingerprint();
+
+        let mut transformed = DefGraph::new("Ingest".to_string(), vec![], vec![]);
+        connect_ports(
+            &mut transformed,
+            &port("Fetcher", "out"),
+            &port("Parser", "in"),
+            true,
+        )
+        .unwrap();
+        connect(&mut transformed, "Parser", "out", "Indexer", "in");
+        transformed.ensure_inlet_and_stream(port("Auditor", "in"), false);
+        transformed.ensure_inlet_and_stream(port("Fetcher", "in"), false);
+        transformed.ensure_outlet_and_stream(port("Indexer", "out"));
+        transformed.assign_alias("url", "Fetcher", "Ingest", None, PortDirection::In, "in");
+        transformed.assign_alias("indexed", "Indexer", "Ingest", None, PortDirection::Out, "out");
+        assert_ne!(transformed.fingerprint(), base);
+
+        let mut realiased = pipeline(&steps);
+        realiased.assign_alias("raw", "Parser", "Ingest", None, PortDirection::In, "in");
+        assert_ne!(realiased.fingerprint(), base);
+
+        let mut relabelled = pipeline(&steps);
+        relabelled.ensure_inlet_and_stream(
+            SpecTagInfo {
+                unique_spec_label: Some("nightly".to_string()),
+                ..port("Auditor", "in")
+            },
+            false,
+        );
+        assert_ne!(relabelled.fingerprint(), base);
+    }
+}
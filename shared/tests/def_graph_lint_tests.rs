@@ -0,0 +1,137 @@
+mod common;
+
+use common::{connect, port};
+use livestack_shared::systems::def_graph::{DefGraph, PortDirection};
+use livestack_shared::systems::def_graph_lint::{DefGraphLint, LintCode, LintSeverity};
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// `request` -> A -> B -> `reply`, with every port accounted for.
+    fn clean(root_inputs: &[&str]) -> DefGraph {
+        let mut graph = DefGraph::new(
+            "Root".to_string(),
+            root_inputs.iter().map(|tag| tag.to_string()).collect(),
+            vec!["reply".to_string()],
+        );
+        connect(&mut graph, "A", "out", "B", "in");
+        graph.ensure_inlet_and_stream(port("A", "in"), false);
+        graph.ensure_outlet_and_stream(port("B", "out"));
+        graph.assign_alias("request", "A", "Root", None, PortDirection::In, "in");
+        graph.assign_alias("reply", "B", "Root", None, PortDirection::Out, "out");
+        graph
+    }
+
+    fn codes(lints: &[DefGraphLint]) -> Vec<(LintCode, String)> {
+        lints
+            .iter()
+            .map(|lint| (lint.code, lint.message.clone()))
+            .collect()
+    }
+
+    #[test]
+    fn a_fully_wired_graph_has_no_lints() {
+        let graph = clean(&["request"]);
+
+        assert!(graph.validate().is_empty(), "{:?}", graph.validate());
+        assert_eq!(graph.lint(), vec![]);
+    }
+
+    #[test]
+    fn reports_each_silent_disconnection() {
+        let mut graph = clean(&["request", "extra"]);
+        graph.ensure_outlet_and_stream(port("A", "debug"));
+        graph.ensure_inlet_and_stream(port("Lonely", "in"), true);
+        graph.assign_alias("direct", "B", "Root", None, PortDirection::In, "in");
+
+        let lints = graph.lint();
+
+        let mut found = codes(&lints);
+        found.sort();
+        assert_eq!(
+            found,
+            vec![
+                (
+                    LintCode::UnconnectedOutlet,
+                    "Outlet A/debug feeds nothing".to_string()
+                ),
+                (
+                    LintCode::UnusedRootInput,
+                    "Root input extra is not aliased to any spec input".to_string()
+                ),
+                (
+                    LintCode::UnreachableSpec,
+                    "Spec Lonely is not reached by any root input".to_string()
+                ),
+                (
+                    LintCode::DeadEndSpec,
+                    "Spec Lonely does not reach any root output".to_string()
+                ),
+                (
+                    LintCode::AliasOnInternalStream,
+                    "Input alias direct points at B/in, which another spec already feeds"
+                        .to_string()
+                ),
+                (
+                    LintCode::TransformWithoutUpstream,
+                    "Inlet Lonely/in expects a transform but nothing feeds it".to_string()
+                ),
+            ]
+        );
+        assert!(lints.windows(2).all(|w| w[0].node_id <= w[1].node_id));
+        assert!(graph.validate().is_empty(), "{:?}", graph.validate());
+    }
+
+    #[test]
+    fn reachability_lints_need_root_aliases_in_their_direction() {
+        let mut graph = DefGraph::new("Root".to_string(), vec![], vec![]);
+        connect(&mut graph, "A", "out", "B", "in");
+        connect(&mut graph, "C", "out", "D", "in");
+        assert_eq!(graph.lint(), vec![]);
+
+        graph.ensure_outlet_and_stream(port("B", "out"));
+        graph.assign_alias("reply", "B", "Root", None, PortDirection::Out, "out");
+
+        let dead_ends: Vec<u32> = graph
+            .lint()
+            .iter()
+            .filter(|lint| lint.code == LintCode::DeadEndSpec)
+            .map(|lint| lint.node_id)
+            .collect();
+        assert_eq!(
+            dead_ends,
+            vec![
+                graph.find_spec_node("C", None).unwrap(),
+                graph.find_spec_node("D", None).unwrap()
+            ]
+        );
+        assert!(graph
+            .lint()
+            .iter()
+            .all(|lint| lint.code != LintCode::UnreachableSpec));
+    }
+
+    #[test]
+    fn codes_and_severities_serialize_as_stable_names() {
+        let mut graph = clean(&["request"]);
+        graph.ensure_outlet_and_stream(port("A", "debug"));
+        let lint = graph.lint().remove(0);
+
+        assert_eq!(lint.severity, LintSeverity::Warning);
+        assert_eq!(LintCode::DeadEndSpec.severity(), LintSeverity::Info);
+        assert_eq!(
+            serde_json::to_value(&lint).unwrap(),
+            serde_json::json!({
+                "code": "unconnected-outlet",
+                "severity": "warning",
+                "nodeId": lint.node_id,
+                "message": "Outlet A/debug feeds nothing",
+            })
+        );
+        assert_eq!(
+            LintCode::AliasOnInternalStream.to_string(),
+            "alias-on-internal-stream"
+        );
+    }
+}
@@ -0,0 +1,157 @@
+mod common;
+
+use common::connect;
+use livestack_shared::systems::def_graph::{
+    load_from_json, DefGraph, DefGraphError, DefGraphNodeType, MergePolicy, PortDirection,
+};
+use livestack_shared::systems::def_graph_utils::SpecTagInfo;
+use livestack_shared::systems::def_graph_validation::DefGraphDiagnostic;
+use livestack_shared::systems::instantiated_graph::{InstantiatedGraph, InstantiatedNodeType};
+use std::collections::HashMap;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn summarizer_input() -> SpecTagInfo {
+        SpecTagInfo {
+            spec_name: "Summarizer".to_string(),
+            unique_spec_label: None,
+            tag: "text".to_string(),
+        }
+    }
+
+    fn spec_id(graph: &DefGraph, spec_name: &str) -> u32 {
+        graph
+            .find_node(|n| {
+                n.node_type == DefGraphNodeType::Spec && n.spec_name.as_deref() == Some(spec_name)
+            })
+            .unwrap()
+    }
+
+    /// Three transcribers all feeding the summarizer's single `text` input.
+    fn fan_in(policy: Option<MergePolicy>) -> DefGraph {
+        let mut graph = DefGraph::new("Meeting".to_string(), vec![], vec![]);
+        for transcriber in ["TranscriberA", "TranscriberB", "TranscriberC"] {
+            connect(&mut graph, transcriber, "transcript", "Summarizer", "text");
+        }
+        graph
+            .set_inlet_merge_policy(&summarizer_input(), policy)
+            .unwrap();
+        graph
+    }
+
+    #[test]
+    fn inbound_stream_nodes_lists_every_producer() {
+        let graph = fan_in(Some(MergePolicy::Interleave));
+
+        let inbound = graph
+            .get_inbound_stream_nodes(spec_id(&graph, "Summarizer"))
+            .unwrap();
+
+        assert_eq!(inbound.len(), 3);
+        let inlet = inbound[0].0;
+        assert!(inbound.iter().all(|&(i, _)| i == inlet));
+        let mut stream_def_ids: Vec<String> = inbound
+            .iter()
+            .map(|&(_, s)| graph.node_weight(s).unwrap().stream_def_id.unwrap())
+            .collect();
+        stream_def_ids.sort();
+        assert_eq!(
+            stream_def_ids,
+            vec![
+                "TranscriberA/transcript>>Summarizer/text",
+                "TranscriberB/transcript>>Summarizer/text",
+                "TranscriberC/transcript>>Summarizer/text"
+            ]
+        );
+        assert_eq!(
+            graph.node_weight(inlet).unwrap().merge_policy,
+            Some(MergePolicy::Interleave)
+        );
+    }
+
+    #[test]
+    fn declared_merge_policy_silences_multiple_stream_diagnostic() {
+        let undeclared = fan_in(None);
+        assert!(undeclared
+            .validate()
+            .iter()
+            .any(|d| matches!(d, DefGraphDiagnostic::InletHasMultipleStreamDefs { .. })));
+
+        let declared = fan_in(Some(MergePolicy::RoundRobin));
+        assert!(declared.validate().is_empty(), "{:?}", declared.validate());
+    }
+
+    #[test]
+    fn merge_policy_reaches_instantiated_inlet() {
+        let policy = MergePolicy::ZipByKey {
+            key: "speakerTurn".to_string(),
+        };
+        let graph = fan_in(Some(policy.clone()));
+
+        let instantiated = InstantiatedGraph::new(
+            "ctx".to_string(),
+            "rootJob".to_string(),
+            HashMap::new(),
+            HashMap::new(),
+            HashMap::new(),
+            &graph,
+        );
+
+        let policies: Vec<_> = instantiated
+            .node_indices()
+            .into_iter()
+            .filter_map(|id| instantiated.node_weight(id))
+            .filter(|n| n.node_type == InstantiatedNodeType::Inlet)
+            .map(|n| n.merge_policy.clone())
+            .collect();
+        assert_eq!(policies, vec![Some(policy)]);
+    }
+
+    #[test]
+    fn merge_policy_survives_json_round_trip() {
+        let graph = fan_in(Some(MergePolicy::ZipByKey {
+            key: "id".to_string(),
+        }));
+        let json = graph.to_json().unwrap();
+        assert!(json.contains(r#""mergePolicy":{"key":"id","kind":"zipByKey"}"#));
+
+        let reloaded = load_from_json(json);
+
+        let inlet = reloaded
+            .get_inbound_stream_nodes(spec_id(&reloaded, "Summarizer"))
+            .unwrap()[0]
+            .0;
+        assert_eq!(
+            reloaded.node_weight(inlet).unwrap().merge_policy,
+            Some(MergePolicy::ZipByKey {
+                key: "id".to_string()
+            })
+        );
+    }
+
+    #[test]
+    fn setting_policy_on_unknown_inlet_fails() {
+        let mut graph = fan_in(None);
+
+        let err = graph
+            .set_inlet_merge_policy(
+                &SpecTagInfo {
+                    tag: "audio".to_string(),
+                    ..summarizer_input()
+                },
+                Some(MergePolicy::Interleave),
+            )
+            .unwrap_err();
+
+        assert_eq!(
+            err,
+            DefGraphError::PortNotFound {
+                spec_name: "Summarizer".to_string(),
+                tag: "audio".to_string(),
+                direction: PortDirection::In,
+            }
+        );
+    }
+}
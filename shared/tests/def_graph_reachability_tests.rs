@@ -0,0 +1,151 @@
+mod common;
+
+use common::connect;
+use livestack_shared::systems::def_graph::{DefGraph, DefGraphError, DefGraphNodeType, PortDirection};
+use livestack_shared::systems::def_graph_reachability::{Reachable, ReachedAlias};
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn spec_id(graph: &DefGraph, spec_name: &str) -> u32 {
+        graph
+            .find_node(|n| {
+                n.node_type == DefGraphNodeType::Spec && n.spec_name.as_deref() == Some(spec_name)
+            })
+            .unwrap()
+    }
+
+    fn spec_names(reachable: &Reachable) -> Vec<&str> {
+        let mut names: Vec<&str> = reachable
+            .specs
+            .iter()
+            .map(|s| s.spec_identifier.as_str())
+            .collect();
+        names.sort();
+        names
+    }
+
+    /// Transcriber feeds both Translator and Summarizer; Translator also feeds Summarizer,
+    /// whose output is exposed as the root's `summary`. Logger hangs off the transcriber.
+    fn pipeline() -> DefGraph {
+        let mut graph = DefGraph::new("Meeting".to_string(), vec![], vec![]);
+        connect(&mut graph, "Transcriber", "text", "Translator", "text");
+        connect(&mut graph, "Translator", "text", "Summarizer", "translated");
+        connect(&mut graph, "Transcriber", "text", "Summarizer", "original");
+        connect(&mut graph, "Summarizer", "summary", "Publisher", "summary");
+        connect(&mut graph, "Transcriber", "raw", "Logger", "line");
+        graph.assign_alias("summary", "Summarizer", "Meeting", None, PortDirection::Out, "summary");
+        graph
+    }
+
+    #[test]
+    fn downstream_of_a_spec_follows_every_hop() {
+        let graph = pipeline();
+
+        let affected = graph.downstream_of(spec_id(&graph, "Translator")).unwrap();
+
+        assert_eq!(spec_names(&affected), vec!["Publisher", "Summarizer"]);
+        let mut streams: Vec<&str> = affected
+            .streams
+            .iter()
+            .map(|s| s.stream_def_id.as_str())
+            .collect();
+        streams.sort();
+        assert_eq!(
+            streams,
+            vec![
+                "Summarizer/summary>>Publisher/summary",
+                "Translator/text>>Summarizer/translated"
+            ]
+        );
+        assert_eq!(
+            affected.aliases,
+            vec![ReachedAlias {
+                alias: "summary".to_string(),
+                direction: PortDirection::Out,
+                spec_node_id: spec_id(&graph, "Summarizer"),
+                tag: "summary".to_string(),
+            }]
+        );
+    }
+
+    #[test]
+    fn upstream_of_a_stream_stops_at_sources() {
+        let graph = pipeline();
+        let (_, stream) = graph.get_outbound_stream_nodes(spec_id(&graph, "Summarizer")).unwrap()[0];
+
+        let sources = graph.upstream_of(stream).unwrap();
+
+        assert_eq!(
+            spec_names(&sources),
+            vec!["Summarizer", "Transcriber", "Translator"]
+        );
+        assert_eq!(sources.streams.len(), 2);
+        assert!(sources.aliases.is_empty());
+    }
+
+    #[test]
+    fn loops_lead_back_to_the_start() {
+        let mut graph = DefGraph::new("Agent".to_string(), vec![], vec![]);
+        connect(&mut graph, "Planner", "plan", "Actor", "plan");
+        connect(&mut graph, "Actor", "result", "Planner", "feedback");
+
+        let reachable = graph.downstream_of(spec_id(&graph, "Planner")).unwrap();
+
+        assert_eq!(spec_names(&reachable), vec!["Actor", "Planner"]);
+    }
+
+    #[test]
+    fn spec_paths_lists_every_route() {
+        let graph = pipeline();
+
+        let paths = graph
+            .spec_paths(spec_id(&graph, "Transcriber"), spec_id(&graph, "Publisher"))
+            .unwrap();
+
+        let routes: Vec<Vec<&str>> = paths
+            .iter()
+            .map(|path| path.iter().map(|c| c.stream_def_id.as_str()).collect())
+            .collect();
+        assert_eq!(
+            routes,
+            vec![
+                vec![
+                    "Transcriber/text>>Translator/text",
+                    "Summarizer/summary>>Publisher/summary"
+                ],
+                vec![
+                    "Transcriber/text>>Translator/text",
+                    "Translator/text>>Summarizer/translated",
+                    "Summarizer/summary>>Publisher/summary"
+                ],
+            ]
+        );
+        assert!(graph
+            .spec_paths(spec_id(&graph, "Logger"), spec_id(&graph, "Publisher"))
+            .unwrap()
+            .is_empty());
+    }
+
+    #[test]
+    fn queries_reject_other_node_types() {
+        let graph = pipeline();
+        let root = graph.get_root_spec_node_id().unwrap();
+
+        assert_eq!(
+            graph.downstream_of(root),
+            Err(DefGraphError::UnexpectedNodeType {
+                node_id: root,
+                expected: "spec or stream def".to_string(),
+            })
+        );
+        assert_eq!(
+            graph.spec_paths(root, 9999),
+            Err(DefGraphError::UnexpectedNodeType {
+                node_id: root,
+                expected: "spec".to_string(),
+            })
+        );
+    }
+}
@@ -0,0 +1,180 @@
+mod common;
+
+use common::{connect, port};
+use livestack_shared::systems::def_graph::{
+    load_from_json, DefGraph, DefGraphError, DefGraphNodeType, PortDirection,
+};
+use livestack_shared::systems::def_graph_validation::DefGraphDiagnostic;
+use livestack_shared::systems::instantiated_graph::InstantiatedGraph;
+use std::collections::HashMap;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn spec_id(graph: &DefGraph, spec_name: &str) -> Option<u32> {
+        graph.find_node(|n| {
+            n.node_type == DefGraphNodeType::Spec && n.spec_name.as_deref() == Some(spec_name)
+        })
+    }
+
+    /// A -> B -> C, with B's input exposed as the root alias "publicB".
+    fn pipeline() -> DefGraph {
+        let mut graph = DefGraph::new("Root".to_string(), vec![], vec![]);
+        connect(&mut graph, "A", "out", "B", "in");
+        connect(&mut graph, "B", "out", "C", "in");
+        graph.assign_alias("publicB", "B", "Root", None, PortDirection::In, "in");
+        graph
+    }
+
+    #[test]
+    fn remove_spec_keeps_ids_of_remaining_nodes() {
+        let mut graph = pipeline();
+        let a = spec_id(&graph, "A").unwrap();
+        let c = spec_id(&graph, "C").unwrap();
+        let c_label = graph.node_weight(c).unwrap().label;
+
+        let removed = graph.remove_spec("B", None).unwrap();
+
+        assert!(spec_id(&graph, "B").is_none());
+        assert!(!removed.contains(&a) && !removed.contains(&c));
+        assert_eq!(spec_id(&graph, "A"), Some(a));
+        assert_eq!(graph.node_weight(c).unwrap().label, c_label);
+        assert!(graph.get_all_alias_node_ids().is_empty());
+        // Streams still attached to A's outlet or C's inlet stay behind.
+        assert!(graph.validate().is_empty(), "{:?}", graph.validate());
+    }
+
+    #[test]
+    fn removal_survives_json_round_trip_with_holes() {
+        let mut graph = pipeline();
+        graph.remove_spec("A", None).unwrap();
+        let c = spec_id(&graph, "C").unwrap();
+
+        let reloaded = load_from_json(graph.to_json().unwrap());
+
+        assert_eq!(spec_id(&reloaded, "C"), Some(c));
+        assert_eq!(reloaded.node_count(), graph.node_count());
+        assert!(reloaded.validate().is_empty(), "{:?}", reloaded.validate());
+    }
+
+    #[test]
+    fn disconnect_prunes_ports_and_stream_but_keeps_specs() {
+        let mut graph = pipeline();
+        let before = graph.node_count();
+
+        let removed = graph
+            .disconnect(&port("B", "out"), &port("C", "in"))
+            .unwrap();
+
+        assert_eq!(removed.len(), 3, "inlet, stream and outlet");
+        assert_eq!(graph.node_count(), before - 3);
+        assert!(spec_id(&graph, "B").is_some());
+        assert!(spec_id(&graph, "C").is_some());
+        assert_matches::assert_matches!(
+            graph.disconnect(&port("B", "out"), &port("C", "in")),
+            Err(DefGraphError::PortNotFound { .. })
+        );
+    }
+
+    #[test]
+    fn disconnect_keeps_aliased_inlet() {
+        let mut graph = pipeline();
+        let a = spec_id(&graph, "A").unwrap();
+
+        graph
+            .disconnect(&port("A", "out"), &port("B", "in"))
+            .unwrap();
+
+        assert_eq!(
+            graph.lookup_root_spec_alias("B".to_string(), None, "in".to_string(), PortDirection::In),
+            Some("publicB".to_string())
+        );
+        // A is kept even though nothing connects to it anymore.
+        assert_eq!(
+            graph.validate(),
+            vec![DefGraphDiagnostic::OrphanedNode { node_id: a }]
+        );
+    }
+
+    #[test]
+    fn disconnected_aliased_inlet_keeps_an_unconnected_stream() {
+        let mut graph = DefGraph::new("Root".to_string(), vec![], vec![]);
+        connect(&mut graph, "A", "out", "B", "in");
+        graph.assign_alias("q", "B", "Root", None, PortDirection::In, "in");
+
+        graph
+            .disconnect(&port("A", "out"), &port("B", "in"))
+            .unwrap();
+
+        let b = spec_id(&graph, "B").unwrap();
+        let inbound = graph.get_inbound_stream_nodes(b).unwrap();
+        assert_eq!(inbound.len(), 1);
+        let stream = graph.node_weight(inbound[0].1).unwrap();
+        assert_eq!(stream.stream_def_id.as_deref(), Some("(*)>>B/in"));
+        assert_eq!(
+            graph.lookup_root_spec_alias("B".to_string(), None, "in".to_string(), PortDirection::In),
+            Some("q".to_string())
+        );
+    }
+
+    #[test]
+    fn disconnect_unconnected_ports_is_an_error() {
+        let mut graph = pipeline();
+        connect(&mut graph, "A", "extra", "D", "in");
+
+        assert_matches::assert_matches!(
+            graph.disconnect(&port("A", "extra"), &port("B", "in")),
+            Err(DefGraphError::NotConnected { .. })
+        );
+    }
+
+    #[test]
+    fn remove_alias_by_name_and_direction() {
+        let mut graph = pipeline();
+
+        assert_matches::assert_matches!(
+            graph.remove_alias("publicB", PortDirection::Out),
+            Err(DefGraphError::AliasNotFound { .. })
+        );
+        graph.remove_alias("publicB", PortDirection::In).unwrap();
+
+        assert!(graph.get_all_alias_node_ids().is_empty());
+        assert!(graph.validate().is_empty(), "{:?}", graph.validate());
+    }
+
+    #[test]
+    fn instantiates_after_removal() {
+        let mut graph = pipeline();
+        graph.remove_spec("A", None).unwrap();
+
+        let instantiated = InstantiatedGraph::new(
+            "ctx".to_string(),
+            "rootJob".to_string(),
+            HashMap::new(),
+            HashMap::new(),
+            HashMap::new(),
+            &graph,
+        );
+
+        assert_eq!(instantiated.edge_count(), graph.edge_count());
+    }
+
+    #[test]
+    fn loads_graph_saved_before_stable_ids() {
+        let graph =
+            load_from_json(include_str!("fixtures/def_graph_digraph_format.json").to_string());
+
+        assert!(graph.validate().is_empty(), "{:?}", graph.validate());
+        assert_eq!(spec_id(&graph, "SpecB"), Some(9));
+        assert_eq!(
+            graph.lookup_root_spec_alias(
+                "SpecB".to_string(),
+                None,
+                "b".to_string(),
+                PortDirection::In
+            ),
+            Some("publicA".to_string())
+        );
+    }
+}
@@ -1,4 +1,4 @@
-use livestack_shared::systems::def_graph::{DefGraph, DefGraphNode, DefGraphNodeType,load_from_json};
+use livestack_shared::systems::def_graph::{DefGraph, DefGraphNode, DefGraphNodeType, PortDirection, load_from_json};
 
 #[cfg(test)]
 mod tests {
@@ -45,7 +45,7 @@ mod tests {
             })
             .expect("Spec node should exist");
 
-        let inbound_node_sets = graph.get_inbound_stream_nodes(spec_node_id);
+        let inbound_node_sets = graph.get_inbound_stream_nodes(spec_node_id).unwrap();
 
         // Ensure that the correct inlet and stream nodes are included
         assert_eq!(inbound_node_sets.len(), 2);
@@ -116,6 +116,7 @@ mod tests {
             alias: None,
             direction: None,
             label: "SpecA".to_string(),
+            ..Default::default()
         };
         let spec_node_id = graph.ensure_node("SpecA", spec_node_data);
 
@@ -130,6 +131,7 @@ mod tests {
             alias: None,
             direction: None,
             label: "StreamA".to_string(),
+            ..Default::default()
         };
         let non_spec_node_id = graph.ensure_node("StreamA", non_spec_node_data);
 
@@ -157,6 +159,7 @@ mod tests {
             alias: None,
             direction: None,
             label: "TestNode".to_string(),
+            ..Default::default()
         };
         let node_id = graph.ensure_node("TestNode", test_node.clone());
         
@@ -247,6 +250,7 @@ mod tests {
             alias: None,
             direction: None,
             label: "FromNode".to_string(),
+            ..Default::default()
         };
         let to_node = DefGraphNode {
             node_type: DefGraphNodeType::Spec,
@@ -258,6 +262,7 @@ mod tests {
             alias: None,
             direction: None,
             label: "ToNode".to_string(),
+            ..Default::default()
         };
         let from_index = graph.ensure_node("FromNode", from_node);
         let to_index = graph.ensure_node("ToNode", to_node);
@@ -346,7 +351,7 @@ mod tests {
             })
             .expect("Spec node should exist");
 
-        let outbound_node_sets = graph.get_outbound_stream_nodes(spec_node_id);
+        let outbound_node_sets = graph.get_outbound_stream_nodes(spec_node_id).unwrap();
 
         // Ensure that the correct outlet and stream nodes are included
         assert_eq!(outbound_node_sets.len(), 1);
@@ -363,7 +368,7 @@ mod tests {
         let spec_name = "SpecA";
         let alias = "AliasA";
         let tag = "input1";
-        let type_ = "in";
+        let type_ = PortDirection::In;
 
         // Ensure inlet and stream nodes are created for SpecA
         graph.ensure_inlet_and_stream(
@@ -382,7 +387,7 @@ mod tests {
             spec_name.to_string(),
             None,
              tag.to_string(), 
-             type_.to_string()
+             type_
         );
 
         assert_eq!(found_alias, Some(alias.to_string()));
@@ -454,7 +459,7 @@ mod tests {
         let spec_name = "SpecA";
         let alias = "AliasA";
         let tag = "input1";
-        let direction = "in";
+        let direction = PortDirection::In;
 
         // Ensure inlet and stream nodes are created for SpecA
         graph.ensure_inlet_and_stream(SpecTagInfo{
@@ -497,6 +502,7 @@ mod tests {
             alias: None,
             direction: None,
             label: "SpecA".to_string(),
+            ..Default::default()
         };
         let spec_node_a_id = graph.ensure_node("SpecA", spec_node_a);
 
@@ -510,6 +516,7 @@ mod tests {
             alias: None,
             direction: None,
             label: "SpecB".to_string(),
+            ..Default::default()
         };
         let spec_node_b_id = graph.ensure_node("SpecB", spec_node_b);
 
@@ -617,6 +624,7 @@ mod stream_connections_tests {
             alias: None,
             direction: None,
             label: "Spec1".to_string(),
+            ..Default::default()
         };
         let spec_node_id = graph.ensure_node("Spec1", spec_node);
         
@@ -631,6 +639,7 @@ mod stream_connections_tests {
             alias: None,
             direction: None,
             label: "Spec1/out".to_string(),
+            ..Default::default()
         };
         let outlet_node_id = graph.ensure_node("Spec1/out", outlet_node);
         
@@ -645,6 +654,7 @@ mod stream_connections_tests {
             alias: None,
             direction: None,
             label: "stream1".to_string(),
+            ..Default::default()
         };
         let stream_node_id = graph.ensure_node("stream1", stream_node);
         
@@ -679,6 +689,7 @@ mod stream_connections_tests {
             alias: None,
             direction: None,
             label: "Spec2".to_string(),
+            ..Default::default()
         };
         let spec_node_id = graph.ensure_node("Spec2", spec_node);
         
@@ -693,6 +704,7 @@ mod stream_connections_tests {
             alias: None,
             direction: None,
             label: "Spec2/in".to_string(),
+            ..Default::default()
         };
         let inlet_node_id = graph.ensure_node("Spec2/in", inlet_node);
         
@@ -707,6 +719,7 @@ mod stream_connections_tests {
             alias: None,
             direction: None,
             label: "stream2".to_string(),
+            ..Default::default()
         };
         let stream_node_id = graph.ensure_node("stream2", stream_node);
         
@@ -739,6 +752,7 @@ mod stream_connections_tests {
             alias: None,
             direction: None,
             label: "Spec1".to_string(),
+            ..Default::default()
         };
         let spec1_id = graph.ensure_node("Spec1", spec1);
         let outlet = DefGraphNode {
@@ -751,6 +765,7 @@ mod stream_connections_tests {
             alias: None,
             direction: None,
             label: "Spec1/out".to_string(),
+            ..Default::default()
         };
         let outlet_id = graph.ensure_node("Spec1/out", outlet);
         
@@ -765,6 +780,7 @@ mod stream_connections_tests {
             alias: None,
             direction: None,
             label: "Spec2".to_string(),
+            ..Default::default()
         };
         let spec2_id = graph.ensure_node("Spec2", spec2);
         let inlet = DefGraphNode {
@@ -777,6 +793,7 @@ mod stream_connections_tests {
             alias: None,
             direction: None,
             label: "Spec2/in".to_string(),
+            ..Default::default()
         };
         let inlet_id = graph.ensure_node("Spec2/in", inlet);
         
@@ -791,6 +808,7 @@ mod stream_connections_tests {
             alias: None,
             direction: None,
             label: "stream3".to_string(),
+            ..Default::default()
         };
         let stream_id = graph.ensure_node("stream3", stream);
         
@@ -813,4 +831,224 @@ mod stream_connections_tests {
         assert_eq!(target.inlet_node.tag, Some("in".to_string()));
         assert_eq!(target.destination.spec_name, Some("Spec2".to_string()));
     }
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod def_graph_error_tests {
+    use super::*;
+    use livestack_shared::systems::def_graph::{try_load_from_json, DefGraphError};
+    use livestack_shared::systems::def_graph_utils::{FromSpecAndTag, SpecTagInfo, ToSpecAndTag};
+
+    fn graph_with_spec_a() -> DefGraph {
+        let mut graph = DefGraph::new("RootSpec".to_string(), vec![], vec![]);
+        graph.ensure_inlet_and_stream(
+            SpecTagInfo {
+                spec_name: "SpecA".to_string(),
+                tag: "input1".to_string(),
+                unique_spec_label: None,
+            },
+            false,
+        );
+        graph
+    }
+
+    #[test]
+    fn try_assign_alias_reports_missing_spec() {
+        let mut graph = graph_with_spec_a();
+        let node_count = graph.node_count();
+
+        let result = graph.try_assign_alias("AliasA", "Missing", "RootSpec", None, PortDirection::In, "input1");
+
+        assert_eq!(
+            result,
+            Err(DefGraphError::SpecNotFound {
+                spec_name: "Missing".to_string(),
+                unique_spec_label: None,
+            })
+        );
+        assert_eq!(graph.node_count(), node_count, "graph should be left untouched");
+    }
+
+    #[test]
+    fn try_assign_alias_reports_missing_root_spec() {
+        let mut graph = graph_with_spec_a();
+
+        let result = graph.try_assign_alias("AliasA", "SpecA", "OtherRoot", None, PortDirection::In, "input1");
+
+        assert_eq!(
+            result,
+            Err(DefGraphError::RootSpecNotFound {
+                root_spec_name: "OtherRoot".to_string(),
+            })
+        );
+    }
+
+    #[test]
+    fn try_assign_alias_reports_missing_port_without_adding_alias_node() {
+        let mut graph = graph_with_spec_a();
+        let node_count = graph.node_count();
+
+        let result = graph.try_assign_alias("AliasA", "SpecA", "RootSpec", None, PortDirection::Out, "input1");
+
+        assert_eq!(
+            result,
+            Err(DefGraphError::PortNotFound {
+                spec_name: "SpecA".to_string(),
+                tag: "input1".to_string(),
+                direction: PortDirection::Out,
+            })
+        );
+        assert_eq!(graph.node_count(), node_count, "no alias node should be created");
+        assert!(graph.get_all_alias_node_ids().is_empty());
+    }
+
+    #[test]
+    fn port_direction_parses_and_serializes_as_in_and_out() {
+        assert_eq!("in".parse::<PortDirection>(), Ok(PortDirection::In));
+        assert_eq!(PortDirection::Out.to_string(), "out");
+        assert_eq!(serde_json::to_string(&PortDirection::Out).unwrap(), "\"out\"");
+        assert_eq!(
+            "input".parse::<PortDirection>(),
+            Err(DefGraphError::InvalidDirection("input".to_string()))
+        );
+    }
+
+    #[test]
+    fn try_add_connected_dual_specs_reports_ambiguous_stream_def() {
+        let mut graph = DefGraph::new("RootSpec".to_string(), vec![], vec![]);
+        let from = FromSpecAndTag {
+            spec_name: "SpecA".to_string(),
+            output: "out".to_string(),
+            unique_spec_label: None,
+        };
+        let to = ToSpecAndTag {
+            spec_name: "SpecB".to_string(),
+            input: "in".to_string(),
+            has_transform: false,
+            unique_spec_label: None,
+        };
+        let (_, _, _, outlet_node_id, _) = graph.add_connected_dual_specs(&from, &to);
+
+        // Wire a second stream def onto the same outlet by hand.
+        let extra_stream_node_id = graph.ensure_node(
+            "extra",
+            DefGraphNode {
+                node_type: DefGraphNodeType::StreamDef,
+                spec_name: None,
+                unique_spec_label: None,
+                tag: None,
+                has_transform: None,
+                stream_def_id: Some("extra".to_string()),
+                alias: None,
+                direction: None,
+                label: "extra".to_string(),
+                ..Default::default()
+            },
+        );
+        graph.ensure_edge(outlet_node_id, extra_stream_node_id);
+        let node_count = graph.node_count();
+
+        let result = graph.try_add_connected_dual_specs(
+            &from,
+            &ToSpecAndTag {
+                spec_name: "SpecC".to_string(),
+                ..to
+            },
+        );
+
+        assert_eq!(
+            result,
+            Err(DefGraphError::AmbiguousStreamDef {
+                outlet_label: "SpecA/out".to_string(),
+                count: 2,
+            })
+        );
+        assert_eq!(graph.node_count(), node_count, "graph should be left untouched");
+    }
+
+    #[test]
+    fn try_ensure_edge_reports_missing_node() {
+        let mut graph = DefGraph::new("RootSpec".to_string(), vec![], vec![]);
+        let root_id = graph.get_root_spec_node_id().unwrap();
+
+        assert_eq!(
+            graph.try_ensure_edge(root_id, 999),
+            Err(DefGraphError::NodeNotFound(999))
+        );
+    }
+
+    #[test]
+    fn try_load_from_json_reports_malformed_input() {
+        let result = try_load_from_json("{ not json".to_string());
+
+        assert!(matches!(result, Err(DefGraphError::Deserialize(_))));
+    }
+
+    #[test]
+    fn try_ensure_ports_report_ports_a_composite_does_not_expose() {
+        let mut child = DefGraph::new("Child".to_string(), vec![], vec![]);
+        child.ensure_inlet_and_stream(
+            SpecTagInfo {
+                spec_name: "Inner".to_string(),
+                tag: "in".to_string(),
+                unique_spec_label: None,
+            },
+            false,
+        );
+        child.assign_alias("in", "Inner", "Child", None, PortDirection::In, "in");
+        let mut graph = DefGraph::new("RootSpec".to_string(), vec![], vec![]);
+        graph.try_add_composite_spec(child, None).unwrap();
+        let node_count = graph.node_count();
+        let port = |tag: &str| SpecTagInfo {
+            spec_name: "Child".to_string(),
+            tag: tag.to_string(),
+            unique_spec_label: None,
+        };
+
+        assert_eq!(
+            graph.try_ensure_inlet_and_stream(port("other"), false),
+            Err(DefGraphError::PortNotFound {
+                spec_name: "Child".to_string(),
+                tag: "other".to_string(),
+                direction: PortDirection::In,
+            })
+        );
+        assert_eq!(
+            graph.try_ensure_outlet_and_stream(port("in")),
+            Err(DefGraphError::PortNotFound {
+                spec_name: "Child".to_string(),
+                tag: "in".to_string(),
+                direction: PortDirection::Out,
+            })
+        );
+        assert_eq!(graph.node_count(), node_count, "graph should be left untouched");
+
+        let (inlet, _) = graph.try_ensure_inlet_and_stream(port("in"), false).unwrap();
+        assert_eq!(graph.ensure_inlet_and_stream(port("in"), false).0, inlet);
+    }
+
+    #[test]
+    fn stream_node_lookups_report_ports_without_streams() {
+        // SpecA/a -> SpecB/b with both ends cut off from the stream between them.
+        let mut json: serde_json::Value =
+            serde_json::from_str(include_str!("fixtures/def_graph_digraph_format.json")).unwrap();
+        json["graph"]["edges"]
+            .as_array_mut()
+            .unwrap()
+            .retain(|edge| edge[0] != 7 && edge[1] != 7);
+        let graph = load_from_json(json.to_string());
+
+        assert_matches::assert_matches!(
+            graph.get_inbound_stream_nodes(9),
+            Err(DefGraphError::MalformedNode { node_id: 8, .. })
+        );
+        assert_matches::assert_matches!(
+            graph.get_outbound_stream_nodes(5),
+            Err(DefGraphError::MalformedNode { node_id: 6, .. })
+        );
+        assert_eq!(
+            graph.get_inbound_stream_nodes(99),
+            Err(DefGraphError::NodeNotFound(99))
+        );
+    }
+}
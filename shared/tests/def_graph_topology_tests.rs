@@ -0,0 +1,230 @@
+mod common;
+
+use common::{connect, port};
+use livestack_shared::systems::def_graph::{DefGraph, DefGraphError, DefGraphNodeType};
+use livestack_shared::systems::instantiated_graph::InstantiatedGraph;
+use std::collections::HashMap;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn mark_feedback(graph: &mut DefGraph) {
+        graph
+            .try_mark_feedback_connection(
+                &port("Critic", "verdict"),
+                &port("Planner", "feedback"),
+                true,
+            )
+            .unwrap();
+    }
+
+    fn spec_id(graph: &DefGraph, spec_name: &str) -> u32 {
+        graph
+            .find_node(|n| {
+                n.node_type == DefGraphNodeType::Spec && n.spec_name.as_deref() == Some(spec_name)
+            })
+            .unwrap()
+    }
+
+    /// Planner -> Actor -> Critic, with the critic's verdict fed back into the planner.
+    fn agent_loop() -> DefGraph {
+        let mut graph = DefGraph::new("Agent".to_string(), vec![], vec![]);
+        connect(&mut graph, "Actor", "result", "Critic", "result");
+        connect(&mut graph, "Planner", "plan", "Actor", "plan");
+        connect(&mut graph, "Critic", "verdict", "Planner", "feedback");
+        graph
+    }
+
+    #[test]
+    fn orders_specs_by_data_flow_not_insertion_order() {
+        let mut graph = DefGraph::new("Root".to_string(), vec![], vec![]);
+        connect(&mut graph, "B", "out", "C", "in");
+        connect(&mut graph, "A", "out", "B", "in");
+
+        let order = graph.topological_spec_order().unwrap();
+
+        assert_eq!(
+            order,
+            vec![spec_id(&graph, "A"), spec_id(&graph, "B"), spec_id(&graph, "C")]
+        );
+    }
+
+    #[test]
+    fn reports_unmarked_loop_with_its_specs_and_streams() {
+        let graph = agent_loop();
+
+        let cycles = graph.topological_spec_order().unwrap_err();
+
+        assert_eq!(cycles.len(), 1);
+        let mut specs = cycles[0].spec_identifiers.clone();
+        specs.sort();
+        assert_eq!(specs, vec!["Actor", "Critic", "Planner"]);
+        assert_eq!(
+            cycles[0].stream_def_ids,
+            vec![
+                "Actor/result>>Critic/result",
+                "Critic/verdict>>Planner/feedback",
+                "Planner/plan>>Actor/plan",
+            ]
+        );
+        assert!(graph.find_feedback_cycles().is_empty());
+    }
+
+    #[test]
+    fn marked_feedback_connection_breaks_the_loop() {
+        let mut graph = agent_loop();
+        mark_feedback(&mut graph);
+
+        assert!(graph.find_spec_cycles().is_empty());
+        assert_eq!(
+            graph.topological_spec_order().unwrap(),
+            vec![
+                spec_id(&graph, "Planner"),
+                spec_id(&graph, "Actor"),
+                spec_id(&graph, "Critic"),
+            ]
+        );
+        let feedback = graph.find_feedback_cycles();
+        assert_eq!(feedback.len(), 1);
+        assert_eq!(feedback[0].spec_node_ids.len(), 3);
+
+        let feedback_connections: Vec<_> = graph
+            .get_spec_connections()
+            .into_iter()
+            .filter(|c| c.is_feedback)
+            .collect();
+        assert_eq!(feedback_connections.len(), 1);
+        assert_eq!(
+            feedback_connections[0].stream_def_id,
+            "Critic/verdict>>Planner/feedback"
+        );
+    }
+
+    #[test]
+    fn marking_an_unrelated_edge_leaves_the_loop_reported() {
+        let mut graph = agent_loop();
+        connect(&mut graph, "Critic", "verdict", "Logger", "in");
+        graph
+            .try_mark_feedback_connection(&port("Critic", "verdict"), &port("Logger", "in"), true)
+            .unwrap();
+
+        assert_eq!(graph.find_spec_cycles().len(), 1);
+        assert!(graph.find_feedback_cycles().is_empty());
+    }
+
+    #[test]
+    fn feedback_loop_sharing_specs_with_an_unmarked_loop_is_still_reported() {
+        let mut graph = agent_loop();
+        mark_feedback(&mut graph);
+        connect(&mut graph, "Actor", "draft", "Helper", "draft");
+        connect(&mut graph, "Helper", "fix", "Actor", "fix");
+
+        let accidental = graph.find_spec_cycles();
+        let feedback = graph.find_feedback_cycles();
+
+        assert_eq!(accidental.len(), 1);
+        assert_eq!(accidental[0].spec_identifiers, vec!["Actor", "Helper"]);
+        assert_eq!(feedback.len(), 1);
+        let mut specs = feedback[0].spec_identifiers.clone();
+        specs.sort();
+        assert_eq!(specs, vec!["Actor", "Critic", "Helper", "Planner"]);
+        assert!(feedback[0]
+            .stream_def_ids
+            .contains(&"Critic/verdict>>Planner/feedback".to_string()));
+    }
+
+    #[test]
+    fn self_loop_is_a_cycle() {
+        let mut graph = DefGraph::new("Root".to_string(), vec![], vec![]);
+        connect(&mut graph, "Refiner", "draft", "Refiner", "draft");
+
+        let cycles = graph.find_spec_cycles();
+
+        assert_eq!(cycles.len(), 1);
+        assert_eq!(cycles[0].spec_identifiers, vec!["Refiner"]);
+    }
+
+    #[test]
+    fn marking_feedback_on_missing_ports_fails() {
+        let mut graph = agent_loop();
+
+        assert_eq!(
+            graph.try_mark_feedback_connection(
+                &port("Critic", "verdict"),
+                &port("Nobody", "feedback"),
+                true
+            ),
+            Err(DefGraphError::SpecNotFound {
+                spec_name: "Nobody".to_string(),
+                unique_spec_label: None,
+            })
+        );
+        assert!(matches!(
+            graph.try_mark_feedback_connection(
+                &port("Critic", "verdict"),
+                &port("Planner", "nope"),
+                true
+            ),
+            Err(DefGraphError::PortNotFound { .. })
+        ));
+        assert!(matches!(
+            graph.try_mark_feedback_connection(
+                &port("Actor", "result"),
+                &port("Planner", "feedback"),
+                true
+            ),
+            Err(DefGraphError::NotConnected { .. })
+        ));
+    }
+
+    #[test]
+    fn feedback_is_marked_per_connection_not_per_inlet() {
+        // Seed also feeds the planner's feedback inlet, as a forward edge.
+        let mut graph = agent_loop();
+        connect(&mut graph, "Seed", "hint", "Planner", "feedback");
+        mark_feedback(&mut graph);
+
+        let feedback: Vec<String> = graph
+            .get_spec_connections()
+            .into_iter()
+            .filter(|c| c.is_feedback)
+            .map(|c| c.stream_def_id)
+            .collect();
+        assert_eq!(feedback, vec!["Critic/verdict>>Planner/feedback"]);
+        let order = graph.topological_spec_order().unwrap();
+        let position = |name: &str| order.iter().position(|&id| id == spec_id(&graph, name));
+        assert!(position("Seed") < position("Planner"), "{:?}", order);
+
+        graph
+            .try_mark_feedback_connection(
+                &port("Critic", "verdict"),
+                &port("Planner", "feedback"),
+                false,
+            )
+            .unwrap();
+        assert_eq!(graph.find_spec_cycles().len(), 1);
+    }
+
+    #[test]
+    fn instantiated_graph_lists_jobs_in_start_order() {
+        let mut graph = agent_loop();
+        mark_feedback(&mut graph);
+
+        let instantiated = InstantiatedGraph::new(
+            "ctx".to_string(),
+            "rootJob".to_string(),
+            HashMap::new(),
+            HashMap::new(),
+            HashMap::new(),
+            &graph,
+        );
+
+        let job_ids: Vec<String> = instantiated
+            .get_job_node_ids_in_start_order()
+            .into_iter()
+            .map(|id| instantiated.node_weight(id).unwrap().job_id.clone().unwrap())
+            .collect();
+        assert_eq!(job_ids, vec!["[ctx]Planner", "[ctx]Actor", "[ctx]Critic"]);
+    }
+}
@@ -0,0 +1,134 @@
+mod common;
+
+use common::labelled_port;
+use livestack_shared::systems::def_graph::DefGraphError;
+use livestack_shared::systems::def_graph_utils::{
+    parse_spec_identifier, parse_stream_identifier, unique_spec_identifier,
+    unique_stream_identifier, IdentifierSyntax,
+};
+use std::collections::HashSet;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Every reserved character, alone and mixed into text, plus the empty string.
+    const AWKWARD: [&str; 10] = [
+        "",
+        "plain",
+        "a/b",
+        "x[1]",
+        "f(x)",
+        "a>>b",
+        ">",
+        "back\\slash",
+        "(*)",
+        "é ü",
+    ];
+
+    #[test]
+    fn ids_of_plain_names_are_unchanged() {
+        assert_eq!(
+            unique_spec_identifier("Summarizer".to_string(), Some("short".to_string())),
+            "Summarizer[short]"
+        );
+        assert_eq!(
+            unique_stream_identifier(
+                Some(labelled_port("A", Some("x"), "out")),
+                Some(labelled_port("B", Some("y"), "in"))
+            ),
+            "A[x]/out>>B(y)/in"
+        );
+        assert_eq!(
+            unique_stream_identifier(None, Some(labelled_port("B", Some("default_label"), "in"))),
+            "(*)>>B/in"
+        );
+    }
+
+    #[test]
+    fn spec_ids_round_trip_and_never_collide() {
+        let mut ids = HashSet::new();
+        for name in AWKWARD {
+            for label in [None].into_iter().chain(AWKWARD.map(Some)) {
+                let parts = (name.to_string(), label.map(str::to_string));
+                let id = unique_spec_identifier(parts.0.clone(), parts.1.clone());
+                assert_eq!(
+                    parse_spec_identifier(&id, IdentifierSyntax::Escaped),
+                    Ok(parts),
+                    "{}",
+                    id
+                );
+                assert!(ids.insert(id));
+            }
+        }
+    }
+
+    #[test]
+    fn stream_ids_round_trip_and_never_collide() {
+        let mut ports = vec![None];
+        for text in AWKWARD {
+            ports.push(Some(labelled_port(text, None, "tag")));
+            ports.push(Some(labelled_port("spec", Some(text), "tag")));
+            ports.push(Some(labelled_port("spec", None, text)));
+        }
+
+        let mut ids = HashSet::new();
+        for from in &ports {
+            for to in &ports {
+                let id = unique_stream_identifier(from.clone(), to.clone());
+                assert_eq!(
+                    parse_stream_identifier(&id, IdentifierSyntax::Escaped),
+                    Ok((from.clone(), to.clone())),
+                    "{}",
+                    id
+                );
+                assert!(ids.insert(id));
+            }
+        }
+    }
+
+    #[test]
+    fn legacy_syntax_reads_stored_ids() {
+        assert_eq!(
+            parse_stream_identifier("A[x)/out>>B(y)/in", IdentifierSyntax::Legacy),
+            Ok((
+                Some(labelled_port("A", Some("x"), "out")),
+                Some(labelled_port("B", Some("y"), "in"))
+            ))
+        );
+        assert_eq!(
+            parse_stream_identifier("A/out>>(*)", IdentifierSyntax::Legacy),
+            Ok((Some(labelled_port("A", None, "out")), None))
+        );
+        // Unescaped, so a backslash is just a character.
+        assert_eq!(
+            parse_spec_identifier("C:\\dir[main]", IdentifierSyntax::Legacy),
+            Ok(("C:\\dir".to_string(), Some("main".to_string())))
+        );
+        assert_eq!(
+            parse_stream_identifier("A[x)/out>>B(y)/in", IdentifierSyntax::Escaped),
+            Err(DefGraphError::InvalidIdentifier {
+                id: "A[x)/out>>B(y)/in".to_string(),
+                reason: "label must end with `]`".to_string(),
+            })
+        );
+    }
+
+    #[test]
+    fn malformed_ids_are_rejected() {
+        for id in ["a>>b>>c", "a/b>c/d", "A[x", "A[x]y", "A\\", "A/b>>C/d/e[f]"] {
+            let parsed = if id.contains(">>") {
+                parse_stream_identifier(id, IdentifierSyntax::Escaped).map(|_| ())
+            } else {
+                parse_spec_identifier(id, IdentifierSyntax::Escaped).map(|_| ())
+            };
+            assert!(
+                matches!(parsed, Err(DefGraphError::InvalidIdentifier { .. })),
+                "{} parsed as {:?}",
+                id,
+                parsed
+            );
+        }
+        assert!(parse_stream_identifier("A/out", IdentifierSyntax::Legacy).is_err());
+    }
+}
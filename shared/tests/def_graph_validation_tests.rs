@@ -0,0 +1,206 @@
+use livestack_shared::systems::def_graph::{load_from_json, DefGraph, DefGraphNode, DefGraphNodeType, PortDirection};
+use livestack_shared::systems::def_graph_utils::{FromSpecAndTag, SpecTagInfo, ToSpecAndTag};
+use livestack_shared::systems::def_graph_validation::DefGraphDiagnostic;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn node(node_type: DefGraphNodeType, label: &str) -> DefGraphNode {
+        DefGraphNode {
+            node_type,
+            spec_name: None,
+            unique_spec_label: None,
+            tag: None,
+            has_transform: None,
+            stream_def_id: None,
+            alias: None,
+            direction: None,
+            label: label.to_string(),
+            ..Default::default()
+        }
+    }
+
+    fn well_formed_graph() -> DefGraph {
+        let mut graph = DefGraph::new(
+            "RootSpec".to_string(),
+            vec!["input1".to_string()],
+            vec!["output1".to_string()],
+        );
+        graph.add_connected_dual_specs(
+            &FromSpecAndTag {
+                spec_name: "SpecA".to_string(),
+                output: "outA".to_string(),
+                unique_spec_label: None,
+            },
+            &ToSpecAndTag {
+                spec_name: "SpecB".to_string(),
+                input: "inB".to_string(),
+                has_transform: false,
+                unique_spec_label: None,
+            },
+        );
+        graph.ensure_inlet_and_stream(
+            SpecTagInfo {
+                spec_name: "SpecA".to_string(),
+                tag: "inA".to_string(),
+                unique_spec_label: None,
+            },
+            false,
+        );
+        graph.ensure_outlet_and_stream(SpecTagInfo {
+            spec_name: "SpecB".to_string(),
+            tag: "outB".to_string(),
+            unique_spec_label: None,
+        });
+        graph.assign_alias("publicIn", "SpecA", "RootSpec", None, PortDirection::In, "inA");
+        graph.assign_alias("publicOut", "SpecB", "RootSpec", None, PortDirection::Out, "outB");
+        graph
+    }
+
+    #[test]
+    fn well_formed_graph_has_no_diagnostics() {
+        let graph = well_formed_graph();
+        assert_eq!(graph.validate(), vec![]);
+    }
+
+    #[test]
+    fn validation_survives_a_json_round_trip() {
+        let graph = well_formed_graph();
+        let reloaded = load_from_json(graph.to_json().unwrap());
+        assert_eq!(reloaded.validate(), vec![]);
+    }
+
+    #[test]
+    fn reports_multiple_root_specs_and_orphans() {
+        let mut graph = DefGraph::new("RootSpec".to_string(), vec![], vec![]);
+        let second_root = graph.ensure_node(
+            "OtherRoot",
+            DefGraphNode {
+                spec_name: Some("OtherRoot".to_string()),
+                ..node(DefGraphNodeType::RootSpec, "OtherRoot")
+            },
+        );
+        let orphan = graph.ensure_node("Lonely", node(DefGraphNodeType::Spec, "Lonely"));
+        let root = graph.get_root_spec_node_id().unwrap();
+
+        let diagnostics = graph.validate();
+
+        assert!(diagnostics.contains(&DefGraphDiagnostic::MultipleRootSpecs {
+            node_ids: vec![root, second_root],
+        }));
+        assert!(diagnostics.contains(&DefGraphDiagnostic::OrphanedNode { node_id: orphan }));
+        assert!(
+            !diagnostics.contains(&DefGraphDiagnostic::OrphanedNode { node_id: root }),
+            "a bare RootSpec is not an orphan"
+        );
+    }
+
+    #[test]
+    fn reports_every_port_violation_instead_of_stopping_at_the_first() {
+        let mut graph = well_formed_graph();
+        let spec_a = graph
+            .find_node(|n| n.node_type == DefGraphNodeType::Spec && n.spec_name.as_deref() == Some("SpecA"))
+            .unwrap();
+        let (inlet, _) = graph.get_inbound_stream_nodes(spec_a).unwrap()[0];
+        let (outlet, _) = graph.get_outbound_stream_nodes(spec_a).unwrap()[0];
+
+        let extra_in = graph.ensure_node("extraIn", node(DefGraphNodeType::StreamDef, "extraIn"));
+        graph.ensure_edge(extra_in, inlet);
+        let extra_out = graph.ensure_node("extraOut", node(DefGraphNodeType::StreamDef, "extraOut"));
+        graph.ensure_edge(outlet, extra_out);
+        let dangling_inlet = graph.ensure_node("dangling", node(DefGraphNodeType::Inlet, "dangling"));
+        let stream = graph.ensure_node("s", node(DefGraphNodeType::StreamDef, "s"));
+        graph.ensure_edge(stream, dangling_inlet);
+
+        let diagnostics = graph.validate();
+
+        assert!(diagnostics.iter().any(|d| matches!(
+            d,
+            DefGraphDiagnostic::InletHasMultipleStreamDefs { inlet_node_id, stream_node_ids }
+                if *inlet_node_id == inlet && stream_node_ids.len() == 2
+        )));
+        assert!(diagnostics.iter().any(|d| matches!(
+            d,
+            DefGraphDiagnostic::OutletHasMultipleStreamDefs { outlet_node_id, stream_node_ids }
+                if *outlet_node_id == outlet && stream_node_ids.len() == 2
+        )));
+        assert!(diagnostics.contains(&DefGraphDiagnostic::InletNotAttachedToOneSpec {
+            inlet_node_id: dangling_inlet,
+            spec_node_ids: vec![],
+        }));
+    }
+
+    #[test]
+    fn reports_aliases_wired_against_their_direction() {
+        let mut graph = well_formed_graph();
+        let root = graph.get_root_spec_node_id().unwrap();
+        let backwards = graph.ensure_node(
+            "RootSpec/backwards",
+            DefGraphNode {
+                alias: Some("backwards".to_string()),
+                direction: Some(PortDirection::In),
+                ..node(DefGraphNodeType::Alias, "RootSpec/backwards")
+            },
+        );
+        graph.ensure_edge(root, backwards);
+        let sideways = graph.ensure_node(
+            "RootSpec/sideways",
+            DefGraphNode {
+                alias: Some("sideways".to_string()),
+                direction: None,
+                ..node(DefGraphNodeType::Alias, "RootSpec/sideways")
+            },
+        );
+        graph.ensure_edge(root, sideways);
+
+        let diagnostics = graph.validate();
+
+        assert!(diagnostics.contains(&DefGraphDiagnostic::AliasMiswired {
+            alias_node_id: backwards,
+            direction: PortDirection::In,
+        }));
+        assert!(diagnostics.contains(&DefGraphDiagnostic::AliasMissingDirection {
+            alias_node_id: sideways,
+        }));
+    }
+
+    #[test]
+    fn stored_node_indices_are_rebuilt_rather_than_trusted() {
+        // Only the unversioned (v1) layout stores lookup indices; corrupted ones are dropped.
+        let mut json: serde_json::Value =
+            serde_json::from_str(include_str!("fixtures/def_graph_digraph_format.json")).unwrap();
+        let indices = json["nodeIndices"].as_object_mut().unwrap();
+        let spec_a_index = indices["Spec_SpecA"].clone();
+        indices.insert("Outlet_bogus".to_string(), spec_a_index);
+        indices.insert("Spec_ghost".to_string(), serde_json::json!(9999));
+        let loaded = load_from_json(json.to_string());
+
+        assert!(loaded.validate().is_empty(), "{:?}", loaded.validate());
+    }
+
+    #[test]
+    fn reports_nodes_no_index_entry_points_at() {
+        // Indices are rebuilt on load, one entry per key, so of two nodes for the same spec
+        // only the later one keeps its entry.
+        let mut json: serde_json::Value =
+            serde_json::from_str(include_str!("fixtures/def_graph_digraph_format.json")).unwrap();
+        let graph = json["graph"].as_object_mut().unwrap();
+        graph["nodes"].as_array_mut().unwrap().push(serde_json::json!({
+            "nodeType": "spec", "specName": "SpecA", "label": "SpecA"
+        }));
+        graph["edges"].as_array_mut().unwrap().push(serde_json::json!([11, 6, null]));
+        let loaded = load_from_json(json.to_string());
+
+        assert_eq!(
+            loaded.validate(),
+            vec![
+                DefGraphDiagnostic::OutletNotAttachedToOneSpec {
+                    outlet_node_id: 6,
+                    spec_node_ids: vec![5, 11],
+                },
+                DefGraphDiagnostic::UnindexedNode { node_id: 5 },
+            ]
+        );
+    }
+}
@@ -0,0 +1,359 @@
+mod common;
+
+use common::labelled_port;
+use livestack_shared::systems::def_graph::{
+    load_from_json, try_load_from_json, DefGraph, DefGraphError, PortDirection,
+};
+use livestack_shared::systems::def_graph_utils::{FromSpecAndTag, ToSpecAndTag};
+use livestack_shared::systems::graph_format::{
+    to_canonical_json, DEF_GRAPH_FORMAT_VERSION, INSTANTIATED_GRAPH_FORMAT_VERSION,
+    MAX_REMOVED_NODE_IDS,
+};
+use livestack_shared::systems::instantiated_graph::InstantiatedGraph;
+use std::collections::HashMap;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn connect_a_to_b(graph: &mut DefGraph) {
+        graph.add_connected_dual_specs(
+            &FromSpecAndTag {
+                spec_name: "SpecA".to_string(),
+                output: "a".to_string(),
+                unique_spec_label: None,
+            },
+            &ToSpecAndTag {
+                spec_name: "SpecB".to_string(),
+                input: "b".to_string(),
+                has_transform: true,
+                unique_spec_label: None,
+            },
+        );
+    }
+
+    fn sample_graph() -> DefGraph {
+        let mut graph = DefGraph::new(
+            "Root".to_string(),
+            vec!["in".to_string()],
+            vec!["out".to_string()],
+        );
+        connect_a_to_b(&mut graph);
+        graph.assign_alias("publicA", "SpecB", "Root", None, PortDirection::In, "b");
+        graph
+    }
+
+    fn instantiate(graph: &DefGraph) -> InstantiatedGraph {
+        let mut overrides = HashMap::new();
+        overrides.insert("in/b".to_string(), "custom-b".to_string());
+        InstantiatedGraph::new(
+            "ctx".to_string(),
+            "rootJob".to_string(),
+            overrides,
+            HashMap::new(),
+            HashMap::new(),
+            graph,
+        )
+    }
+
+    fn assert_same_def_graph(a: &DefGraph, b: &DefGraph) {
+        assert_eq!(a.node_indices(), b.node_indices());
+        for id in a.node_indices() {
+            assert_eq!(a.node_weight(id), b.node_weight(id));
+        }
+        let mut a_edges = a.raw_edges();
+        let mut b_edges = b.raw_edges();
+        a_edges.sort();
+        b_edges.sort();
+        assert_eq!(a_edges, b_edges);
+    }
+
+    #[test]
+    fn def_graph_json_is_versioned_and_free_of_lookup_maps() {
+        let json: serde_json::Value =
+            serde_json::from_str(&sample_graph().to_json().unwrap()).unwrap();
+
+        assert_eq!(json["version"], serde_json::json!(DEF_GRAPH_FORMAT_VERSION));
+        assert!(json["nodes"].is_array());
+        assert!(json["edges"].is_array());
+        assert!(json.get("nodeIndices").is_none());
+        assert!(json.get("graph").is_none());
+    }
+
+    #[test]
+    fn def_graph_round_trip_rebuilds_lookup_maps() {
+        let graph = sample_graph();
+        let mut reloaded = load_from_json(graph.to_json().unwrap());
+
+        assert_same_def_graph(&graph, &reloaded);
+        assert!(reloaded.validate().is_empty(), "{:?}", reloaded.validate());
+        // The rebuilt maps let the builder find the existing nodes again.
+        let node_count = reloaded.node_count();
+        connect_a_to_b(&mut reloaded);
+        assert_eq!(reloaded.node_count(), node_count);
+    }
+
+    #[test]
+    fn migrates_unversioned_def_graph() {
+        let legacy =
+            load_from_json(include_str!("fixtures/def_graph_digraph_format.json").to_string());
+        let mut migrated = load_from_json(legacy.to_json().unwrap());
+
+        assert_same_def_graph(&legacy, &migrated);
+        assert!(migrated.validate().is_empty(), "{:?}", migrated.validate());
+        let node_count = migrated.node_count();
+        connect_a_to_b(&mut migrated);
+        assert_eq!(migrated.node_count(), node_count);
+    }
+
+    #[test]
+    fn unversioned_def_graph_with_labelled_spec_keeps_building() {
+        let mut graph =
+            load_from_json(include_str!("fixtures/def_graph_v1_labelled_spec.json").to_string());
+        let node_count = graph.node_count();
+
+        graph.ensure_inlet_and_stream(labelled_port("translate", Some("fr"), "text"), false);
+        graph.add_connected_dual_specs(
+            &FromSpecAndTag {
+                spec_name: "Source".to_string(),
+                output: "out".to_string(),
+                unique_spec_label: None,
+            },
+            &ToSpecAndTag {
+                spec_name: "translate".to_string(),
+                input: "text".to_string(),
+                has_transform: false,
+                unique_spec_label: Some("fr".to_string()),
+            },
+        );
+
+        assert_eq!(graph.node_count(), node_count);
+        assert!(graph.validate().is_empty(), "{:?}", graph.validate());
+    }
+
+    #[test]
+    fn migrates_inlet_feedback_flags_onto_connections() {
+        let mut json: serde_json::Value =
+            serde_json::from_str(&sample_graph().to_json().unwrap()).unwrap();
+        json["version"] = serde_json::json!(3);
+        for node in json["nodes"].as_array_mut().unwrap() {
+            if node["label"] == "SpecB/b" {
+                node["isFeedback"] = serde_json::json!(true);
+            }
+        }
+
+        let migrated = load_from_json(json.to_string());
+
+        let connections = migrated.get_spec_connections();
+        assert_eq!(connections.len(), 1);
+        assert!(connections[0].is_feedback);
+        let rewritten: serde_json::Value =
+            serde_json::from_str(&migrated.to_json().unwrap()).unwrap();
+        let flagged: Vec<&serde_json::Value> = rewritten["edges"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .filter(|edge| edge["meta"]["isFeedback"] == true)
+            .collect();
+        assert_eq!(flagged.len(), 1);
+        assert!(rewritten["nodes"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .all(|node| node.get("isFeedback").is_none()));
+    }
+
+    #[test]
+    fn rejects_newer_format_versions() {
+        let json = serde_json::json!({ "version": 99, "nodes": [], "edges": [] }).to_string();
+
+        assert_eq!(
+            try_load_from_json(json).unwrap_err(),
+            DefGraphError::UnsupportedFormatVersion {
+                version: 99,
+                latest: DEF_GRAPH_FORMAT_VERSION,
+            }
+        );
+    }
+
+    #[test]
+    fn rejects_edges_to_missing_nodes() {
+        let json = serde_json::json!({
+            "version": 2,
+            "nodes": [{ "id": 0, "nodeType": "root-spec", "specName": "Root", "label": "Root" }],
+            "edges": [{ "from": 0, "to": 4 }],
+        })
+        .to_string();
+
+        assert_eq!(
+            try_load_from_json(json).unwrap_err(),
+            DefGraphError::NodeNotFound(4)
+        );
+    }
+
+    #[test]
+    fn rejects_node_ids_far_beyond_the_node_count() {
+        let document = |id: u64| {
+            serde_json::json!({
+                "version": 5,
+                "nodes": [
+                    { "id": 0, "nodeType": "root-spec", "specName": "Root", "label": "Root" },
+                    { "id": id, "nodeType": "spec", "specName": "A", "label": "A" },
+                ],
+                "edges": [],
+            })
+            .to_string()
+        };
+
+        assert_eq!(
+            try_load_from_json(document(4_000_000_000)).unwrap_err(),
+            DefGraphError::Deserialize(format!(
+                "node id 4000000000 leaves more than {} unused ids below it",
+                MAX_REMOVED_NODE_IDS
+            ))
+        );
+        let highest = (MAX_REMOVED_NODE_IDS + 1) as u64;
+        assert!(try_load_from_json(document(highest)).is_ok());
+        assert!(try_load_from_json(document(highest + 1)).is_err());
+    }
+
+    #[test]
+    fn instantiated_graph_round_trip_keeps_nodes_and_edges() {
+        let instantiated = instantiate(&sample_graph());
+        let json = instantiated.to_json().unwrap();
+        let value: serde_json::Value = serde_json::from_str(&json).unwrap();
+        assert_eq!(
+            value["version"],
+            serde_json::json!(INSTANTIATED_GRAPH_FORMAT_VERSION)
+        );
+
+        let reloaded = InstantiatedGraph::from_json(&json).unwrap();
+
+        assert_eq!(reloaded.node_indices(), instantiated.node_indices());
+        for id in instantiated.node_indices() {
+            assert_eq!(reloaded.node_weight(id), instantiated.node_weight(id));
+        }
+        assert_eq!(reloaded.raw_edges(), instantiated.raw_edges());
+        assert_eq!(
+            reloaded.get_root_job_node_id(),
+            instantiated.get_root_job_node_id()
+        );
+        assert_eq!(reloaded.to_json().unwrap(), json);
+    }
+
+    #[test]
+    fn instantiated_graph_with_removed_nodes_keeps_its_node_ids() {
+        // SpecC comes first, so removing it leaves a gap below the other nodes.
+        let mut graph = DefGraph::new("Root".to_string(), vec![], vec![]);
+        graph.ensure_inlet_and_stream(labelled_port("SpecC", None, "c"), false);
+        connect_a_to_b(&mut graph);
+        let mut instantiated = instantiate(&graph);
+        graph.remove_spec("SpecC", None).unwrap();
+        instantiated.apply_def_graph_change(&graph).unwrap();
+        let ids = instantiated.node_indices();
+        assert_ne!(ids.last().map(|&id| id as usize + 1), Some(ids.len()));
+
+        let json = instantiated.to_json().unwrap();
+        let reloaded = InstantiatedGraph::from_json(&json).unwrap();
+
+        assert_eq!(reloaded.node_indices(), ids);
+        assert_eq!(reloaded.raw_edges(), instantiated.raw_edges());
+        let spec_b = instantiated.find_job_node_id("[ctx]SpecB");
+        assert!(spec_b.is_some());
+        assert_eq!(reloaded.find_job_node_id("[ctx]SpecB"), spec_b);
+        assert_eq!(reloaded.to_json().unwrap(), json);
+    }
+
+    #[test]
+    fn migrates_unversioned_instantiated_graph() {
+        let legacy = InstantiatedGraph::from_json(include_str!(
+            "fixtures/instantiated_graph_digraph_format.json"
+        ))
+        .unwrap();
+        let fresh = instantiate(&sample_graph());
+
+        assert_eq!(legacy.node_indices(), fresh.node_indices());
+        for id in fresh.node_indices() {
+            assert_eq!(legacy.node_weight(id), fresh.node_weight(id));
+        }
+        assert_eq!(legacy.raw_edges(), fresh.raw_edges());
+        assert_eq!(legacy.context_id, "ctx");
+
+        let migrated = InstantiatedGraph::from_json(&legacy.to_json().unwrap()).unwrap();
+        assert_eq!(migrated.raw_edges(), legacy.raw_edges());
+        assert_eq!(
+            migrated.get_root_job_node_id(),
+            legacy.get_root_job_node_id()
+        );
+    }
+
+    #[test]
+    fn canonical_json_sorts_keys_at_every_level() {
+        let value = serde_json::json!({ "b": [{ "z": 1, "a": 2 }], "a": { "y": null, "x": true } });
+
+        assert_eq!(
+            to_canonical_json(&value).unwrap(),
+            r#"{"a":{"x":true,"y":null},"b":[{"a":2,"z":1}]}"#
+        );
+    }
+
+    #[test]
+    fn instantiated_graph_json_does_not_depend_on_map_order() {
+        let tags: Vec<String> = (0..32).map(|i| format!("tag{}", i)).collect();
+        let build = |tags: &mut dyn Iterator<Item = &String>| {
+            let mut overrides = HashMap::new();
+            let mut transforms = HashMap::new();
+            for tag in tags {
+                overrides.insert(format!("in/{}", tag), format!("stream-{}", tag));
+                transforms.insert(tag.clone(), tag.len() % 2 == 0);
+            }
+            InstantiatedGraph::new(
+                "ctx".to_string(),
+                "rootJob".to_string(),
+                overrides,
+                transforms,
+                HashMap::new(),
+                &sample_graph(),
+            )
+            .to_json()
+            .unwrap()
+        };
+
+        let forward = build(&mut tags.iter());
+        let backward = build(&mut tags.iter().rev());
+
+        assert_eq!(forward, backward);
+        let tag10 = forward.find(r#""in/tag10""#).unwrap();
+        let tag9 = forward.find(r#""in/tag9""#).unwrap();
+        assert!(tag10 < tag9);
+    }
+
+    #[test]
+    fn canonical_json_is_stable_across_round_trips() {
+        let mut graph = sample_graph();
+        graph.add_connected_dual_specs(
+            &FromSpecAndTag {
+                spec_name: "SpecB".to_string(),
+                output: "c".to_string(),
+                unique_spec_label: None,
+            },
+            &ToSpecAndTag {
+                spec_name: "SpecC".to_string(),
+                input: "c".to_string(),
+                has_transform: false,
+                unique_spec_label: Some("x".to_string()),
+            },
+        );
+        graph.remove_spec("SpecA", None).unwrap();
+        let json = graph.to_json().unwrap();
+
+        let reloaded = try_load_from_json(json.clone()).unwrap();
+        assert_same_def_graph(&reloaded, &graph);
+        assert_eq!(reloaded.to_json().unwrap(), json);
+
+        let instantiated_json = instantiate(&graph).to_json().unwrap();
+        let reinstantiated = InstantiatedGraph::from_json(&instantiated_json).unwrap();
+        assert_eq!(reinstantiated.to_json().unwrap(), instantiated_json);
+        assert_eq!(instantiate(&reloaded).to_json().unwrap(), instantiated_json);
+    }
+}
@@ -0,0 +1,194 @@
+mod common;
+
+use common::{connect, connect_ports, labelled_port, port};
+use livestack_shared::systems::def_graph::{
+    try_load_from_json, DefGraph, DefGraphError, DefGraphNodeType, PortDirection,
+};
+use livestack_shared::systems::def_graph_utils::SpecTagInfo;
+use livestack_shared::systems::instantiated_graph::{InstantiatedGraph, InstantiatedNodeType};
+use std::collections::HashMap;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Root input `request` -> A -> B[x] and A -> C, with A's input aliased.
+    fn graph() -> DefGraph {
+        let mut graph = DefGraph::new("Root".to_string(), vec![], vec![]);
+        connect_ports(
+            &mut graph,
+            &port("A", "out"),
+            &labelled_port("B", Some("x"), "in"),
+            false,
+        )
+        .unwrap();
+        connect(&mut graph, "A", "out", "C", "in");
+        graph.ensure_inlet_and_stream(
+            SpecTagInfo {
+                spec_name: "A".to_string(),
+                unique_spec_label: None,
+                tag: "in".to_string(),
+            },
+            false,
+        );
+        graph.assign_alias("request", "A", "Root", None, PortDirection::In, "in");
+        graph
+    }
+
+    fn scanned(graph: &DefGraph, node_type: DefGraphNodeType) -> Vec<u32> {
+        graph
+            .node_indices()
+            .into_iter()
+            .filter(|&id| graph.node_weight(id).unwrap().node_type == node_type)
+            .collect()
+    }
+
+    fn assert_indexes_match_scan(graph: &DefGraph) {
+        assert_eq!(
+            graph.get_spec_node_ids(),
+            scanned(graph, DefGraphNodeType::Spec)
+        );
+        assert_eq!(
+            graph.get_all_alias_node_ids(),
+            scanned(graph, DefGraphNodeType::Alias)
+        );
+        assert_eq!(
+            graph.get_root_spec_node_id(),
+            scanned(graph, DefGraphNodeType::RootSpec).first().copied()
+        );
+        for id in graph.get_spec_node_ids() {
+            let node = graph.node_weight(id).unwrap();
+            assert_eq!(
+                graph.find_spec_node(
+                    node.spec_name.as_deref().unwrap(),
+                    node.unique_spec_label.as_deref()
+                ),
+                Some(id)
+            );
+        }
+    }
+
+    #[test]
+    fn def_graph_indexes_follow_additions_and_removals() {
+        let mut graph = graph();
+        assert_indexes_match_scan(&graph);
+        assert!(graph.find_spec_node("B", Some("x")).is_some());
+        assert_eq!(graph.find_spec_node("B", None), None);
+
+        graph.remove_spec("C", None).unwrap();
+        graph.remove_alias("request", PortDirection::In).unwrap();
+        assert_indexes_match_scan(&graph);
+        assert_eq!(graph.find_spec_node("C", None), None);
+        assert!(graph.get_all_alias_node_ids().is_empty());
+
+        // Freed node ids are reused by new nodes, which must be indexed under their new type.
+        connect(&mut graph, "B", "out", "D", "in");
+        assert_indexes_match_scan(&graph);
+    }
+
+    #[test]
+    fn ports_are_found_again_after_being_removed_and_recreated() {
+        let mut graph = graph();
+        graph
+            .disconnect(&port("A", "out"), &port("C", "in"))
+            .unwrap();
+        assert_eq!(
+            graph.set_inlet_merge_policy(&port("C", "in"), None),
+            Err(DefGraphError::PortNotFound {
+                spec_name: "C".to_string(),
+                tag: "in".to_string(),
+                direction: PortDirection::In,
+            })
+        );
+
+        connect(&mut graph, "A", "out", "C", "in");
+        graph
+            .set_inlet_merge_policy(&port("C", "in"), None)
+            .unwrap();
+        graph.assign_alias("cIn", "C", "Root", None, PortDirection::In, "in");
+
+        for graph in [
+            &graph,
+            &try_load_from_json(graph.to_json().unwrap()).unwrap(),
+        ] {
+            assert_eq!(
+                graph.lookup_root_spec_alias(
+                    "C".to_string(),
+                    None,
+                    "in".to_string(),
+                    PortDirection::In
+                ),
+                Some("cIn".to_string())
+            );
+            assert_eq!(
+                graph.lookup_root_spec_alias(
+                    "A".to_string(),
+                    None,
+                    "in".to_string(),
+                    PortDirection::In
+                ),
+                Some("request".to_string())
+            );
+        }
+    }
+
+    #[test]
+    fn def_graph_indexes_are_rebuilt_on_load() {
+        let mut graph = graph();
+        graph.remove_spec("C", None).unwrap();
+
+        let reloaded = try_load_from_json(graph.to_json().unwrap()).unwrap();
+
+        assert_indexes_match_scan(&reloaded);
+        assert_eq!(
+            reloaded.find_spec_node("B", Some("x")),
+            graph.find_spec_node("B", Some("x"))
+        );
+    }
+
+    #[test]
+    fn instantiated_graph_lookups_use_job_and_stream_ids() {
+        let graph = graph();
+        let instantiated = InstantiatedGraph::new(
+            "ctx".to_string(),
+            "root".to_string(),
+            HashMap::new(),
+            HashMap::new(),
+            HashMap::new(),
+            &graph,
+        );
+        let reloaded = InstantiatedGraph::from_json(&instantiated.to_json().unwrap()).unwrap();
+
+        for instantiated in [&instantiated, &reloaded] {
+            let job = instantiated.find_job_node_id("[ctx]B[x]").unwrap();
+            assert_eq!(
+                instantiated.node_weight(job).unwrap().node_type,
+                InstantiatedNodeType::Job
+            );
+            assert_eq!(
+                instantiated.find_job_node_id("root"),
+                Some(instantiated.get_root_job_node_id())
+            );
+
+            let stream = instantiated
+                .find_stream_node_id_connected_to_job("[ctx]B[x]", PortDirection::In, "in")
+                .unwrap();
+            let stream_id = instantiated
+                .node_weight(stream)
+                .unwrap()
+                .stream_id
+                .clone()
+                .unwrap();
+            assert_eq!(instantiated.find_stream_node_id(&stream_id), Some(stream));
+            assert_eq!(
+                instantiated.find_stream_node_id_connected_to_job(
+                    "[ctx]B[x]",
+                    PortDirection::In,
+                    "missing"
+                ),
+                None
+            );
+            assert_eq!(instantiated.find_job_node_id("[ctx]Missing"), None);
+        }
+    }
+}
@@ -0,0 +1,147 @@
+use livestack_shared::systems::def_graph::{DefGraph, PortDirection};
+use livestack_shared::systems::def_graph_utils::{FromSpecAndTag, ToSpecAndTag};
+use livestack_shared::systems::graph_render::RenderOptions;
+use livestack_shared::systems::instantiated_graph::InstantiatedGraph;
+use std::collections::HashMap;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Mic -> ASR through a transform, with ASR's input exposed as `transcript`.
+    fn pipeline() -> DefGraph {
+        let mut graph = DefGraph::new("Pipeline".to_string(), vec![], vec![]);
+        graph.add_connected_dual_specs(
+            &FromSpecAndTag {
+                spec_name: "Mic".to_string(),
+                output: "audio".to_string(),
+                unique_spec_label: None,
+            },
+            &ToSpecAndTag {
+                spec_name: "ASR".to_string(),
+                input: "audio".to_string(),
+                has_transform: true,
+                unique_spec_label: None,
+            },
+        );
+        graph.assign_alias("transcript", "ASR", "Pipeline", None, PortDirection::In, "audio");
+        graph
+    }
+
+    fn expanded() -> RenderOptions {
+        RenderOptions::default()
+    }
+
+    fn collapsed() -> RenderOptions {
+        RenderOptions {
+            collapse_ports: true,
+        }
+    }
+
+    #[test]
+    fn def_graph_to_dot() {
+        assert_eq!(
+            pipeline().to_dot(&expanded()),
+            r#"digraph DefGraph {
+  rankdir=LR;
+  n0 [label="Pipeline", shape=doubleoctagon];
+  n1 [label="Mic", shape=box];
+  n2 [label="out: audio", shape=house];
+  n3 [label="Mic/audio>>ASR/audio", shape=ellipse];
+  n4 [label="in: audio (transform)", shape=invhouse];
+  n5 [label="ASR", shape=box];
+  n6 [label="alias in/transcript", shape=note];
+  n1 -> n2;
+  n2 -> n3;
+  n3 -> n4;
+  n4 -> n5;
+  n4 -> n6;
+  n6 -> n0;
+}
+"#
+        );
+    }
+
+    #[test]
+    fn def_graph_to_mermaid() {
+        assert_eq!(
+            pipeline().to_mermaid(&expanded()),
+            r#"flowchart LR
+  n0{{"Pipeline"}}
+  n1["Mic"]
+  n2[\"out: audio"/]
+  n3(["Mic/audio>>ASR/audio"])
+  n4[/"in: audio (transform)"\]
+  n5["ASR"]
+  n6>"alias in/transcript"]
+  n1 --> n2
+  n2 --> n3
+  n3 --> n4
+  n4 --> n5
+  n4 --> n6
+  n6 --> n0
+"#
+        );
+    }
+
+    #[test]
+    fn collapsed_ports_become_edge_labels() {
+        let graph = pipeline();
+
+        assert_eq!(
+            graph.to_dot(&collapsed()),
+            r#"digraph DefGraph {
+  rankdir=LR;
+  n0 [label="Pipeline", shape=doubleoctagon];
+  n1 [label="Mic", shape=box];
+  n3 [label="Mic/audio>>ASR/audio", shape=ellipse];
+  n5 [label="ASR", shape=box];
+  n6 [label="alias in/transcript", shape=note];
+  n6 -> n0;
+  n1 -> n3 [label="audio"];
+  n3 -> n5 [label="audio"];
+  n5 -> n6 [label="audio"];
+}
+"#
+        );
+        assert!(graph
+            .to_mermaid(&collapsed())
+            .contains("  n3 -->|\"audio\"| n5\n"));
+    }
+
+    #[test]
+    fn instantiated_graph_shows_resolved_ids() {
+        let mut overrides = HashMap::new();
+        overrides.insert("in/audio".to_string(), "mic-feed-7".to_string());
+        let instantiated = InstantiatedGraph::new(
+            "ctx".to_string(),
+            "job1".to_string(),
+            overrides,
+            HashMap::new(),
+            HashMap::new(),
+            &pipeline(),
+        );
+
+        let dot = instantiated.to_dot(&collapsed());
+
+        assert!(dot.starts_with("digraph InstantiatedGraph {\n"));
+        assert!(dot.contains("n0 [label=\"job1\", shape=doubleoctagon];"));
+        assert!(dot.contains("[label=\"[ctx]ASR (ASR)\", shape=box];"));
+        assert!(dot.contains("[label=\"mic-feed-7\", shape=ellipse];"));
+        assert!(!dot.contains("house"));
+        let mermaid = instantiated.to_mermaid(&expanded());
+        assert!(mermaid.contains("[/\"in: audio (transform)\"\\]"));
+    }
+
+    #[test]
+    fn labels_are_escaped() {
+        let graph = DefGraph::new("Say \"hi\"".to_string(), vec![], vec![]);
+
+        assert!(graph
+            .to_dot(&expanded())
+            .contains(r#"n0 [label="Say \"hi\"", shape=doubleoctagon];"#));
+        assert!(graph
+            .to_mermaid(&expanded())
+            .contains(r#"n0{{"Say #quot;hi#quot;"}}"#));
+    }
+}
@@ -0,0 +1,227 @@
+use livestack_shared::systems::def_graph::{try_load_from_json, DefGraph, PortDirection};
+use livestack_shared::systems::def_graph_utils::{FromSpecAndTag, SpecTagInfo, ToSpecAndTag};
+use livestack_shared::systems::id_scheme::{
+    BuiltinIdScheme, HashedIds, IdScheme, NamespacedIds, ReadableIds,
+};
+use livestack_shared::systems::instantiated_graph::{InstantiatedGraph, InstantiatedNodeType};
+use std::collections::{BTreeSet, HashMap};
+use std::sync::Arc;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// `Root/request` -> A -> B[fast] -> `Root/reply`.
+    fn pipeline(id_scheme: Arc<dyn IdScheme>) -> DefGraph {
+        let mut graph = DefGraph::new_with_id_scheme(
+            "Root".to_string(),
+            vec!["request".to_string()],
+            vec!["reply".to_string()],
+            id_scheme,
+        );
+        graph.add_connected_dual_specs(
+            &FromSpecAndTag {
+                spec_name: "A".to_string(),
+                output: "out".to_string(),
+                unique_spec_label: None,
+            },
+            &ToSpecAndTag {
+                spec_name: "B".to_string(),
+                input: "in".to_string(),
+                has_transform: false,
+                unique_spec_label: Some("fast".to_string()),
+            },
+        );
+        graph
+    }
+
+    fn instantiate(graph: &DefGraph, id_scheme: Option<Arc<dyn IdScheme>>) -> InstantiatedGraph {
+        let args = (
+            "ctx".to_string(),
+            "root".to_string(),
+            HashMap::new(),
+            HashMap::new(),
+            HashMap::new(),
+        );
+        match id_scheme {
+            Some(id_scheme) => InstantiatedGraph::new_with_id_scheme(
+                args.0, args.1, args.2, args.3, args.4, graph, id_scheme,
+            ),
+            None => InstantiatedGraph::new(args.0, args.1, args.2, args.3, args.4, graph),
+        }
+    }
+
+    /// (job ids, stream ids) of every Job and Stream node.
+    fn ids(graph: &InstantiatedGraph) -> (BTreeSet<String>, BTreeSet<String>) {
+        let (mut jobs, mut streams) = (BTreeSet::new(), BTreeSet::new());
+        for id in graph.node_indices() {
+            let node = graph.node_weight(id).unwrap();
+            match node.node_type {
+                InstantiatedNodeType::Job => jobs.insert(node.job_id.clone().unwrap()),
+                InstantiatedNodeType::Stream => streams.insert(node.stream_id.clone().unwrap()),
+                _ => false,
+            };
+        }
+        (jobs, streams)
+    }
+
+    fn set(ids: &[&str]) -> BTreeSet<String> {
+        ids.iter().map(|id| id.to_string()).collect()
+    }
+
+    #[test]
+    fn the_default_scheme_keeps_readable_ids() {
+        let graph = pipeline(Arc::new(ReadableIds));
+        let instantiated = instantiate(&graph, None);
+
+        assert_eq!(
+            ids(&instantiated),
+            (
+                set(&["[ctx]A", "[ctx]B[fast]"]),
+                set(&[
+                    "[ctx](*)>>Root/request",
+                    "[ctx]A/out>>B(fast)/in",
+                    "[ctx]Root/reply>>(*)",
+                ])
+            )
+        );
+        assert_eq!(
+            ids(&instantiated),
+            ids(&instantiate(&pipeline(Arc::new(ReadableIds)), None))
+        );
+        assert!(instantiated.find_job_node_id("root").is_some());
+    }
+
+    #[test]
+    fn hashed_ids_are_short_distinct_and_deterministic() {
+        let scheme = Arc::new(HashedIds { length: 12 });
+        let graph = pipeline(scheme.clone());
+        let instantiated = instantiate(&graph, None);
+
+        let (jobs, streams) = ids(&instantiated);
+        assert_eq!((jobs.len(), streams.len()), (2, 3));
+        for id in jobs.iter().chain(&streams) {
+            assert_eq!(id.len(), 12, "{}", id);
+            assert!(id.chars().all(|c| c.is_ascii_hexdigit()), "{}", id);
+        }
+        assert_eq!(
+            ids(&instantiate(&pipeline(scheme.clone()), None)),
+            (jobs, streams)
+        );
+        // The StreamDefs of the DefGraph are hashed too.
+        let connections = graph.get_spec_connections();
+        assert_eq!(connections[0].stream_def_id.len(), 12);
+
+        assert_eq!(HashedIds::default().job_id("ctx", "A", None).len(), 16);
+        assert_eq!(HashedIds { length: 0 }.job_id("ctx", "A", None).len(), 1);
+        assert_eq!(HashedIds { length: 99 }.job_id("ctx", "A", None).len(), 64);
+    }
+
+    #[test]
+    fn namespaced_ids_prefix_jobs_and_streams_only() {
+        let graph = pipeline(Arc::new(ReadableIds));
+        let scheme = Arc::new(NamespacedIds {
+            namespace: "7d1c5a0e".to_string(),
+        });
+        let instantiated = instantiate(&graph, Some(scheme));
+
+        let (jobs, streams) = ids(&instantiated);
+        assert_eq!(jobs, set(&["7d1c5a0e:[ctx]A", "7d1c5a0e:[ctx]B[fast]"]));
+        assert!(streams.contains("7d1c5a0e:[ctx]A/out>>B(fast)/in"));
+        assert_eq!(
+            graph.get_spec_connections()[0].stream_def_id,
+            "A/out>>B(fast)/in"
+        );
+        assert!(instantiated.find_job_node_id("root").is_some());
+    }
+
+    #[test]
+    fn any_implementation_can_be_plugged_in() {
+        #[derive(Debug)]
+        struct Dotted;
+        impl IdScheme for Dotted {
+            fn job_id(&self, context_id: &str, spec_name: &str, label: Option<&str>) -> String {
+                format!("{}.{}.{}", context_id, spec_name, label.unwrap_or("-"))
+            }
+        }
+
+        let instantiated = instantiate(&pipeline(Arc::new(ReadableIds)), Some(Arc::new(Dotted)));
+
+        let (jobs, streams) = ids(&instantiated);
+        assert_eq!(jobs, set(&["ctx.A.-", "ctx.B.fast"]));
+        assert!(streams.contains("[ctx]A/out>>B(fast)/in"));
+        assert!(instantiated
+            .find_stream_node_id_connected_to_job("ctx.B.fast", PortDirection::In, "in")
+            .is_some());
+    }
+
+    #[test]
+    fn builtin_schemes_are_saved_and_restored() {
+        for scheme in [
+            BuiltinIdScheme::HashedShort { length: 8 },
+            BuiltinIdScheme::Namespaced {
+                namespace: "p1".to_string(),
+            },
+        ] {
+            let graph = pipeline(scheme.clone().build());
+            let instantiated = instantiate(&graph, None);
+
+            let loaded = try_load_from_json(graph.to_json().unwrap()).unwrap();
+            assert_eq!(loaded.id_scheme().builtin(), Some(scheme.clone()));
+            assert_eq!(ids(&instantiate(&loaded, None)), ids(&instantiated));
+
+            let reloaded = InstantiatedGraph::from_json(&instantiated.to_json().unwrap()).unwrap();
+            assert_eq!(reloaded.id_scheme().builtin(), Some(scheme));
+        }
+    }
+
+    #[test]
+    fn a_loaded_graph_keeps_its_ids_and_takes_a_custom_scheme_again() {
+        #[derive(Debug)]
+        struct Dotted;
+        impl IdScheme for Dotted {
+            fn stream_def_id(
+                &self,
+                from: Option<&SpecTagInfo>,
+                to: Option<&SpecTagInfo>,
+            ) -> String {
+                ReadableIds.stream_def_id(from, to).replace('/', ".")
+            }
+        }
+        let graph = pipeline(Arc::new(Dotted));
+
+        let mut loaded = try_load_from_json(graph.to_json().unwrap()).unwrap();
+        assert_eq!(
+            loaded.get_spec_connections()[0].stream_def_id,
+            graph.get_spec_connections()[0].stream_def_id
+        );
+        assert_eq!(
+            loaded.id_scheme().builtin(),
+            Some(BuiltinIdScheme::Readable)
+        );
+        loaded.set_id_scheme(Arc::new(Dotted));
+        assert_eq!(
+            ids(&instantiate(&loaded, None)),
+            ids(&instantiate(&graph, None))
+        );
+    }
+
+    #[test]
+    fn builtin_schemes_are_selected_by_kind() {
+        let parse = |json: &str| serde_json::from_str::<BuiltinIdScheme>(json).unwrap();
+
+        assert_eq!(parse(r#"{"kind":"readable"}"#), BuiltinIdScheme::Readable);
+        assert_eq!(
+            parse(r#"{"kind":"hashedShort","length":8}"#),
+            BuiltinIdScheme::HashedShort { length: 8 }
+        );
+        let namespaced = parse(r#"{"kind":"namespaced","namespace":"p1"}"#).build();
+        assert_eq!(namespaced.stream_id("ctx", "s"), "p1:[ctx]s");
+        assert_eq!(
+            BuiltinIdScheme::default()
+                .build()
+                .job_id("c", "A", Some("x")),
+            "[c]A[x]"
+        );
+    }
+}
@@ -0,0 +1,232 @@
+mod common;
+
+use common::connect;
+use livestack_shared::systems::def_graph::{DefGraph, DefGraphNode, DefGraphNodeType};
+use livestack_shared::systems::id_scheme::HashedIds;
+use livestack_shared::systems::instantiated_graph::{InstantiatedGraph, InstantiationError};
+use livestack_shared::systems::instantiated_graph_delta::{InletRef, InstantiationDelta};
+use std::collections::HashMap;
+use std::sync::Arc;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A -> B, then whatever `more` adds.
+    fn def_graph(more: &[(&str, &str)]) -> DefGraph {
+        let mut graph = DefGraph::new("Root".to_string(), vec![], vec![]);
+        connect(&mut graph, "A", "out", "B", "in");
+        for (from, to) in more {
+            connect(&mut graph, from, "out", to, "in");
+        }
+        graph
+    }
+
+    fn instantiate(def_graph: &DefGraph) -> InstantiatedGraph {
+        InstantiatedGraph::new(
+            "ctx".to_string(),
+            "root".to_string(),
+            HashMap::new(),
+            HashMap::new(),
+            HashMap::new(),
+            def_graph,
+        )
+    }
+
+    fn inlet(job_id: &str, tag: &str) -> InletRef {
+        InletRef {
+            job_id: job_id.to_string(),
+            tag: tag.to_string(),
+        }
+    }
+
+    fn strings(ids: &[&str]) -> Vec<String> {
+        ids.iter().map(|id| id.to_string()).collect()
+    }
+
+    #[test]
+    fn an_unchanged_graph_gives_an_empty_delta() {
+        let mut graph = instantiate(&def_graph(&[]));
+
+        let delta = graph.apply_def_graph_change(&def_graph(&[])).unwrap();
+
+        assert!(delta.is_empty(), "{:?}", delta);
+    }
+
+    #[test]
+    fn a_new_downstream_spec_is_added_and_its_producer_rewired() {
+        let mut graph = instantiate(&def_graph(&[]));
+
+        let delta = graph
+            .apply_def_graph_change(&def_graph(&[("B", "C")]))
+            .unwrap();
+
+        assert_eq!(
+            delta,
+            InstantiationDelta {
+                added_jobs: strings(&["[ctx]C"]),
+                rewired_jobs: strings(&["[ctx]B"]),
+                added_streams: strings(&["[ctx]B/out>>C/in"]),
+                added_inlets: vec![inlet("[ctx]C", "in")],
+                ..InstantiationDelta::default()
+            }
+        );
+        // The indexes follow the new graph.
+        let c = graph.find_job_node_id("[ctx]C").unwrap();
+        assert_eq!(
+            graph.node_weight(c).unwrap().job_id.as_deref(),
+            Some("[ctx]C")
+        );
+        assert!(graph.find_stream_node_id("[ctx]A/out>>B/in").is_some());
+    }
+
+    #[test]
+    fn removed_specs_take_their_streams_and_inlets_with_them() {
+        let mut graph = instantiate(&def_graph(&[("B", "C")]));
+        let mut smaller = def_graph(&[("B", "C")]);
+        smaller.remove_spec("C", None).unwrap();
+
+        let delta = graph.apply_def_graph_change(&smaller).unwrap();
+
+        assert_eq!(delta.removed_jobs, strings(&["[ctx]C"]));
+        assert_eq!(delta.removed_inlets, vec![inlet("[ctx]C", "in")]);
+        assert!(delta.added_jobs.is_empty());
+        assert!(graph.find_job_node_id("[ctx]C").is_none());
+    }
+
+    #[test]
+    fn an_inlet_fed_by_a_second_producer_is_rewired() {
+        let mut graph = instantiate(&def_graph(&[]));
+
+        let delta = graph
+            .apply_def_graph_change(&def_graph(&[("Z", "B")]))
+            .unwrap();
+
+        assert_eq!(delta.added_jobs, strings(&["[ctx]Z"]));
+        assert_eq!(delta.rewired_jobs, strings(&["[ctx]B"]));
+        assert_eq!(delta.rewired_inlets, vec![inlet("[ctx]B", "in")]);
+    }
+
+    #[test]
+    fn ids_from_the_graphs_scheme_stay_stable() {
+        let mut before = DefGraph::new_with_id_scheme(
+            "Root".to_string(),
+            vec![],
+            vec![],
+            Arc::new(HashedIds::default()),
+        );
+        connect(&mut before, "A", "out", "B", "in");
+        let mut after = before.clone();
+        connect(&mut after, "B", "out", "C", "in");
+        let mut graph = instantiate(&before);
+        let jobs_before: Vec<u32> = graph.get_job_node_ids_in_start_order();
+        let job_ids: Vec<String> = jobs_before
+            .iter()
+            .map(|&id| graph.node_weight(id).unwrap().job_id.clone().unwrap())
+            .collect();
+
+        let delta = graph.apply_def_graph_change(&after).unwrap();
+
+        assert_eq!(delta.added_jobs.len(), 1);
+        assert!(delta.removed_jobs.is_empty());
+        for job_id in &job_ids {
+            assert!(graph.find_job_node_id(job_id).is_some(), "{}", job_id);
+        }
+    }
+
+    #[test]
+    fn job_stream_and_node_ids_survive() {
+        let mut graph = instantiate(&def_graph(&[("B", "C")]));
+        let c_before = graph.find_job_node_id("[ctx]C").unwrap();
+        let stream_before = graph.find_stream_node_id("[ctx]B/out>>C/in").unwrap();
+
+        graph
+            .apply_def_graph_change(&def_graph(&[("Z", "B"), ("B", "C")]))
+            .unwrap();
+
+        let c_after = graph.find_job_node_id("[ctx]C").unwrap();
+        assert_eq!(
+            graph.node_weight(c_after).unwrap().job_id.as_deref(),
+            Some("[ctx]C")
+        );
+        assert_eq!(c_after, c_before);
+        assert_eq!(
+            graph.find_stream_node_id("[ctx]B/out>>C/in"),
+            Some(stream_before)
+        );
+    }
+
+    #[test]
+    fn untouched_jobs_keep_their_node_ids_as_specs_come_and_go() {
+        let mut graph = instantiate(&def_graph(&[]));
+        let node_ids = |graph: &InstantiatedGraph| {
+            (
+                graph.find_job_node_id("[ctx]A").unwrap(),
+                graph.find_job_node_id("[ctx]B").unwrap(),
+                graph.find_stream_node_id("[ctx]A/out>>B/in").unwrap(),
+            )
+        };
+        let before = node_ids(&graph);
+        let fresh = instantiate(&def_graph(&[("B", "C")]));
+
+        graph
+            .apply_def_graph_change(&def_graph(&[("B", "C")]))
+            .unwrap();
+
+        assert_eq!(node_ids(&graph), before);
+        // Same shape as instantiating the new DefGraph from scratch.
+        assert_eq!(graph.node_indices().len(), fresh.node_indices().len());
+        assert_eq!(graph.edge_count(), fresh.edge_count());
+        let c_stream = graph.find_stream_node_id("[ctx]B/out>>C/in").unwrap();
+        let connections = graph.get_nodes_connected_to_stream(c_stream);
+        assert_eq!(
+            connections.source.unwrap().origin.job_id.as_deref(),
+            Some("[ctx]B")
+        );
+        assert_eq!(
+            connections.targets[0].destination.job_id.as_deref(),
+            Some("[ctx]C")
+        );
+
+        graph.apply_def_graph_change(&def_graph(&[])).unwrap();
+
+        assert_eq!(node_ids(&graph), before);
+        assert!(graph.find_job_node_id("[ctx]C").is_none());
+        assert_eq!(graph.edge_count(), instantiate(&def_graph(&[])).edge_count());
+    }
+
+    #[test]
+    fn a_failed_change_leaves_the_graph_as_it_was() {
+        let mut graph = instantiate(&def_graph(&[]));
+        let mut broken = def_graph(&[("B", "C")]);
+        broken.ensure_node(
+            "Broken",
+            DefGraphNode {
+                node_type: DefGraphNodeType::Spec,
+                label: "Broken".to_string(),
+                ..Default::default()
+            },
+        );
+
+        let error = graph.apply_def_graph_change(&broken).unwrap_err();
+
+        assert!(matches!(error, InstantiationError::MissingSpecName { .. }));
+        assert!(graph.find_job_node_id("[ctx]C").is_none());
+        assert!(graph.find_job_node_id("[ctx]B").is_some());
+    }
+
+    #[test]
+    fn the_delta_serializes_with_camel_case_keys() {
+        let mut graph = instantiate(&def_graph(&[]));
+        let delta = graph
+            .apply_def_graph_change(&def_graph(&[("B", "C")]))
+            .unwrap();
+
+        let json = serde_json::to_value(&delta).unwrap();
+        assert_eq!(json["addedJobs"], serde_json::json!(["[ctx]C"]));
+        assert_eq!(
+            json["addedInlets"],
+            serde_json::json!([{ "jobId": "[ctx]C", "tag": "in" }])
+        );
+    }
+}
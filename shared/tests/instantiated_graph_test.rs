@@ -1,6 +1,8 @@
 use std::collections::HashMap;
+use std::sync::Arc;
 use livestack_shared::systems::def_graph::{DefGraph, DefGraphNode, DefGraphNodeType};
-use livestack_shared::systems::instantiated_graph::InstantiatedGraph;
+use livestack_shared::systems::id_scheme::IdScheme;
+use livestack_shared::systems::instantiated_graph::{InstantiatedGraph, InstantiationError};
 
 #[cfg(test)]
 mod tests {
@@ -26,6 +28,7 @@ mod tests {
             alias: None,
             direction: None,
             label: "SpecA".to_string(),
+            ..Default::default()
         };
         let _spec_node_id = def_graph.ensure_node("SpecA", spec_node_data);
 
@@ -204,4 +207,117 @@ mod tests {
             }
         }
     }
+
+    fn node(node_type: DefGraphNodeType, spec_name: Option<&str>, label: &str) -> DefGraphNode {
+        DefGraphNode {
+            node_type,
+            spec_name: spec_name.map(str::to_string),
+            label: label.to_string(),
+            ..Default::default()
+        }
+    }
+
+    fn try_instantiate(def_graph: &DefGraph) -> Result<InstantiatedGraph, InstantiationError> {
+        InstantiatedGraph::try_new(
+            "ctx".to_string(),
+            "rootJob".to_string(),
+            HashMap::new(),
+            HashMap::new(),
+            HashMap::new(),
+            def_graph,
+        )
+    }
+
+    #[test]
+    fn try_new_instantiates_a_well_formed_graph() {
+        let mut def_graph = DefGraph::new("Root".to_string(), vec!["in".to_string()], vec![]);
+        def_graph.ensure_node("SpecA", node(DefGraphNodeType::Spec, Some("SpecA"), "SpecA"));
+
+        let instantiated = try_instantiate(&def_graph).unwrap();
+        assert!(instantiated.find_job_node_id("[ctx]SpecA").is_some());
+        assert_eq!(instantiated.node_indices().len(), def_graph.node_count());
+    }
+
+    #[test]
+    fn try_new_names_a_spec_without_a_name() {
+        let mut def_graph = DefGraph::new("Root".to_string(), vec![], vec![]);
+        let id = def_graph.ensure_node("Broken", node(DefGraphNodeType::Spec, None, "Broken"));
+
+        let error = try_instantiate(&def_graph).unwrap_err();
+        assert_eq!(
+            error,
+            InstantiationError::MissingSpecName {
+                node_id: id,
+                label: "Broken".to_string(),
+            }
+        );
+        assert_eq!(
+            error.to_string(),
+            format!("DefGraph node {} (Broken) is a Spec without a spec_name", id)
+        );
+    }
+
+    #[test]
+    fn try_new_names_a_stream_def_without_an_id() {
+        let mut def_graph = DefGraph::new("Root".to_string(), vec![], vec![]);
+        let id = def_graph.ensure_node("s", node(DefGraphNodeType::StreamDef, None, "s"));
+
+        assert_eq!(
+            try_instantiate(&def_graph).unwrap_err(),
+            InstantiationError::MissingStreamDefId {
+                node_id: id,
+                label: "s".to_string(),
+            }
+        );
+    }
+
+    #[test]
+    fn try_new_rejects_job_ids_that_collide() {
+        #[derive(Debug)]
+        struct OneJob;
+        impl IdScheme for OneJob {
+            fn job_id(&self, context_id: &str, _: &str, _: Option<&str>) -> String {
+                format!("[{}]job", context_id)
+            }
+        }
+
+        let mut def_graph = DefGraph::new("Root".to_string(), vec![], vec![]);
+        def_graph.ensure_node("A", node(DefGraphNodeType::Spec, Some("A"), "A"));
+        let b = def_graph.ensure_node("B", node(DefGraphNodeType::Spec, Some("B"), "B"));
+
+        let error = InstantiatedGraph::try_new_with_id_scheme(
+            "ctx".to_string(),
+            "rootJob".to_string(),
+            HashMap::new(),
+            HashMap::new(),
+            HashMap::new(),
+            &def_graph,
+            Arc::new(OneJob),
+        )
+        .unwrap_err();
+        assert_eq!(
+            error,
+            InstantiationError::DuplicateJobId {
+                node_id: b,
+                label: "B".to_string(),
+                job_id: "[ctx]job".to_string(),
+            }
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "(Broken) is a Spec without a spec_name")]
+    fn new_panics_with_the_instantiation_error() {
+        let mut def_graph = DefGraph::new("Root".to_string(), vec![], vec![]);
+        def_graph.ensure_node("Broken", node(DefGraphNodeType::Spec, None, "Broken"));
+
+        InstantiatedGraph::new(
+            "ctx".to_string(),
+            "rootJob".to_string(),
+            HashMap::new(),
+            HashMap::new(),
+            HashMap::new(),
+            &def_graph,
+        );
+    }
 } 
\ No newline at end of file
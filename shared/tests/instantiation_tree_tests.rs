@@ -0,0 +1,241 @@
+mod common;
+
+use common::{connect, connect_ports, labelled_port, port};
+use livestack_shared::systems::def_graph::{DefGraph, PortDirection};
+use livestack_shared::systems::instantiated_graph::{InstantiatedGraph, InstantiatedNodeType};
+use livestack_shared::systems::instantiation_tree::InstantiationTree;
+use std::collections::{BTreeSet, HashMap};
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// VAD -> ASR, taking `audio` in and giving `text` out.
+    fn voice_pipeline() -> DefGraph {
+        let mut graph = DefGraph::new(
+            "VoicePipeline".to_string(),
+            vec!["audio".to_string()],
+            vec!["text".to_string()],
+        );
+        connect(&mut graph, "VAD", "speech", "ASR", "speech");
+        graph.ensure_inlet_and_stream(port("VAD", "audio"), false);
+        graph.ensure_outlet_and_stream(port("ASR", "text"));
+        graph.assign_alias(
+            "audio",
+            "VAD",
+            "VoicePipeline",
+            None,
+            PortDirection::In,
+            "audio",
+        );
+        graph.assign_alias(
+            "text",
+            "ASR",
+            "VoicePipeline",
+            None,
+            PortDirection::Out,
+            "text",
+        );
+        graph
+    }
+
+    /// Mic -> Middle[outer] -> Chat, where Middle runs VoicePipeline[inner] -> Sink and
+    /// exposes the pipeline's text as `text` too.
+    fn assistant() -> DefGraph {
+        let mut middle = DefGraph::new(
+            "Middle".to_string(),
+            vec!["audio".to_string()],
+            vec!["text".to_string()],
+        );
+        middle
+            .try_add_composite_spec(voice_pipeline(), Some("inner"))
+            .unwrap();
+        connect_ports(
+            &mut middle,
+            &labelled_port("VoicePipeline", Some("inner"), "text"),
+            &port("Sink", "text"),
+            false,
+        )
+        .unwrap();
+        middle.assign_alias(
+            "audio",
+            "VoicePipeline",
+            "Middle",
+            Some("inner"),
+            PortDirection::In,
+            "audio",
+        );
+        middle.assign_alias(
+            "text",
+            "VoicePipeline",
+            "Middle",
+            Some("inner"),
+            PortDirection::Out,
+            "text",
+        );
+
+        let mut outer = DefGraph::new("Assistant".to_string(), vec![], vec![]);
+        outer.try_add_composite_spec(middle, Some("outer")).unwrap();
+        connect_ports(
+            &mut outer,
+            &port("Mic", "pcm"),
+            &labelled_port("Middle", Some("outer"), "audio"),
+            false,
+        )
+        .unwrap();
+        connect_ports(
+            &mut outer,
+            &labelled_port("Middle", Some("outer"), "text"),
+            &port("Chat", "text"),
+            false,
+        )
+        .unwrap();
+        outer
+    }
+
+    fn tree(def_graph: &DefGraph) -> InstantiationTree {
+        InstantiationTree::try_new(
+            "ctx".to_string(),
+            "root".to_string(),
+            HashMap::new(),
+            HashMap::new(),
+            HashMap::new(),
+            def_graph,
+        )
+        .unwrap()
+    }
+
+    /// (job ids, stream ids) of every Job and Stream node.
+    fn ids(graph: &InstantiatedGraph) -> (BTreeSet<String>, BTreeSet<String>) {
+        let (mut jobs, mut streams) = (BTreeSet::new(), BTreeSet::new());
+        for id in graph.node_indices() {
+            let node = graph.node_weight(id).unwrap();
+            match node.node_type {
+                InstantiatedNodeType::Job => jobs.insert(node.job_id.clone().unwrap()),
+                InstantiatedNodeType::Stream => streams.insert(node.stream_id.clone().unwrap()),
+                _ => false,
+            };
+        }
+        (jobs, streams)
+    }
+
+    fn strings(ids: &[&str]) -> BTreeSet<String> {
+        ids.iter().map(|id| id.to_string()).collect()
+    }
+
+    const MIDDLE: &str = "[ctx]Middle[outer]";
+    const PIPELINE: &str = "[[ctx]Middle[outer]]VoicePipeline[inner]";
+
+    #[test]
+    fn each_composite_gets_its_own_level() {
+        let tree = tree(&assistant());
+
+        assert_eq!(
+            ids(&tree.graph).0,
+            strings(&["[ctx]Chat", "[ctx]Mic", MIDDLE])
+        );
+        assert_eq!(tree.children.keys().collect::<Vec<_>>(), vec![MIDDLE]);
+        let middle = &tree.children[MIDDLE];
+        assert_eq!(
+            ids(&middle.graph).0,
+            strings(&["[[ctx]Middle[outer]]Sink", PIPELINE])
+        );
+        let pipeline = &middle.children[PIPELINE];
+        assert!(pipeline.children.is_empty());
+
+        let levels: Vec<(&str, &str, Option<&str>)> = tree
+            .levels()
+            .into_iter()
+            .map(|level| {
+                (
+                    level.context_id(),
+                    level.graph.root_job_id.as_str(),
+                    level.parent_job_id.as_deref(),
+                )
+            })
+            .collect();
+        assert_eq!(
+            levels,
+            vec![
+                ("ctx", "root", None),
+                (MIDDLE, MIDDLE, Some("root")),
+                (PIPELINE, PIPELINE, Some(MIDDLE)),
+            ]
+        );
+    }
+
+    #[test]
+    fn jobs_know_the_job_that_spawned_them() {
+        let tree = tree(&assistant());
+
+        assert_eq!(tree.parent_job_id_of("[ctx]Mic"), Some("root"));
+        assert_eq!(tree.parent_job_id_of(MIDDLE), Some("root"));
+        assert_eq!(
+            tree.parent_job_id_of("[[ctx]Middle[outer]]Sink"),
+            Some(MIDDLE)
+        );
+        assert_eq!(
+            tree.parent_job_id_of("[[[ctx]Middle[outer]]VoicePipeline[inner]]ASR"),
+            Some(PIPELINE)
+        );
+        assert_eq!(tree.parent_job_id_of("root"), None);
+        assert_eq!(tree.parent_job_id_of("[ctx]Nobody"), None);
+    }
+
+    #[test]
+    fn port_streams_are_shared_with_the_level_outside() {
+        let tree = tree(&assistant());
+        let pipeline = &tree.children[MIDDLE].children[PIPELINE];
+        let audio = "[ctx]Mic/pcm>>Middle(outer)/audio";
+
+        // Two levels down, VAD reads the stream Mic writes at the top.
+        let vad_input = pipeline
+            .graph
+            .find_stream_node_id_connected_to_job(
+                "[[[ctx]Middle[outer]]VoicePipeline[inner]]VAD",
+                PortDirection::In,
+                "audio",
+            )
+            .unwrap();
+        assert_eq!(
+            pipeline
+                .graph
+                .node_weight(vad_input)
+                .unwrap()
+                .stream_id
+                .as_deref(),
+            Some(audio)
+        );
+        assert_eq!(pipeline.graph.find_stream_node_id(audio), Some(vad_input));
+        assert!(ids(&pipeline.graph)
+            .1
+            .contains("[ctx]Middle[outer]/text>>Chat/text"));
+
+        assert_eq!(tree.root_job_owning_stream(audio), Some("root"));
+        assert_eq!(
+            tree.root_job_owning_stream(
+                "[[[ctx]Middle[outer]]VoicePipeline[inner]]VAD/speech>>ASR/speech"
+            ),
+            Some(PIPELINE)
+        );
+        assert_eq!(tree.root_job_owning_stream("[ctx]Nowhere"), None);
+    }
+
+    #[test]
+    fn a_graph_without_composites_is_a_single_level() {
+        let pipeline = voice_pipeline();
+        let tree = tree(&pipeline);
+
+        assert!(tree.children.is_empty());
+        assert_eq!(tree.levels().len(), 1);
+        let flat = InstantiatedGraph::new(
+            "ctx".to_string(),
+            "root".to_string(),
+            HashMap::new(),
+            HashMap::new(),
+            HashMap::new(),
+            &pipeline,
+        );
+        assert_eq!(ids(&tree.graph), ids(&flat));
+    }
+}
@@ -0,0 +1,162 @@
+mod common;
+
+use common::{connect_ports, labelled_port};
+use livestack_shared::systems::def_graph::{try_load_from_json, DefGraph, PortDirection};
+use livestack_shared::systems::instantiated_graph::InstantiatedGraph;
+use std::collections::{BTreeSet, HashMap};
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn labels(n: usize) -> Vec<String> {
+        (0..n).map(|i| format!("lang{}", i)).collect()
+    }
+
+    /// One `translate[label]` per label, each fed by its own root input `text_{label}` and
+    /// writing to its own input of `Collect`.
+    fn parallel(labels: &[String]) -> DefGraph {
+        let mut graph = DefGraph::new("Root".to_string(), vec![], vec![]);
+        for label in labels {
+            graph.ensure_inlet_and_stream(labelled_port("translate", Some(label), "text"), false);
+            graph.assign_alias(
+                &format!("text_{}", label),
+                "translate",
+                "Root",
+                Some(label),
+                PortDirection::In,
+                "text",
+            );
+            connect_ports(
+                &mut graph,
+                &labelled_port("translate", Some(label), "out"),
+                &labelled_port("Collect", None, label),
+                false,
+            )
+            .unwrap();
+        }
+        graph
+    }
+
+    fn assert_independent(graph: &DefGraph, labels: &[String]) {
+        assert!(graph.validate().is_empty(), "{:?}", graph.validate());
+        assert_eq!(graph.find_spec_node("translate", None), None);
+
+        let mut specs = BTreeSet::new();
+        let mut streams = BTreeSet::new();
+        for label in labels {
+            let spec = graph.find_spec_node("translate", Some(label)).unwrap();
+            specs.insert(spec);
+            let inbound = graph.get_inbound_stream_nodes(spec).unwrap();
+            let outbound = graph.get_outbound_stream_nodes(spec).unwrap();
+            assert_eq!(
+                (inbound.len(), outbound.len()),
+                (1, 1),
+                "translate[{}]",
+                label
+            );
+            streams.insert(inbound[0].1);
+            streams.insert(outbound[0].1);
+
+            let alias = format!("text_{}", label);
+            assert_eq!(
+                graph.lookup_spec_and_tag_by_alias(alias.clone(), PortDirection::In),
+                Some(labelled_port("translate", Some(label), "text"))
+            );
+            assert_eq!(
+                graph.lookup_root_spec_alias(
+                    "translate".to_string(),
+                    Some(label.clone()),
+                    "text".to_string(),
+                    PortDirection::In
+                ),
+                Some(alias)
+            );
+        }
+        assert_eq!(specs.len(), labels.len());
+        assert_eq!(streams.len(), 2 * labels.len());
+
+        let instantiated = InstantiatedGraph::new(
+            "ctx".to_string(),
+            "root".to_string(),
+            HashMap::new(),
+            HashMap::new(),
+            HashMap::new(),
+            graph,
+        );
+        let mut stream_ids = BTreeSet::new();
+        for label in labels {
+            let job_id = format!("[ctx]translate[{}]", label);
+            assert!(
+                instantiated.find_job_node_id(&job_id).is_some(),
+                "{}",
+                job_id
+            );
+            // The copy's own input, and the input of Collect its output feeds.
+            for (job_id, tag) in [(job_id.as_str(), "text"), ("[ctx]Collect", label.as_str())] {
+                let stream = instantiated
+                    .find_stream_node_id_connected_to_job(job_id, PortDirection::In, tag)
+                    .unwrap();
+                let stream = instantiated.node_weight(stream).unwrap();
+                stream_ids.insert(stream.stream_id.clone().unwrap());
+            }
+        }
+        assert_eq!(stream_ids.len(), 2 * labels.len());
+    }
+
+    #[test]
+    fn two_labelled_copies_are_independent() {
+        let labels = labels(2);
+        assert_independent(&parallel(&labels), &labels);
+    }
+
+    #[test]
+    fn three_labelled_copies_are_independent() {
+        let labels = labels(3);
+        assert_independent(&parallel(&labels), &labels);
+    }
+
+    #[test]
+    fn many_labelled_copies_are_independent_after_a_round_trip() {
+        let labels = labels(25);
+        let graph = parallel(&labels);
+        assert_independent(&graph, &labels);
+
+        let mut reloaded = try_load_from_json(graph.to_json().unwrap()).unwrap();
+        assert_independent(&reloaded, &labels);
+        // The reloaded lookup table still finds each copy's own input stream.
+        let spec = reloaded.find_spec_node("translate", Some("lang7")).unwrap();
+        let (_, stream) = graph.get_inbound_stream_nodes(spec).unwrap()[0];
+        assert_eq!(
+            reloaded
+                .ensure_inlet_and_stream(labelled_port("translate", Some("lang7"), "text"), false)
+                .1,
+            stream
+        );
+    }
+
+    #[test]
+    fn a_labelled_copy_does_not_reuse_another_copys_input_stream() {
+        let mut graph = DefGraph::new("Root".to_string(), vec![], vec![]);
+        connect_ports(
+            &mut graph,
+            &labelled_port("Source", None, "out"),
+            &labelled_port("translate", Some("en"), "text"),
+            false,
+        )
+        .unwrap();
+
+        let (_, en_stream) =
+            graph.ensure_inlet_and_stream(labelled_port("translate", Some("en"), "text"), false);
+        let (_, fr_stream) =
+            graph.ensure_inlet_and_stream(labelled_port("translate", Some("fr"), "text"), false);
+
+        let en = graph.find_spec_node("translate", Some("en")).unwrap();
+        assert_eq!(graph.get_inbound_stream_nodes(en).unwrap()[0].1, en_stream);
+        assert_ne!(fr_stream, en_stream);
+        assert_eq!(
+            graph.get_nodes_connected_to_stream(fr_stream).targets.len(),
+            1
+        );
+    }
+}
@@ -0,0 +1,221 @@
+mod common;
+
+use common::{connect, connect_ports, port};
+use livestack_shared::systems::def_graph::{load_from_json, DefGraph, DefGraphError, PortDirection};
+use livestack_shared::systems::def_graph_validation::DefGraphDiagnostic;
+use livestack_shared::systems::port_schema::check_schema_compatibility;
+use serde_json::{json, Value};
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use assert_matches::assert_matches;
+
+    /// Tokenizer emits strings and Averager takes numbers; each is already wired elsewhere
+    /// so both ports, and their schemas, exist before they are connected to each other.
+    fn mismatched_ports() -> DefGraph {
+        let mut graph = DefGraph::new("Root".to_string(), vec![], vec![]);
+        connect(&mut graph, "Tokenizer", "out", "Logger", "in");
+        connect(&mut graph, "Sensor", "out", "Averager", "in");
+        graph
+            .set_port_schema(
+                &port("Tokenizer", "out"),
+                PortDirection::Out,
+                Some(r#"{"type":"string"}"#.to_string()),
+            )
+            .unwrap();
+        graph
+            .set_port_schema(
+                &port("Averager", "in"),
+                PortDirection::In,
+                Some(r#"{"type":"number"}"#.to_string()),
+            )
+            .unwrap();
+        graph
+    }
+
+    #[test]
+    fn connecting_incompatible_ports_is_rejected() {
+        let mut graph = mismatched_ports();
+        let node_count = graph.node_count();
+
+        let err = connect_ports(
+            &mut graph,
+            &port("Tokenizer", "out"),
+            &port("Averager", "in"),
+            false,
+        )
+        .unwrap_err();
+
+        assert_eq!(
+            err,
+            DefGraphError::IncompatibleSchemas {
+                from: "Tokenizer/out".to_string(),
+                to: "Averager/in".to_string(),
+                reason: "value may be string but must be number".to_string(),
+            }
+        );
+        assert_eq!(graph.node_count(), node_count);
+    }
+
+    #[test]
+    fn transform_connections_skip_the_check() {
+        let mut graph = mismatched_ports();
+
+        assert!(connect_ports(
+            &mut graph,
+            &port("Tokenizer", "out"),
+            &port("Averager", "in"),
+            true
+        )
+        .is_ok());
+    }
+
+    #[test]
+    fn setting_a_schema_that_breaks_a_connection_is_rejected() {
+        let mut graph = DefGraph::new("Root".to_string(), vec![], vec![]);
+        connect(&mut graph, "Users", "out", "Mailer", "in");
+        graph
+            .set_port_schema(
+                &port("Users", "out"),
+                PortDirection::Out,
+                Some(r#"{"type":"object","required":["name"]}"#.to_string()),
+            )
+            .unwrap();
+
+        let err = graph
+            .set_port_schema(
+                &port("Mailer", "in"),
+                PortDirection::In,
+                Some(r#"{"type":"object","required":["email"]}"#.to_string()),
+            )
+            .unwrap_err();
+
+        assert_matches!(err, DefGraphError::IncompatibleSchemas { reason, .. } if reason == "value.email is required but may be missing");
+        assert_eq!(graph.port_schema(&port("Mailer", "in"), PortDirection::In), Ok(None));
+    }
+
+    #[test]
+    fn malformed_schema_is_rejected() {
+        let mut graph = DefGraph::new("Root".to_string(), vec![], vec![]);
+        connect(&mut graph, "A", "out", "B", "in");
+
+        let err = graph
+            .set_port_schema(&port("B", "in"), PortDirection::In, Some("{not json".to_string()))
+            .unwrap_err();
+
+        assert_matches!(err, DefGraphError::InvalidSchema { port, .. } if port == "B/in");
+    }
+
+    #[test]
+    fn validate_reports_mismatch_in_loaded_graph() {
+        let mut graph = DefGraph::new("Root".to_string(), vec![], vec![]);
+        connect(&mut graph, "A", "out", "B", "in");
+        graph
+            .set_port_schema(
+                &port("A", "out"),
+                PortDirection::Out,
+                Some(r#"{"type":"string"}"#.to_string()),
+            )
+            .unwrap();
+        graph
+            .set_port_schema(
+                &port("B", "in"),
+                PortDirection::In,
+                Some(r#"{"type":"string"}"#.to_string()),
+            )
+            .unwrap();
+        assert!(graph.validate().is_empty(), "{:?}", graph.validate());
+
+        // Edit the saved document by hand so the inlet no longer accepts the outlet's values.
+        let json = graph.to_json().unwrap().replace(
+            r#"{\"type\":\"string\"}","label":"B/in""#,
+            r#"{\"type\":\"boolean\"}","label":"B/in""#,
+        );
+        let reloaded = load_from_json(json);
+
+        assert_matches!(
+            reloaded.validate().as_slice(),
+            [DefGraphDiagnostic::IncompatiblePortSchemas { reason, .. }]
+                if reason == "value may be string but must be boolean"
+        );
+    }
+
+    #[test]
+    fn structural_compatibility_rules() {
+        let user = json!({
+            "type": "object",
+            "required": ["id", "name"],
+            "properties": { "id": { "type": "integer" }, "name": { "type": "string" } }
+        });
+        let by_id = json!({
+            "type": "object",
+            "required": ["id"],
+            "properties": { "id": { "type": "number" } }
+        });
+        assert_eq!(check_schema_compatibility(&user, &by_id), Ok(()));
+        assert_eq!(check_schema_compatibility(&user, &json!(true)), Ok(()));
+
+        let closed = json!({
+            "type": "object",
+            "properties": { "id": { "type": "number" } },
+            "additionalProperties": false
+        });
+        assert_eq!(
+            check_schema_compatibility(&user, &closed),
+            Err("value.name is not an accepted property".to_string())
+        );
+
+        let colors = json!({ "enum": ["red", "green"] });
+        assert_eq!(
+            check_schema_compatibility(&json!({ "const": "red" }), &colors),
+            Ok(())
+        );
+        assert_eq!(
+            check_schema_compatibility(&json!({ "enum": ["red", "blue"] }), &colors),
+            Err(r#"value may be "blue" which is not accepted"#.to_string())
+        );
+
+        assert_eq!(
+            check_schema_compatibility(&json!(true), &json!({ "description": "x" })),
+            Ok(())
+        );
+        assert_eq!(
+            check_schema_compatibility(&json!(true), &json!({ "format": "email" })),
+            Ok(())
+        );
+        assert_eq!(
+            check_schema_compatibility(&json!(true), &json!({ "type": "string" })),
+            Err("value may be any type but must be string".to_string())
+        );
+
+        let list = |item: &str| json!({ "type": "array", "items": { "type": item } });
+        assert_eq!(
+            check_schema_compatibility(&list("string"), &list("number")),
+            Err("value[] may be string but must be number".to_string())
+        );
+    }
+
+    #[test]
+    fn outlets_shaped_by_unknown_keywords_are_accepted() {
+        let number = json!({ "type": "number" });
+        let any_of = json!({ "anyOf": [{ "type": "integer" }, { "const": 1.5 }] });
+        assert_eq!(check_schema_compatibility(&any_of, &number), Ok(()));
+        let by_ref = json!({ "$ref": "#/$defs/amount" });
+        assert_eq!(check_schema_compatibility(&by_ref, &number), Ok(()));
+
+        let colors = json!({ "enum": ["red", "green"] });
+        let one_of = json!({ "oneOf": [{ "const": "red" }, { "const": "green" }] });
+        assert_eq!(check_schema_compatibility(&one_of, &colors), Ok(()));
+
+        let closed = json!({ "type": "object", "additionalProperties": false });
+        let all_of = json!({ "type": "object", "allOf": [{ "maxProperties": 0 }] });
+        assert_eq!(check_schema_compatibility(&all_of, &closed), Ok(()));
+
+        let nested = |item: Value| json!({ "type": "array", "items": item });
+        assert_eq!(
+            check_schema_compatibility(&nested(any_of), &nested(number)),
+            Ok(())
+        );
+    }
+}
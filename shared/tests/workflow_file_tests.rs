@@ -0,0 +1,310 @@
+use livestack_shared::systems::def_graph::{DefGraph, PortDirection};
+use livestack_shared::systems::def_graph_utils::{FromSpecAndTag, SpecTagInfo, ToSpecAndTag};
+use livestack_shared::systems::workflow_file::{load_workflow, WorkflowError, WorkflowFormat};
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const MEETING_YAML: &str = "\
+root:
+  name: Meeting
+  inputs: [audio]
+  outputs: [summary]
+specs:
+  - name: Transcriber
+    inputs: [audio]
+  - name: Summarizer
+    label: short
+    outputs: [summary]
+connections:
+  - from: Transcriber/text
+    to: Summarizer[short]/text
+    transform: true
+aliases:
+  - name: audio
+    direction: in
+    port: Transcriber/audio
+  - name: summary
+    direction: out
+    port: Summarizer[short]/summary
+";
+
+    #[test]
+    fn yaml_toml_and_json_compile_to_the_same_graph() {
+        let toml = r#"
+[root]
+name = "Meeting"
+inputs = ["audio"]
+outputs = ["summary"]
+
+[[specs]]
+name = "Transcriber"
+inputs = ["audio"]
+
+[[specs]]
+name = "Summarizer"
+label = "short"
+outputs = ["summary"]
+
+[[connections]]
+from = "Transcriber/text"
+to = "Summarizer[short]/text"
+transform = true
+
+[[aliases]]
+name = "audio"
+direction = "in"
+port = "Transcriber/audio"
+
+[[aliases]]
+name = "summary"
+direction = "out"
+port = "Summarizer[short]/summary"
+"#;
+        let json = r#"{
+  "root": { "name": "Meeting", "inputs": ["audio"], "outputs": ["summary"] },
+  "specs": [
+    { "name": "Transcriber", "inputs": ["audio"] },
+    { "name": "Summarizer", "label": "short", "outputs": ["summary"] }
+  ],
+  "connections": [
+    { "from": "Transcriber/text", "to": "Summarizer[short]/text", "transform": true }
+  ],
+  "aliases": [
+    { "name": "audio", "direction": "in", "port": "Transcriber/audio" },
+    { "name": "summary", "direction": "out", "port": "Summarizer[short]/summary" }
+  ]
+}"#;
+
+        let from_yaml = load_workflow(MEETING_YAML, WorkflowFormat::Yaml).unwrap();
+        let from_toml = load_workflow(toml, WorkflowFormat::Toml).unwrap();
+        let from_json = load_workflow(json, WorkflowFormat::Json).unwrap();
+
+        assert_eq!(from_yaml.fingerprint(), from_toml.fingerprint());
+        assert_eq!(from_yaml.fingerprint(), from_json.fingerprint());
+        assert!(
+            from_yaml.validate().is_empty(),
+            "{:?}",
+            from_yaml.validate()
+        );
+    }
+
+    #[test]
+    fn compiled_graph_matches_imperative_build() {
+        let source = "\
+root:
+  name: Meeting
+  inputs: [audio]
+specs:
+  - name: Transcriber
+    inputs: [audio]
+  - name: Summarizer
+connections:
+  - from: Transcriber/text
+    to: Summarizer/text
+    transform: true
+aliases:
+  - name: audio
+    direction: in
+    port: Transcriber/audio
+";
+        let mut built = DefGraph::new("Meeting".to_string(), vec!["audio".to_string()], vec![]);
+        built.add_connected_dual_specs(
+            &FromSpecAndTag {
+                spec_name: "Transcriber".to_string(),
+                output: "text".to_string(),
+                unique_spec_label: None,
+            },
+            &ToSpecAndTag {
+                spec_name: "Summarizer".to_string(),
+                input: "text".to_string(),
+                has_transform: true,
+                unique_spec_label: None,
+            },
+        );
+        built.ensure_inlet_and_stream(
+            SpecTagInfo {
+                spec_name: "Transcriber".to_string(),
+                unique_spec_label: None,
+                tag: "audio".to_string(),
+            },
+            false,
+        );
+        built.assign_alias("audio", "Transcriber", "Meeting", None, PortDirection::In, "audio");
+
+        let compiled = load_workflow(source, WorkflowFormat::Yaml).unwrap();
+
+        assert!(
+            built.diff(&compiled).is_empty(),
+            "{}",
+            built.diff(&compiled)
+        );
+        assert_eq!(compiled.fingerprint(), built.fingerprint());
+    }
+
+    #[test]
+    fn undeclared_spec_points_at_the_connection() {
+        let source = MEETING_YAML.replace("to: Summarizer[short]/text", "to: Sumarizer/text");
+
+        let err = load_workflow(&source, WorkflowFormat::Yaml).unwrap_err();
+
+        assert_eq!(
+            err,
+            WorkflowError {
+                line: Some(12),
+                column: None,
+                message: "`Sumarizer/text` refers to an undeclared spec".to_string(),
+            }
+        );
+    }
+
+    #[test]
+    fn malformed_port_reference_reports_parser_position() {
+        let source = MEETING_YAML.replace("port: Transcriber/audio", "port: Transcriber");
+
+        let err = load_workflow(&source, WorkflowFormat::Yaml).unwrap_err();
+
+        // The YAML parser reports where the alias entry starts.
+        assert_eq!(err.line, Some(16));
+        assert!(
+            err.message
+                .contains("expected `Spec/tag` or `Spec[label]/tag`, found `Transcriber`"),
+            "{}",
+            err
+        );
+    }
+
+    #[test]
+    fn graph_errors_carry_the_entry_line() {
+        let json = r#"{
+  "root": { "name": "Root" },
+  "specs": [{ "name": "A" }, { "name": "B" }],
+  "connections": [{ "from": "A/out", "to": "B/in" }],
+  "aliases": [
+    { "name": "entry", "direction": "in", "port": "A/in" }
+  ]
+}"#;
+
+        let err = load_workflow(json, WorkflowFormat::Json).unwrap_err();
+
+        assert_eq!(err.line, Some(6));
+        assert_eq!(err.to_string(), format!("line 6: {}", err.message));
+
+        let toml = "[root]\nname = \"Root\"\nunknown = 1\n";
+        let err = load_workflow(toml, WorkflowFormat::Toml).unwrap_err();
+        assert_eq!(err.line, Some(3));
+    }
+
+    #[test]
+    fn alias_errors_point_at_the_alias_even_when_its_port_is_also_connected() {
+        let yaml = "\
+root:
+  name: Root
+specs:
+  - name: A
+  - name: B
+connections:
+  - to: B/in
+    from: A/out
+
+aliases:
+  - name: entry
+    direction: in
+    port: A/out
+";
+
+        let err = load_workflow(yaml, WorkflowFormat::Yaml).unwrap_err();
+        assert_eq!(err.line, Some(11), "{}", err);
+
+        let toml = "\
+[root]
+name = \"Root\"
+
+[[specs]]
+name = \"A\"
+
+[[specs]]
+name = \"B\"
+
+[[connections]]
+from = \"A/out\"
+to = \"B/in\"
+
+[[aliases]]
+name = \"entry\"
+direction = \"in\"
+port = \"A/out\"
+";
+        let err = load_workflow(toml, WorkflowFormat::Toml).unwrap_err();
+        assert_eq!(err.line, Some(14), "{}", err);
+    }
+
+    #[test]
+    fn declared_specs_without_ports_or_connections_are_kept() {
+        let yaml = "\
+root:
+  name: Root
+specs:
+  - name: Standalone
+  - name: Spare
+    label: backup
+";
+
+        let graph = load_workflow(yaml, WorkflowFormat::Yaml).unwrap();
+
+        assert!(graph.find_spec_node("Standalone", None).is_some());
+        assert!(graph.find_spec_node("Spare", Some("backup")).is_some());
+    }
+
+    #[test]
+    fn port_references_use_escaped_identifiers() {
+        let yaml = r"
+root:
+  name: Root
+  outputs: [raw]
+specs:
+  - name: io/reader
+    label: a[1]
+  - name: Sink
+connections:
+  - from: 'io\/reader[a\[1\]]/out\/raw'
+    to: 'Sink/in'
+aliases:
+  - name: raw
+    direction: out
+    port: 'io\/reader[a\[1\]]/out\/raw'
+";
+
+        let graph = load_workflow(yaml, WorkflowFormat::Yaml).unwrap();
+
+        assert_eq!(
+            graph.lookup_root_spec_alias(
+                "io/reader".to_string(),
+                Some("a[1]".to_string()),
+                "out/raw".to_string(),
+                PortDirection::Out,
+            ),
+            Some("raw".to_string())
+        );
+        assert!(graph.validate().is_empty(), "{:?}", graph.validate());
+
+        let unescaped = yaml.replace(r"'io\/reader[a\[1\]]/out\/raw'", "'io/reader[a[1]]/out/raw'");
+        assert!(load_workflow(&unescaped, WorkflowFormat::Yaml).is_err());
+    }
+
+    #[test]
+    fn duplicate_specs_point_at_the_second_declaration() {
+        let json = r#"{
+  "root": { "name": "Root" },
+  "specs": [
+    { "name": "A" },
+    { "name": "A" }
+  ]
+}"#;
+
+        let err = load_workflow(json, WorkflowFormat::Json).unwrap_err();
+
+        assert_eq!(err.line, Some(5), "{}", err);
+    }
+}